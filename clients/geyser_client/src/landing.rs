@@ -0,0 +1,121 @@
+//! Watching transactions land by signature.
+
+use std::collections::HashMap;
+
+use solana_sdk::{signature::Signature, transaction::TransactionError};
+use tokio::time::{timeout_at, Instant};
+
+use crate::{
+    CommitmentLevel, GeyserClient, GeyserResult, GeyserSubscription, GeyserUpdate,
+    SubscriptionBuilder, SubscriptionConfig, SubscriptionEvent, TransactionFilter,
+};
+
+/// Where a watched transaction landed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionLanding {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Position of the transaction in its block.
+    pub index: u64,
+    /// Why the transaction failed, `None` when it succeeded.
+    pub err: Option<TransactionError>,
+}
+
+/// A transaction subscription for a fixed set of signatures.
+///
+/// Opened with [`GeyserClient::watch_signatures`] before the transactions are sent, so
+/// none of them can land unseen.
+pub struct SignatureWatch {
+    subscription: GeyserSubscription,
+    signatures: Vec<Signature>,
+    landed: HashMap<Signature, TransactionLanding>,
+}
+
+impl GeyserClient {
+    /// Subscribes to the transactions with `signatures`, failed ones included.
+    pub async fn watch_signatures(
+        &self,
+        signatures: &[Signature],
+        commitment: CommitmentLevel,
+        config: SubscriptionConfig,
+    ) -> GeyserResult<SignatureWatch> {
+        // A transaction filter matches a single signature
+        let request = signatures
+            .iter()
+            .fold(SubscriptionBuilder::new(), |builder, signature| {
+                builder.transactions(
+                    signature.to_string(),
+                    TransactionFilter::new().signature(*signature).vote(false),
+                )
+            })
+            .commitment(commitment)
+            .build();
+
+        Ok(SignatureWatch {
+            subscription: self.subscribe(request, config).await?,
+            signatures: signatures.to_vec(),
+            landed: HashMap::new(),
+        })
+    }
+}
+
+impl SignatureWatch {
+    /// The next watched transaction to land, `None` once the subscription closed.
+    ///
+    /// Transactions that land during a reconnect are missed.
+    pub async fn next(&mut self) -> Option<TransactionLanding> {
+        loop {
+            let update = match self.subscription.next().await? {
+                SubscriptionEvent::Update {
+                    update: GeyserUpdate::Transaction(update),
+                    ..
+                } => update,
+                SubscriptionEvent::Update { .. } | SubscriptionEvent::Reconnected => continue,
+            };
+
+            if !self.signatures.contains(&update.signature)
+                || self.landed.contains_key(&update.signature)
+            {
+                continue;
+            }
+
+            let landing = TransactionLanding {
+                signature: update.signature,
+                slot: update.slot,
+                index: update.index,
+                err: update.err().cloned(),
+            };
+            self.landed.insert(landing.signature, landing.clone());
+            return Some(landing);
+        }
+    }
+
+    /// Waits until every watched transaction landed, returning them in the order they
+    /// were watched in.
+    ///
+    /// `None` when the deadline passed or the subscription closed first.
+    pub async fn wait_all(&mut self, deadline: Instant) -> Option<Vec<TransactionLanding>> {
+        while !self.is_complete() {
+            timeout_at(deadline, self.next()).await.ok()??;
+        }
+
+        Some(self.landings())
+    }
+
+    /// Whether every watched transaction landed.
+    pub fn is_complete(&self) -> bool {
+        self.landed.len() == self.signatures.len()
+    }
+
+    /// Transactions seen so far, in the order they were watched in.
+    pub fn landings(&self) -> Vec<TransactionLanding> {
+        self.signatures
+            .iter()
+            .filter_map(|signature| self.landed.get(signature).cloned())
+            .collect()
+    }
+
+    pub fn signatures(&self) -> &[Signature] {
+        &self.signatures
+    }
+}
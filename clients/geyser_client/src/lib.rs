@@ -0,0 +1,217 @@
+//! Client for the Yellowstone gRPC `Geyser` service.
+//!
+//! [`connect`] opens a channel, optionally authenticated with an `x-token`. Requests
+//! for [`GeyserClient::subscribe`] come from a [`SubscriptionBuilder`], and the
+//! resulting [`GeyserSubscription`] keeps its stream alive with pings and
+//! resubscribes when it breaks. [`GeyserClient::watch_signatures`] follows a set of
+//! transactions until they land.
+
+use std::str::FromStr;
+
+use solana_sdk::hash::Hash;
+use thiserror::Error;
+use tonic::{
+    codegen::InterceptedService,
+    metadata::{errors::InvalidMetadataValue, AsciiMetadataValue},
+    service::Interceptor,
+    transport::{self, Channel, ClientTlsConfig, Endpoint},
+    Request, Status,
+};
+use yellowstone_grpc_proto::geyser::{
+    geyser_client, GetBlockHeightRequest, GetLatestBlockhashRequest, GetSlotRequest,
+    GetVersionRequest, IsBlockhashValidRequest, PingRequest,
+};
+
+pub mod landing;
+pub mod subscription;
+pub mod updates;
+
+pub use landing::{SignatureWatch, TransactionLanding};
+pub use subscription::{
+    AccountFilter, BlockFilter, GeyserSubscription, SubscriptionBuilder, SubscriptionConfig,
+    SubscriptionEvent, TransactionFilter,
+};
+pub use updates::{
+    AccountUpdate, BlockMetaUpdate, BlockUpdate, GeyserUpdate, SlotUpdate, TransactionUpdate,
+};
+pub use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequest};
+
+const X_TOKEN_HEADER: &str = "x-token";
+
+#[derive(Debug, Error)]
+pub enum GeyserError {
+    #[error("transport error {0}")]
+    Transport(#[from] transport::Error),
+    #[error("grpc error {0}")]
+    Grpc(Box<Status>),
+    #[error("invalid x-token {0}")]
+    InvalidToken(#[from] InvalidMetadataValue),
+    #[error("invalid update {0}")]
+    InvalidUpdate(String),
+    #[error("subscription closed")]
+    Closed,
+}
+
+impl From<Status> for GeyserError {
+    fn from(status: Status) -> Self {
+        GeyserError::Grpc(Box::new(status))
+    }
+}
+
+pub type GeyserResult<T> = Result<T, GeyserError>;
+
+/// Adds the `x-token` header to every request, when there is one.
+#[derive(Clone)]
+pub struct XTokenInterceptor {
+    x_token: Option<AsciiMetadataValue>,
+}
+
+impl Interceptor for XTokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(x_token) = &self.x_token {
+            request
+                .metadata_mut()
+                .insert(X_TOKEN_HEADER, x_token.clone());
+        }
+
+        Ok(request)
+    }
+}
+
+pub type GeyserGrpcClient =
+    geyser_client::GeyserClient<InterceptedService<Channel, XTokenInterceptor>>;
+
+/// Blockhash returned by [`GeyserClient::get_latest_blockhash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatestBlockhash {
+    pub slot: u64,
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
+}
+
+/// Connects to the geyser endpoint at `url`, sending `x_token` with every request.
+pub async fn connect(url: &str, x_token: Option<&str>) -> GeyserResult<GeyserClient> {
+    let x_token = x_token
+        .filter(|x_token| !x_token.is_empty())
+        .map(AsciiMetadataValue::try_from)
+        .transpose()?;
+
+    let mut endpoint = Endpoint::from_shared(url.to_string())?;
+    if url.starts_with("https") {
+        endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+    }
+    let channel = endpoint.connect().await?;
+
+    Ok(GeyserClient {
+        client: geyser_client::GeyserClient::with_interceptor(
+            channel,
+            XTokenInterceptor { x_token },
+        ),
+    })
+}
+
+/// Unary calls and subscriptions over one channel.
+#[derive(Clone)]
+pub struct GeyserClient {
+    client: GeyserGrpcClient,
+}
+
+impl GeyserClient {
+    /// The generated client, for calls this wrapper does not cover.
+    pub fn grpc(&mut self) -> &mut GeyserGrpcClient {
+        &mut self.client
+    }
+
+    /// Round trip to the server, returning the count it echoed.
+    pub async fn ping(&mut self, count: i32) -> GeyserResult<i32> {
+        let pong = self.client.ping(PingRequest { count }).await?.into_inner();
+
+        Ok(pong.count)
+    }
+
+    pub async fn get_latest_blockhash(
+        &mut self,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserResult<LatestBlockhash> {
+        let response = self
+            .client
+            .get_latest_blockhash(GetLatestBlockhashRequest {
+                commitment: commitment.map(|commitment| commitment as i32),
+            })
+            .await?
+            .into_inner();
+
+        Ok(LatestBlockhash {
+            slot: response.slot,
+            blockhash: Hash::from_str(&response.blockhash)
+                .map_err(|e| GeyserError::InvalidUpdate(format!("blockhash: {}", e)))?,
+            last_valid_block_height: response.last_valid_block_height,
+        })
+    }
+
+    pub async fn is_blockhash_valid(
+        &mut self,
+        blockhash: &Hash,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserResult<bool> {
+        let response = self
+            .client
+            .is_blockhash_valid(IsBlockhashValidRequest {
+                blockhash: blockhash.to_string(),
+                commitment: commitment.map(|commitment| commitment as i32),
+            })
+            .await?
+            .into_inner();
+
+        Ok(response.valid)
+    }
+
+    pub async fn get_slot(&mut self, commitment: Option<CommitmentLevel>) -> GeyserResult<u64> {
+        let response = self
+            .client
+            .get_slot(GetSlotRequest {
+                commitment: commitment.map(|commitment| commitment as i32),
+            })
+            .await?
+            .into_inner();
+
+        Ok(response.slot)
+    }
+
+    pub async fn get_block_height(
+        &mut self,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserResult<u64> {
+        let response = self
+            .client
+            .get_block_height(GetBlockHeightRequest {
+                commitment: commitment.map(|commitment| commitment as i32),
+            })
+            .await?
+            .into_inner();
+
+        Ok(response.block_height)
+    }
+
+    pub async fn get_version(&mut self) -> GeyserResult<String> {
+        let response = self
+            .client
+            .get_version(GetVersionRequest {})
+            .await?
+            .into_inner();
+
+        Ok(response.version)
+    }
+
+    /// Opens a subscription for `request`, e.g. from [`SubscriptionBuilder::build`].
+    ///
+    /// Fails when the first stream cannot be opened; later breaks are handled by
+    /// the subscription itself.
+    pub async fn subscribe(
+        &self,
+        request: SubscribeRequest,
+        config: SubscriptionConfig,
+    ) -> GeyserResult<GeyserSubscription> {
+        GeyserSubscription::open(self.client.clone(), request, config).await
+    }
+}
@@ -0,0 +1,558 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{interval, sleep, Instant, MissedTickBehavior},
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::Streaming;
+use yellowstone_grpc_proto::geyser::{
+    subscribe_request_filter_accounts_filter::Filter as AccountsFilterKind,
+    subscribe_request_filter_accounts_filter_memcmp::Data as MemcmpData,
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+    SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
+    SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterMemcmp,
+    SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+    SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdate,
+};
+
+use crate::{updates::GeyserUpdate, GeyserError, GeyserGrpcClient, GeyserResult};
+
+/// Accounts to receive writes for, by address, owner and data.
+///
+/// Addresses and owners are alternatives, the data filters all have to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountFilter {
+    accounts: Vec<Pubkey>,
+    owners: Vec<Pubkey>,
+    data_size: Option<u64>,
+    memcmp: Vec<(u64, Vec<u8>)>,
+    token_accounts: bool,
+}
+
+impl AccountFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(mut self, pubkey: Pubkey) -> Self {
+        self.accounts.push(pubkey);
+        self
+    }
+
+    pub fn accounts(mut self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.accounts.extend(pubkeys);
+        self
+    }
+
+    pub fn owner(mut self, program: Pubkey) -> Self {
+        self.owners.push(program);
+        self
+    }
+
+    pub fn data_size(mut self, size: u64) -> Self {
+        self.data_size = Some(size);
+        self
+    }
+
+    /// Accounts holding `bytes` at `offset` of their data.
+    pub fn memcmp(mut self, offset: u64, bytes: impl Into<Vec<u8>>) -> Self {
+        self.memcmp.push((offset, bytes.into()));
+        self
+    }
+
+    /// Only valid SPL token accounts.
+    pub fn token_accounts(mut self) -> Self {
+        self.token_accounts = true;
+        self
+    }
+}
+
+impl From<AccountFilter> for SubscribeRequestFilterAccounts {
+    fn from(filter: AccountFilter) -> Self {
+        let mut filters: Vec<_> = filter
+            .memcmp
+            .into_iter()
+            .map(|(offset, bytes)| {
+                AccountsFilterKind::Memcmp(SubscribeRequestFilterAccountsFilterMemcmp {
+                    offset,
+                    data: Some(MemcmpData::Bytes(bytes)),
+                })
+            })
+            .collect();
+        if let Some(size) = filter.data_size {
+            filters.push(AccountsFilterKind::Datasize(size));
+        }
+        if filter.token_accounts {
+            filters.push(AccountsFilterKind::TokenAccountState(true));
+        }
+
+        SubscribeRequestFilterAccounts {
+            account: filter.accounts.iter().map(Pubkey::to_string).collect(),
+            owner: filter.owners.iter().map(Pubkey::to_string).collect(),
+            filters: filters
+                .into_iter()
+                .map(|filter| SubscribeRequestFilterAccountsFilter {
+                    filter: Some(filter),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Transactions to receive, by signature or the accounts they touch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionFilter {
+    vote: Option<bool>,
+    failed: Option<bool>,
+    signature: Option<Signature>,
+    include: Vec<Pubkey>,
+    exclude: Vec<Pubkey>,
+    require: Vec<Pubkey>,
+}
+
+impl TransactionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only votes with `true`, no votes with `false`, both by default.
+    pub fn vote(mut self, vote: bool) -> Self {
+        self.vote = Some(vote);
+        self
+    }
+
+    /// Only failed transactions with `true`, only successful ones with `false`.
+    pub fn failed(mut self, failed: bool) -> Self {
+        self.failed = Some(failed);
+        self
+    }
+
+    pub fn signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Transactions touching any of the included accounts.
+    pub fn include(mut self, pubkey: Pubkey) -> Self {
+        self.include.push(pubkey);
+        self
+    }
+
+    pub fn exclude(mut self, pubkey: Pubkey) -> Self {
+        self.exclude.push(pubkey);
+        self
+    }
+
+    /// Transactions touching all of the required accounts.
+    pub fn require(mut self, pubkey: Pubkey) -> Self {
+        self.require.push(pubkey);
+        self
+    }
+}
+
+impl From<TransactionFilter> for SubscribeRequestFilterTransactions {
+    fn from(filter: TransactionFilter) -> Self {
+        SubscribeRequestFilterTransactions {
+            vote: filter.vote,
+            failed: filter.failed,
+            signature: filter.signature.map(|signature| signature.to_string()),
+            account_include: filter.include.iter().map(Pubkey::to_string).collect(),
+            account_exclude: filter.exclude.iter().map(Pubkey::to_string).collect(),
+            account_required: filter.require.iter().map(Pubkey::to_string).collect(),
+        }
+    }
+}
+
+/// Blocks to receive, optionally only those touching some accounts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockFilter {
+    include: Vec<Pubkey>,
+    transactions: Option<bool>,
+    accounts: Option<bool>,
+    entries: Option<bool>,
+}
+
+impl BlockFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include(mut self, pubkey: Pubkey) -> Self {
+        self.include.push(pubkey);
+        self
+    }
+
+    pub fn with_transactions(mut self, transactions: bool) -> Self {
+        self.transactions = Some(transactions);
+        self
+    }
+
+    pub fn with_accounts(mut self, accounts: bool) -> Self {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    pub fn with_entries(mut self, entries: bool) -> Self {
+        self.entries = Some(entries);
+        self
+    }
+}
+
+impl From<BlockFilter> for SubscribeRequestFilterBlocks {
+    fn from(filter: BlockFilter) -> Self {
+        SubscribeRequestFilterBlocks {
+            account_include: filter.include.iter().map(Pubkey::to_string).collect(),
+            include_transactions: filter.transactions,
+            include_accounts: filter.accounts,
+            include_entries: filter.entries,
+        }
+    }
+}
+
+/// Builds a `SubscribeRequest` from named filters.
+///
+/// Every update carries the names of the filters it matched.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionBuilder {
+    request: SubscribeRequest,
+}
+
+impl SubscriptionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn accounts(mut self, name: impl Into<String>, filter: AccountFilter) -> Self {
+        self.request.accounts.insert(name.into(), filter.into());
+        self
+    }
+
+    pub fn transactions(mut self, name: impl Into<String>, filter: TransactionFilter) -> Self {
+        self.request.transactions.insert(name.into(), filter.into());
+        self
+    }
+
+    /// Slot updates, only at the subscription's commitment with `filter_by_commitment`.
+    pub fn slots(mut self, name: impl Into<String>, filter_by_commitment: bool) -> Self {
+        self.request.slots.insert(
+            name.into(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(filter_by_commitment),
+            },
+        );
+        self
+    }
+
+    pub fn blocks(mut self, name: impl Into<String>, filter: BlockFilter) -> Self {
+        self.request.blocks.insert(name.into(), filter.into());
+        self
+    }
+
+    pub fn blocks_meta(mut self, name: impl Into<String>) -> Self {
+        self.request
+            .blocks_meta
+            .insert(name.into(), SubscribeRequestFilterBlocksMeta {});
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.request.commitment = Some(commitment as i32);
+        self
+    }
+
+    /// Sends only `length` bytes from `offset` of account data, may be repeated.
+    pub fn data_slice(mut self, offset: u64, length: u64) -> Self {
+        self.request
+            .accounts_data_slice
+            .push(SubscribeRequestAccountsDataSlice { offset, length });
+        self
+    }
+
+    pub fn build(self) -> SubscribeRequest {
+        self.request
+    }
+}
+
+/// Keepalive and reconnect behaviour of a [`GeyserSubscription`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionConfig {
+    /// How often to ping the server over the stream.
+    pub ping_interval: Duration,
+    /// Resubscribes after this long without any message from the server.
+    pub idle_timeout: Duration,
+    /// First wait before resubscribing, doubled after each failure.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Gives up after this many failed resubscribes in a row, never when `None`.
+    pub max_retries: Option<u32>,
+    /// Updates buffered for a slow reader before the stream is paused.
+    pub buffer: usize,
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retries: None,
+            buffer: 1024,
+        }
+    }
+}
+
+/// What a [`GeyserSubscription`] yields.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEvent {
+    Update {
+        /// Names of the filters the update matched.
+        filters: Vec<String>,
+        update: GeyserUpdate,
+    },
+    /// The stream broke and was replaced, updates in between were missed.
+    Reconnected,
+}
+
+/// A live subscription, resubscribed with the same request whenever the stream
+/// breaks or goes quiet. Stops when dropped.
+pub struct GeyserSubscription {
+    events: mpsc::Receiver<SubscriptionEvent>,
+    requests: mpsc::UnboundedSender<SubscribeRequest>,
+    reconnects: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+type RequestSink = mpsc::UnboundedSender<SubscribeRequest>;
+
+impl GeyserSubscription {
+    pub(crate) async fn open(
+        mut client: GeyserGrpcClient,
+        request: SubscribeRequest,
+        config: SubscriptionConfig,
+    ) -> GeyserResult<Self> {
+        let (sink, stream) = subscribe(&mut client, &request).await?;
+
+        let (events, events_rx) = mpsc::channel(config.buffer.max(1));
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        let reconnects = Arc::new(AtomicUsize::new(0));
+
+        let task = tokio::spawn(run(
+            client,
+            request,
+            config,
+            (sink, stream),
+            events,
+            requests_rx,
+            reconnects.clone(),
+        ));
+
+        Ok(Self {
+            events: events_rx,
+            requests,
+            reconnects,
+            task,
+        })
+    }
+
+    /// The next update, `None` once the subscription gave up.
+    pub async fn next(&mut self) -> Option<SubscriptionEvent> {
+        self.events.recv().await
+    }
+
+    /// Replaces the filters, also for every later resubscribe.
+    pub fn update(&self, request: SubscribeRequest) -> GeyserResult<()> {
+        self.requests.send(request).map_err(|_| GeyserError::Closed)
+    }
+
+    /// How often the stream had to be replaced.
+    pub fn reconnects(&self) -> usize {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for GeyserSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn subscribe(
+    client: &mut GeyserGrpcClient,
+    request: &SubscribeRequest,
+) -> GeyserResult<(RequestSink, Streaming<SubscribeUpdate>)> {
+    let (sink, requests) = mpsc::unbounded_channel();
+    sink.send(request.clone())
+        .map_err(|_| GeyserError::Closed)?;
+
+    let stream = client
+        .subscribe(UnboundedReceiverStream::new(requests))
+        .await?
+        .into_inner();
+
+    Ok((sink, stream))
+}
+
+/// Why a stream was given up.
+enum StreamEnd {
+    /// The subscription handle was dropped.
+    Closed,
+    Broken(String),
+}
+
+async fn run(
+    mut client: GeyserGrpcClient,
+    mut request: SubscribeRequest,
+    config: SubscriptionConfig,
+    mut connection: (RequestSink, Streaming<SubscribeUpdate>),
+    events: mpsc::Sender<SubscriptionEvent>,
+    mut requests: mpsc::UnboundedReceiver<SubscribeRequest>,
+    reconnects: Arc<AtomicUsize>,
+) {
+    loop {
+        let reason = match forward(
+            &mut connection,
+            &mut request,
+            &config,
+            &events,
+            &mut requests,
+        )
+        .await
+        {
+            StreamEnd::Closed => return,
+            StreamEnd::Broken(reason) => reason,
+        };
+        warn!("Geyser stream broke ({}), resubscribing", reason);
+
+        let mut failures = 0;
+        connection = loop {
+            let backoff = config
+                .initial_backoff
+                .saturating_mul(1 << failures.min(16))
+                .min(config.max_backoff);
+            sleep(backoff).await;
+            if events.is_closed() {
+                return;
+            }
+
+            match subscribe(&mut client, &request).await {
+                Ok(connection) => break connection,
+                Err(e) => {
+                    failures += 1;
+                    warn!(
+                        "Resubscribing to geyser failed ({} in a row): {}",
+                        failures, e
+                    );
+                    if config.max_retries.is_some_and(|max| failures >= max) {
+                        return;
+                    }
+                }
+            }
+        };
+
+        reconnects.fetch_add(1, Ordering::Relaxed);
+        info!("Resubscribed to geyser");
+        if events.send(SubscriptionEvent::Reconnected).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Forwards updates until the stream breaks, pinging the server along the way.
+async fn forward(
+    (sink, stream): &mut (RequestSink, Streaming<SubscribeUpdate>),
+    request: &mut SubscribeRequest,
+    config: &SubscriptionConfig,
+    events: &mpsc::Sender<SubscriptionEvent>,
+    requests: &mut mpsc::UnboundedReceiver<SubscribeRequest>,
+) -> StreamEnd {
+    let mut ping = interval(config.ping_interval);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ping.reset();
+    let mut ping_id = 0;
+    let mut last_message = Instant::now();
+
+    loop {
+        tokio::select! {
+            message = stream.message() => {
+                let update = match message {
+                    Ok(Some(update)) => update,
+                    Ok(None) => return StreamEnd::Broken("stream ended".to_string()),
+                    Err(status) => return StreamEnd::Broken(status.to_string()),
+                };
+                last_message = Instant::now();
+
+                let Some(update_oneof) = update.update_oneof else {
+                    continue;
+                };
+                // The server pings to keep proxies from closing the stream, answering keeps
+                // it open from our side as well
+                if let UpdateOneof::Ping(_) = update_oneof {
+                    ping_id += 1;
+                    if sink.send(ping_request(ping_id)).is_err() {
+                        return StreamEnd::Broken("request stream closed".to_string());
+                    }
+                    continue;
+                }
+
+                match GeyserUpdate::decode(update_oneof) {
+                    Ok(Some(decoded)) => {
+                        let event = SubscriptionEvent::Update {
+                            filters: update.filters,
+                            update: decoded,
+                        };
+                        if events.send(event).await.is_err() {
+                            return StreamEnd::Closed;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Skipping geyser update: {}", e),
+                }
+            }
+            _ = ping.tick() => {
+                if last_message.elapsed() >= config.idle_timeout {
+                    return StreamEnd::Broken(format!(
+                        "nothing received for {:?}",
+                        last_message.elapsed()
+                    ));
+                }
+                ping_id += 1;
+                if sink.send(ping_request(ping_id)).is_err() {
+                    return StreamEnd::Broken("request stream closed".to_string());
+                }
+            }
+            new_request = requests.recv() => {
+                let Some(new_request) = new_request else {
+                    return StreamEnd::Closed;
+                };
+                if sink.send(new_request.clone()).is_err() {
+                    *request = new_request;
+                    return StreamEnd::Broken("request stream closed".to_string());
+                }
+                *request = new_request;
+            }
+        }
+    }
+}
+
+fn ping_request(id: i32) -> SubscribeRequest {
+    SubscribeRequest {
+        ping: Some(SubscribeRequestPing { id }),
+        ..SubscribeRequest::default()
+    }
+}
@@ -0,0 +1,186 @@
+use solana_sdk::{
+    account::Account,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::{ConfirmedBlock, TransactionStatusMeta};
+use yellowstone_grpc_proto::{
+    convert_from,
+    geyser::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeUpdateAccount,
+        SubscribeUpdateBlockMeta, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+    },
+};
+
+use crate::{GeyserError, GeyserResult};
+
+/// An account write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountUpdate {
+    pub slot: u64,
+    pub pubkey: Pubkey,
+    pub account: Account,
+    pub write_version: u64,
+    /// Transaction that wrote the account, `None` for startup snapshots.
+    pub txn_signature: Option<Signature>,
+    /// Sent while the server loads its snapshot, before live updates.
+    pub is_startup: bool,
+}
+
+/// A transaction executed in `slot`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionUpdate {
+    pub slot: u64,
+    pub signature: Signature,
+    pub is_vote: bool,
+    /// Position of the transaction in its block.
+    pub index: u64,
+    pub transaction: VersionedTransaction,
+    pub meta: TransactionStatusMeta,
+}
+
+impl TransactionUpdate {
+    /// Why the transaction failed, `None` when it succeeded.
+    pub fn err(&self) -> Option<&TransactionError> {
+        self.meta.status.as_ref().err()
+    }
+}
+
+/// A slot reaching `status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotUpdate {
+    pub slot: u64,
+    pub parent: Option<u64>,
+    pub status: CommitmentLevel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockUpdate {
+    pub slot: u64,
+    pub block: ConfirmedBlock,
+}
+
+/// A block without its transactions and accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockMetaUpdate {
+    pub slot: u64,
+    pub blockhash: String,
+    pub parent_slot: u64,
+    pub parent_blockhash: String,
+    pub block_height: Option<u64>,
+    pub block_time: Option<i64>,
+    pub executed_transaction_count: u64,
+}
+
+/// A decoded `SubscribeUpdate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeyserUpdate {
+    Account(AccountUpdate),
+    Transaction(Box<TransactionUpdate>),
+    Slot(SlotUpdate),
+    Block(BlockUpdate),
+    BlockMeta(BlockMetaUpdate),
+}
+
+impl GeyserUpdate {
+    /// Decodes `update`, `None` for pings, pongs and entries.
+    pub fn decode(update: UpdateOneof) -> GeyserResult<Option<Self>> {
+        let update = match update {
+            UpdateOneof::Account(account) => GeyserUpdate::Account(decode_account(account)?),
+            UpdateOneof::Transaction(transaction) => {
+                GeyserUpdate::Transaction(Box::new(decode_transaction(transaction)?))
+            }
+            UpdateOneof::Slot(slot) => GeyserUpdate::Slot(decode_slot(slot)),
+            UpdateOneof::Block(block) => GeyserUpdate::Block(BlockUpdate {
+                slot: block.slot,
+                block: convert_from::create_block(block).map_err(GeyserError::InvalidUpdate)?,
+            }),
+            UpdateOneof::BlockMeta(block_meta) => {
+                GeyserUpdate::BlockMeta(decode_block_meta(block_meta))
+            }
+            UpdateOneof::Ping(_) | UpdateOneof::Pong(_) | UpdateOneof::Entry(_) => return Ok(None),
+        };
+
+        Ok(Some(update))
+    }
+
+    pub fn slot(&self) -> u64 {
+        match self {
+            GeyserUpdate::Account(update) => update.slot,
+            GeyserUpdate::Transaction(update) => update.slot,
+            GeyserUpdate::Slot(update) => update.slot,
+            GeyserUpdate::Block(update) => update.slot,
+            GeyserUpdate::BlockMeta(update) => update.slot,
+        }
+    }
+}
+
+fn decode_account(update: SubscribeUpdateAccount) -> GeyserResult<AccountUpdate> {
+    let info = update
+        .account
+        .ok_or_else(|| GeyserError::InvalidUpdate("account update without account".into()))?;
+    let write_version = info.write_version;
+    let txn_signature = info
+        .txn_signature
+        .as_deref()
+        .map(Signature::try_from)
+        .transpose()
+        .map_err(|_| GeyserError::InvalidUpdate("invalid transaction signature".into()))?;
+    let (pubkey, account) =
+        convert_from::create_account(info).map_err(GeyserError::InvalidUpdate)?;
+
+    Ok(AccountUpdate {
+        slot: update.slot,
+        pubkey,
+        account,
+        write_version,
+        txn_signature,
+        is_startup: update.is_startup,
+    })
+}
+
+fn decode_transaction(update: SubscribeUpdateTransaction) -> GeyserResult<TransactionUpdate> {
+    let info = update.transaction.ok_or_else(|| {
+        GeyserError::InvalidUpdate("transaction update without transaction".into())
+    })?;
+    let signature = Signature::try_from(info.signature.as_slice())
+        .map_err(|_| GeyserError::InvalidUpdate("invalid transaction signature".into()))?;
+    let transaction = info
+        .transaction
+        .ok_or_else(|| GeyserError::InvalidUpdate("transaction update without message".into()))
+        .and_then(|tx| convert_from::create_tx_versioned(tx).map_err(GeyserError::InvalidUpdate))?;
+    let meta = info
+        .meta
+        .ok_or_else(|| GeyserError::InvalidUpdate("transaction update without meta".into()))
+        .and_then(|meta| convert_from::create_tx_meta(meta).map_err(GeyserError::InvalidUpdate))?;
+
+    Ok(TransactionUpdate {
+        slot: update.slot,
+        signature,
+        is_vote: info.is_vote,
+        index: info.index,
+        transaction,
+        meta,
+    })
+}
+
+fn decode_slot(update: SubscribeUpdateSlot) -> SlotUpdate {
+    SlotUpdate {
+        slot: update.slot,
+        parent: update.parent,
+        status: CommitmentLevel::try_from(update.status).unwrap_or(CommitmentLevel::Processed),
+    }
+}
+
+fn decode_block_meta(update: SubscribeUpdateBlockMeta) -> BlockMetaUpdate {
+    BlockMetaUpdate {
+        slot: update.slot,
+        blockhash: update.blockhash,
+        parent_slot: update.parent_slot,
+        parent_blockhash: update.parent_blockhash,
+        block_height: update.block_height.map(|height| height.block_height),
+        block_time: update.block_time.map(|time| time.timestamp),
+        executed_transaction_count: update.executed_transaction_count,
+    }
+}
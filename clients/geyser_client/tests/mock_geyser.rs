@@ -0,0 +1,345 @@
+use std::time::Duration;
+
+use geyser_client::{
+    connect, AccountFilter, CommitmentLevel, GeyserError, GeyserSubscription, GeyserUpdate,
+    SubscriptionBuilder, SubscriptionConfig, SubscriptionEvent, TransactionFilter,
+};
+use mock_geyser::{
+    account_update, slot_update, transaction_update, transaction_update_at, MockGeyser,
+    MockGeyserConfig,
+};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, system_transaction,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use tokio::time::{sleep, timeout};
+use tonic::Code;
+
+fn transfer(payer: &Keypair) -> VersionedTransaction {
+    system_transaction::transfer(payer, &Keypair::new().pubkey(), 1, Hash::default()).into()
+}
+
+fn account(owner: Pubkey, lamports: u64) -> Account {
+    Account {
+        lamports,
+        data: vec![1, 2, 3],
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Waits until the mock saw `count` subscribe requests, so emitted updates are filtered.
+async fn wait_for_requests(geyser: &MockGeyser, count: usize) {
+    timeout(Duration::from_secs(5), async {
+        while geyser.requests().len() < count {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("subscribe request never arrived");
+}
+
+async fn next_update(subscription: &mut GeyserSubscription) -> (Vec<String>, GeyserUpdate) {
+    match timeout(Duration::from_secs(5), subscription.next()).await {
+        Ok(Some(SubscriptionEvent::Update { filters, update })) => (filters, update),
+        other => panic!("expected an update, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unary_calls() {
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let blockhash = MockGeyserConfig::default().blockhash;
+    let mut client = connect(&geyser.url(), None).await.unwrap();
+
+    assert_eq!(client.ping(7).await.unwrap(), 7);
+    assert_eq!(client.get_version().await.unwrap(), "mock-geyser");
+    assert_eq!(client.get_block_height(None).await.unwrap(), 900);
+
+    geyser.set_slot(1_234);
+    assert_eq!(
+        client
+            .get_slot(Some(CommitmentLevel::Confirmed))
+            .await
+            .unwrap(),
+        1_234
+    );
+
+    let latest = client.get_latest_blockhash(None).await.unwrap();
+    assert_eq!(latest.slot, 1_234);
+    assert_eq!(latest.last_valid_block_height, 1_050);
+    assert!(client
+        .is_blockhash_valid(&latest.blockhash, None)
+        .await
+        .unwrap());
+    assert!(!client.is_blockhash_valid(&blockhash, None).await.unwrap());
+}
+
+#[tokio::test]
+async fn requires_x_token() {
+    let geyser = MockGeyser::start(MockGeyserConfig {
+        x_token: Some("secret".to_string()),
+        ..MockGeyserConfig::default()
+    })
+    .await
+    .unwrap();
+
+    let mut anonymous = connect(&geyser.url(), None).await.unwrap();
+    match anonymous.ping(1).await {
+        Err(GeyserError::Grpc(status)) => assert_eq!(status.code(), Code::Unauthenticated),
+        other => panic!("expected unauthenticated, got {:?}", other),
+    }
+    assert!(anonymous
+        .subscribe(
+            SubscriptionBuilder::new().build(),
+            SubscriptionConfig::default()
+        )
+        .await
+        .is_err());
+
+    let mut authenticated = connect(&geyser.url(), Some("secret")).await.unwrap();
+    assert_eq!(authenticated.ping(1).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn builder_sends_typed_filters() {
+    let watched = Pubkey::new_unique();
+    let signature = Keypair::new().sign_message(b"tx");
+
+    let request = SubscriptionBuilder::new()
+        .accounts(
+            "pool",
+            AccountFilter::new()
+                .account(watched)
+                .data_size(752)
+                .memcmp(0, vec![6]),
+        )
+        .transactions(
+            "launch",
+            TransactionFilter::new().signature(signature).vote(false),
+        )
+        .slots("slots", true)
+        .commitment(CommitmentLevel::Confirmed)
+        .data_slice(0, 32)
+        .build();
+
+    assert_eq!(request.commitment, Some(CommitmentLevel::Confirmed as i32));
+    assert_eq!(request.accounts["pool"].account, vec![watched.to_string()]);
+    assert_eq!(request.accounts["pool"].filters.len(), 2);
+    assert_eq!(
+        request.transactions["launch"].signature,
+        Some(signature.to_string())
+    );
+    assert_eq!(request.transactions["launch"].vote, Some(false));
+    assert_eq!(request.slots["slots"].filter_by_commitment, Some(true));
+    assert_eq!(request.accounts_data_slice.len(), 1);
+
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let client = connect(&geyser.url(), None).await.unwrap();
+    let _subscription = client
+        .subscribe(request.clone(), SubscriptionConfig::default())
+        .await
+        .unwrap();
+    wait_for_requests(&geyser, 1).await;
+
+    assert_eq!(geyser.requests(), vec![request]);
+}
+
+#[tokio::test]
+async fn delivers_matching_updates_only() {
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let client = connect(&geyser.url(), None).await.unwrap();
+
+    let watched = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let mut subscription = client
+        .subscribe(
+            SubscriptionBuilder::new()
+                .accounts("watched", AccountFilter::new().account(watched))
+                .transactions("payer", TransactionFilter::new().include(payer.pubkey()))
+                .build(),
+            SubscriptionConfig::default(),
+        )
+        .await
+        .unwrap();
+    wait_for_requests(&geyser, 1).await;
+
+    geyser.emit(account_update(
+        &Pubkey::new_unique(),
+        &account(system_program::id(), 1),
+        10,
+    ));
+    geyser.emit(slot_update(10, CommitmentLevel::Processed));
+    geyser.emit(account_update(
+        &watched,
+        &account(system_program::id(), 5),
+        11,
+    ));
+
+    let (filters, update) = next_update(&mut subscription).await;
+    assert_eq!(filters, vec!["watched".to_string()]);
+    match update {
+        GeyserUpdate::Account(update) => {
+            assert_eq!(update.pubkey, watched);
+            assert_eq!(update.slot, 11);
+            assert_eq!(update.account, account(system_program::id(), 5));
+        }
+        other => panic!("expected an account update, got {:?}", other),
+    }
+
+    let failed = transfer(&payer);
+    let err = TransactionError::InstructionError(0, InstructionError::Custom(1));
+    geyser.emit(transaction_update(&transfer(&Keypair::new()), 12, None));
+    geyser.emit(transaction_update(&failed, 12, Some(err.clone())));
+
+    let (filters, update) = next_update(&mut subscription).await;
+    assert_eq!(filters, vec!["payer".to_string()]);
+    match update {
+        GeyserUpdate::Transaction(update) => {
+            assert_eq!(update.signature, failed.signatures[0]);
+            assert_eq!(update.slot, 12);
+            assert_eq!(update.transaction, failed);
+            assert_eq!(update.err(), Some(&err));
+        }
+        other => panic!("expected a transaction update, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn resubscribes_after_disconnect() {
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let client = connect(&geyser.url(), None).await.unwrap();
+
+    let request = SubscriptionBuilder::new().slots("slots", false).build();
+    let mut subscription = client
+        .subscribe(
+            request.clone(),
+            SubscriptionConfig {
+                initial_backoff: Duration::from_millis(10),
+                ..SubscriptionConfig::default()
+            },
+        )
+        .await
+        .unwrap();
+    wait_for_requests(&geyser, 1).await;
+
+    geyser.disconnect_subscribers();
+    assert_eq!(
+        timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .unwrap(),
+        Some(SubscriptionEvent::Reconnected)
+    );
+    wait_for_requests(&geyser, 2).await;
+    assert_eq!(geyser.subscriptions(), 2);
+    assert_eq!(geyser.requests()[1], request);
+    assert_eq!(subscription.reconnects(), 1);
+
+    geyser.emit(slot_update(20, CommitmentLevel::Confirmed));
+    let (_, update) = next_update(&mut subscription).await;
+    assert_eq!(update.slot(), 20);
+}
+
+#[tokio::test]
+async fn answers_pings_and_resubscribes_when_idle() {
+    let geyser = MockGeyser::start(MockGeyserConfig {
+        ping_interval: Some(Duration::from_millis(50)),
+        ..MockGeyserConfig::default()
+    })
+    .await
+    .unwrap();
+    let client = connect(&geyser.url(), None).await.unwrap();
+
+    let mut subscription = client
+        .subscribe(
+            SubscriptionBuilder::new().slots("slots", false).build(),
+            SubscriptionConfig {
+                ping_interval: Duration::from_millis(100),
+                idle_timeout: Duration::from_millis(300),
+                initial_backoff: Duration::from_millis(10),
+                ..SubscriptionConfig::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    // Server pings keep the stream alive and get answered
+    sleep(Duration::from_millis(500)).await;
+    assert!(geyser.pings() >= 3, "only {} pings", geyser.pings());
+    assert_eq!(subscription.reconnects(), 0);
+
+    geyser.pause(true);
+    sleep(Duration::from_millis(500)).await;
+    geyser.pause(false);
+
+    assert_eq!(
+        timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .unwrap(),
+        Some(SubscriptionEvent::Reconnected)
+    );
+    assert!(geyser.subscriptions() >= 2);
+}
+
+#[tokio::test]
+async fn watches_signatures_until_all_land() {
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let client = connect(&geyser.url(), None).await.unwrap();
+
+    let bundle = [transfer(&Keypair::new()), transfer(&Keypair::new())];
+    let signatures: Vec<_> = bundle.iter().map(|tx| tx.signatures[0]).collect();
+    let mut watch = client
+        .watch_signatures(
+            &signatures,
+            CommitmentLevel::Processed,
+            SubscriptionConfig::default(),
+        )
+        .await
+        .unwrap();
+    wait_for_requests(&geyser, 1).await;
+
+    let err = TransactionError::InstructionError(0, InstructionError::Custom(1));
+    geyser.emit(transaction_update(&transfer(&Keypair::new()), 30, None));
+    geyser.emit(transaction_update_at(&bundle[1], 30, 8, Some(err.clone())));
+    geyser.emit(transaction_update_at(&bundle[0], 30, 7, None));
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let landings = watch.wait_all(deadline).await.unwrap();
+    assert_eq!(
+        landings.iter().map(|l| l.signature).collect::<Vec<_>>(),
+        signatures
+    );
+    assert!(landings.iter().all(|landing| landing.slot == 30));
+    assert_eq!(landings[0].index, 7);
+    assert_eq!(landings[1].index, 8);
+    assert_eq!(landings[0].err, None);
+    assert_eq!(landings[1].err, Some(err));
+    assert!(watch.is_complete());
+
+    let mut pending = client
+        .watch_signatures(
+            &[Keypair::new().sign_message(b"never")],
+            CommitmentLevel::Processed,
+            SubscriptionConfig::default(),
+        )
+        .await
+        .unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(200);
+    assert_eq!(pending.wait_all(deadline).await, None);
+}
@@ -0,0 +1,129 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use jito_protos::auth::{
+    auth_service_server::AuthService, GenerateAuthChallengeRequest, GenerateAuthChallengeResponse,
+    GenerateAuthTokensRequest, GenerateAuthTokensResponse, RefreshAccessTokenRequest,
+    RefreshAccessTokenResponse, Token,
+};
+use prost_types::Timestamp;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tonic::{Request, Response, Status};
+
+use crate::State;
+
+pub(crate) struct MockAuthService {
+    pub(crate) state: Arc<State>,
+}
+
+#[allow(clippy::result_large_err)]
+fn pubkey_from_bytes(bytes: &[u8]) -> Result<Pubkey, Status> {
+    Pubkey::try_from(bytes).map_err(|_| Status::invalid_argument("pubkey must be 32 bytes"))
+}
+
+impl MockAuthService {
+    fn token(&self, value: String, ttl: Duration) -> Token {
+        Token {
+            value,
+            expires_at_utc: (!self.state.config.omit_token_expiry)
+                .then(|| Timestamp::from(SystemTime::now() + ttl)),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl AuthService for MockAuthService {
+    async fn generate_auth_challenge(
+        &self,
+        request: Request<GenerateAuthChallengeRequest>,
+    ) -> Result<Response<GenerateAuthChallengeResponse>, Status> {
+        let pubkey = pubkey_from_bytes(&request.into_inner().pubkey)?;
+
+        let mut auth = self.state.auth.lock().unwrap();
+        auth.counter += 1;
+        let challenge = format!("challenge-{}", auth.counter);
+        auth.challenges.insert(pubkey, challenge.clone());
+
+        Ok(Response::new(GenerateAuthChallengeResponse { challenge }))
+    }
+
+    async fn generate_auth_tokens(
+        &self,
+        request: Request<GenerateAuthTokensRequest>,
+    ) -> Result<Response<GenerateAuthTokensResponse>, Status> {
+        let request = request.into_inner();
+        let pubkey = pubkey_from_bytes(&request.client_pubkey)?;
+        let signature = Signature::try_from(request.signed_challenge.as_slice())
+            .map_err(|_| Status::invalid_argument("signature must be 64 bytes"))?;
+
+        let mut auth = self.state.auth.lock().unwrap();
+
+        // Clients sign "<pubkey>-<challenge>", see ClientInterceptor::auth
+        let expected = auth
+            .challenges
+            .remove(&pubkey)
+            .map(|challenge| format!("{}-{}", pubkey, challenge))
+            .ok_or_else(|| Status::permission_denied("no challenge issued for this pubkey"))?;
+        if request.challenge != expected {
+            return Err(Status::permission_denied("challenge mismatch"));
+        }
+        if !signature.verify(pubkey.as_ref(), request.challenge.as_bytes()) {
+            return Err(Status::permission_denied("invalid challenge signature"));
+        }
+
+        auth.counter += 1;
+        let access_token = self.token(
+            format!("access-{}", auth.counter),
+            self.state.config.access_token_ttl,
+        );
+        let refresh_token = self.token(
+            format!("refresh-{}", auth.counter),
+            self.state.config.refresh_token_ttl,
+        );
+        auth.access_tokens.insert(
+            access_token.value.clone(),
+            SystemTime::now() + self.state.config.access_token_ttl,
+        );
+        auth.refresh_tokens.insert(
+            refresh_token.value.clone(),
+            SystemTime::now() + self.state.config.refresh_token_ttl,
+        );
+        auth.full_auths += 1;
+
+        Ok(Response::new(GenerateAuthTokensResponse {
+            access_token: Some(access_token),
+            refresh_token: Some(refresh_token),
+        }))
+    }
+
+    async fn refresh_access_token(
+        &self,
+        request: Request<RefreshAccessTokenRequest>,
+    ) -> Result<Response<RefreshAccessTokenResponse>, Status> {
+        let refresh_token = request.into_inner().refresh_token;
+
+        let mut auth = self.state.auth.lock().unwrap();
+        match auth.refresh_tokens.get(&refresh_token) {
+            Some(expires_at) if *expires_at > SystemTime::now() => {}
+            Some(_) => return Err(Status::unauthenticated("refresh token expired")),
+            None => return Err(Status::unauthenticated("unknown refresh token")),
+        }
+
+        auth.counter += 1;
+        let access_token = self.token(
+            format!("access-{}", auth.counter),
+            self.state.config.access_token_ttl,
+        );
+        auth.access_tokens.insert(
+            access_token.value.clone(),
+            SystemTime::now() + self.state.config.access_token_ttl,
+        );
+        auth.refreshes += 1;
+
+        Ok(Response::new(RefreshAccessTokenResponse {
+            access_token: Some(access_token),
+        }))
+    }
+}
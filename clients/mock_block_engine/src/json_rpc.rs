@@ -0,0 +1,188 @@
+//! A JSON-RPC endpoint answering each method with scripted replies, standing in for the
+//! block engine's bundle API or an RPC node.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    error::Error,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+};
+
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+/// Answer to one JSON-RPC call.
+#[derive(Debug, Clone)]
+pub enum JsonRpcReply {
+    Result(Value),
+    Error { code: i64, message: String },
+}
+
+/// A call received by [`MockJsonRpc`].
+#[derive(Debug, Clone)]
+pub struct JsonRpcCall {
+    pub method: String,
+    pub params: Value,
+}
+
+/// Computes the reply to a call from its params.
+type Handler = Arc<dyn Fn(&Value) -> JsonRpcReply + Send + Sync>;
+
+#[derive(Default)]
+struct JsonRpcState {
+    replies: Mutex<HashMap<String, VecDeque<JsonRpcReply>>>,
+    handlers: Mutex<HashMap<String, Handler>>,
+    calls: Mutex<Vec<JsonRpcCall>>,
+}
+
+impl JsonRpcState {
+    /// Reply of the handler for `method`, otherwise the next scripted one. The last
+    /// reply queued answers every later call.
+    fn reply(&self, method: &str, params: &Value) -> JsonRpcReply {
+        let handler = self.handlers.lock().unwrap().get(method).cloned();
+        if let Some(handler) = handler {
+            return handler(params);
+        }
+
+        let mut replies = self.replies.lock().unwrap();
+        match replies.get_mut(method) {
+            Some(queue) if queue.len() > 1 => queue.pop_front().unwrap(),
+            Some(queue) if !queue.is_empty() => queue[0].clone(),
+            _ => JsonRpcReply::Error {
+                code: -32601,
+                message: format!("Method not found: {}", method),
+            },
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<JsonRpcState>,
+    request: Request<Body>,
+) -> Result<Response<Body>, hyper::Error> {
+    let body = hyper::body::to_bytes(request.into_body()).await?;
+    let Ok(request) = serde_json::from_slice::<Value>(&body) else {
+        let mut response = Response::new(Body::from("invalid JSON"));
+        *response.status_mut() = StatusCode::BAD_REQUEST;
+        return Ok(response);
+    };
+
+    let method = request["method"].as_str().unwrap_or_default().to_string();
+    let reply = state.reply(&method, &request["params"]);
+    state.calls.lock().unwrap().push(JsonRpcCall {
+        method,
+        params: request["params"].clone(),
+    });
+
+    let response = match reply {
+        JsonRpcReply::Result(result) => {
+            json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+        }
+        JsonRpcReply::Error { code, message } => json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": code, "message": message },
+        }),
+    };
+
+    let mut response = Response::new(Body::from(response.to_string()));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    Ok(response)
+}
+
+/// A running JSON-RPC endpoint, stopped when dropped.
+pub struct MockJsonRpc {
+    state: Arc<JsonRpcState>,
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockJsonRpc {
+    /// Starts serving on a free localhost port. Unscripted methods answer "method not found".
+    pub async fn start() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(JsonRpcState::default());
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            let service = service_fn(move |request| handle(state.clone(), request));
+            async move { Ok::<_, Infallible>(service) }
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = Server::from_tcp(listener)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(server);
+
+        Ok(Self {
+            state,
+            addr,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// URL to pass as the block engine or RPC URL, the HTTP sender appends the API path.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Queues `replies` for the next calls of `method`, the last one is repeated.
+    pub fn script(&self, method: &str, replies: Vec<JsonRpcReply>) {
+        self.state
+            .replies
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .extend(replies);
+    }
+
+    /// Answers every call of `method` with `handler`, called with the call's params.
+    ///
+    /// Takes precedence over replies queued with [`script`](Self::script).
+    pub fn answer(
+        &self,
+        method: &str,
+        handler: impl Fn(&Value) -> JsonRpcReply + Send + Sync + 'static,
+    ) {
+        self.state
+            .handlers
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), Arc::new(handler));
+    }
+
+    /// Every call received so far, in order.
+    pub fn calls(&self) -> Vec<JsonRpcCall> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// Calls of `method` received so far.
+    pub fn calls_of(&self, method: &str) -> Vec<JsonRpcCall> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .collect()
+    }
+}
+
+impl Drop for MockJsonRpc {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! An in-process block engine for tests that must not touch the network.
+//!
+//! Serves `AuthService` and `SearcherService` on a local port. Clients run the
+//! real challenge/token flow against it, and every bundle sent is answered with
+//! the next scripted sequence of `BundleResult`s. [`MockJsonRpc`] serves the
+//! JSON-RPC bundle API the same way for the HTTP transport.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    error::Error,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use jito_protos::{
+    auth::auth_service_server::AuthServiceServer,
+    bundle::{
+        bundle_result::Result as BundleResultType, rejected::Reason, Accepted, BundleResult,
+        Dropped, DroppedReason, Finalized, Processed, Rejected,
+    },
+    searcher::searcher_service_server::SearcherServiceServer,
+};
+use solana_sdk::transaction::VersionedTransaction;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, oneshot},
+    task::JoinHandle,
+};
+use tonic::{
+    metadata::MetadataMap,
+    transport::{server::TcpIncoming, Server},
+    Status,
+};
+
+pub use crate::json_rpc::{JsonRpcCall, JsonRpcReply, MockJsonRpc};
+use crate::{auth::MockAuthService, searcher::MockSearcherService};
+
+mod auth;
+mod json_rpc;
+mod searcher;
+
+/// Behaviour of a [`MockBlockEngine`].
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// Rejects searcher calls without a valid access token.
+    pub require_auth: bool,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    /// Issues tokens without `expires_at_utc`.
+    pub omit_token_expiry: bool,
+    /// Returned by `GetTipAccounts` as is, so tests can serve invalid accounts.
+    pub tip_accounts: Vec<String>,
+    pub current_slot: u64,
+    pub current_region: String,
+    /// Leader slots of the validators connected in each region, keyed by region and validator.
+    pub connected_leaders: BTreeMap<String, BTreeMap<String, Vec<u64>>>,
+    /// Results for bundles sent after the scripted ones ran out.
+    pub default_script: Vec<ScriptedResult>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            require_auth: false,
+            access_token_ttl: Duration::from_secs(30 * 60),
+            refresh_token_ttl: Duration::from_secs(24 * 60 * 60),
+            omit_token_expiry: false,
+            tip_accounts: Vec::new(),
+            current_slot: 1_000,
+            current_region: "ny".to_string(),
+            connected_leaders: BTreeMap::new(),
+            default_script: Vec::new(),
+        }
+    }
+}
+
+/// One `BundleResult` sent `delay` after the previous one.
+#[derive(Debug, Clone)]
+pub struct ScriptedResult {
+    pub delay: Duration,
+    pub result: BundleResultType,
+}
+
+impl ScriptedResult {
+    pub fn new(result: BundleResultType) -> Self {
+        Self {
+            delay: Duration::ZERO,
+            result,
+        }
+    }
+
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn accepted(slot: u64, validator: &str) -> Self {
+        Self::new(BundleResultType::Accepted(Accepted {
+            slot,
+            validator_identity: validator.to_string(),
+        }))
+    }
+
+    pub fn processed(slot: u64, validator: &str, bundle_index: u64) -> Self {
+        Self::new(BundleResultType::Processed(Processed {
+            validator_identity: validator.to_string(),
+            slot,
+            bundle_index,
+        }))
+    }
+
+    pub fn finalized() -> Self {
+        Self::new(BundleResultType::Finalized(Finalized {}))
+    }
+
+    pub fn rejected(reason: Reason) -> Self {
+        Self::new(BundleResultType::Rejected(Rejected {
+            reason: Some(reason),
+        }))
+    }
+
+    pub fn dropped(reason: DroppedReason) -> Self {
+        Self::new(BundleResultType::Dropped(Dropped {
+            reason: reason as i32,
+        }))
+    }
+
+    /// Accepted, processed and finalized in `slot` by `validator`.
+    pub fn landed(slot: u64, validator: &str) -> Vec<Self> {
+        vec![
+            Self::accepted(slot, validator),
+            Self::processed(slot, validator, 0),
+            Self::finalized(),
+        ]
+    }
+}
+
+/// A bundle received by `SendBundle`.
+#[derive(Debug, Clone)]
+pub struct ReceivedBundle {
+    pub uuid: String,
+    pub transactions: Vec<VersionedTransaction>,
+}
+
+#[derive(Default)]
+pub(crate) struct AuthState {
+    pub(crate) counter: u64,
+    pub(crate) challenges: HashMap<solana_sdk::pubkey::Pubkey, String>,
+    pub(crate) access_tokens: HashMap<String, SystemTime>,
+    pub(crate) refresh_tokens: HashMap<String, SystemTime>,
+    pub(crate) full_auths: usize,
+    pub(crate) refreshes: usize,
+}
+
+pub(crate) struct State {
+    pub(crate) config: MockConfig,
+    pub(crate) auth: Mutex<AuthState>,
+    pub(crate) scripts: Mutex<VecDeque<Vec<ScriptedResult>>>,
+    pub(crate) bundles: Mutex<Vec<ReceivedBundle>>,
+    pub(crate) results: broadcast::Sender<BundleResult>,
+    /// Ends open bundle result streams with an error when sent to.
+    pub(crate) disconnect: broadcast::Sender<()>,
+    pub(crate) subscriptions: Mutex<usize>,
+}
+
+impl State {
+    /// Checks the bearer token of a searcher request when auth is required.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn authorize(&self, metadata: &MetadataMap) -> Result<(), Status> {
+        if !self.config.require_auth {
+            return Ok(());
+        }
+
+        let token = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing bearer token"))?;
+
+        match self.auth.lock().unwrap().access_tokens.get(token) {
+            Some(expires_at) if *expires_at > SystemTime::now() => Ok(()),
+            Some(_) => Err(Status::unauthenticated("access token expired")),
+            None => Err(Status::unauthenticated("unknown access token")),
+        }
+    }
+}
+
+/// A running mock block engine, stopped when dropped.
+pub struct MockBlockEngine {
+    state: Arc<State>,
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl MockBlockEngine {
+    /// Starts serving on a free localhost port.
+    pub async fn start(config: MockConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)?;
+
+        let (results, _) = broadcast::channel(1024);
+        let (disconnect, _) = broadcast::channel(1);
+        let state = Arc::new(State {
+            config,
+            auth: Mutex::default(),
+            scripts: Mutex::default(),
+            bundles: Mutex::default(),
+            results,
+            disconnect,
+            subscriptions: Mutex::default(),
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = tokio::spawn(
+            Server::builder()
+                .add_service(AuthServiceServer::new(MockAuthService {
+                    state: state.clone(),
+                }))
+                .add_service(SearcherServiceServer::new(MockSearcherService {
+                    state: state.clone(),
+                }))
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = shutdown_rx.await;
+                }),
+        );
+
+        Ok(Self {
+            state,
+            addr,
+            shutdown: Some(shutdown),
+            server,
+        })
+    }
+
+    /// URL to pass as the block engine URL.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Queues the results for the next bundle sent.
+    pub fn push_script(&self, script: Vec<ScriptedResult>) {
+        self.state.scripts.lock().unwrap().push_back(script);
+    }
+
+    /// Sends `result` to every subscriber, e.g. for bundles this server never saw.
+    pub fn emit(&self, result: BundleResult) {
+        let _ = self.state.results.send(result);
+    }
+
+    /// Ends every open bundle results stream with an `Unavailable` error.
+    pub fn disconnect_subscribers(&self) {
+        let _ = self.state.disconnect.send(());
+    }
+
+    /// How often `SubscribeBundleResults` was called.
+    pub fn subscriptions(&self) -> usize {
+        *self.state.subscriptions.lock().unwrap()
+    }
+
+    /// Every bundle received so far, in order.
+    pub fn bundles(&self) -> Vec<ReceivedBundle> {
+        self.state.bundles.lock().unwrap().clone()
+    }
+
+    /// How often a client ran the full challenge flow.
+    pub fn full_auths(&self) -> usize {
+        self.state.auth.lock().unwrap().full_auths
+    }
+
+    /// How often a client refreshed its access token.
+    pub fn refreshes(&self) -> usize {
+        self.state.auth.lock().unwrap().refreshes
+    }
+
+    /// Invalidates every access token issued so far.
+    pub fn expire_access_tokens(&self) {
+        self.state.auth.lock().unwrap().access_tokens.clear();
+    }
+
+    /// Invalidates every refresh token issued so far.
+    pub fn expire_refresh_tokens(&self) {
+        self.state.auth.lock().unwrap().refresh_tokens.clear();
+    }
+
+    /// Stops the server and waits for it to exit.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = (&mut self.server).await;
+    }
+}
+
+impl Drop for MockBlockEngine {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
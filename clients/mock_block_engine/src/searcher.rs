@@ -0,0 +1,256 @@
+use std::{pin::Pin, sync::Arc};
+
+use futures_util::{stream, Stream};
+use jito_protos::{
+    bundle::BundleResult,
+    convert::versioned_tx_from_packet,
+    searcher::{
+        searcher_service_server::SearcherService, ConnectedLeadersRegionedRequest,
+        ConnectedLeadersRegionedResponse, ConnectedLeadersRequest, ConnectedLeadersResponse,
+        GetRegionsRequest, GetRegionsResponse, GetTipAccountsRequest, GetTipAccountsResponse,
+        MempoolSubscription, NextScheduledLeaderRequest, NextScheduledLeaderResponse,
+        PendingTxNotification, SendBundleRequest, SendBundleResponse, SlotList,
+        SubscribeBundleResultsRequest,
+    },
+};
+use log::info;
+use tokio::{sync::broadcast::error::RecvError, time::sleep};
+use tonic::{Request, Response, Status};
+
+use crate::{ReceivedBundle, State};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+pub(crate) struct MockSearcherService {
+    pub(crate) state: Arc<State>,
+}
+
+impl MockSearcherService {
+    fn connected_leaders(&self, region: &str) -> ConnectedLeadersResponse {
+        let connected_validators = self
+            .state
+            .config
+            .connected_leaders
+            .get(region)
+            .map(|validators| {
+                validators
+                    .iter()
+                    .map(|(validator, slots)| {
+                        (
+                            validator.clone(),
+                            SlotList {
+                                slots: slots.clone(),
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ConnectedLeadersResponse {
+            connected_validators,
+        }
+    }
+
+    /// The requested regions, or every configured region when none were asked for.
+    fn regions(&self, requested: Vec<String>) -> Vec<String> {
+        if requested.is_empty() {
+            self.state
+                .config
+                .connected_leaders
+                .keys()
+                .cloned()
+                .collect()
+        } else {
+            requested
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl SearcherService for MockSearcherService {
+    type SubscribeBundleResultsStream = ResponseStream<BundleResult>;
+
+    async fn subscribe_bundle_results(
+        &self,
+        request: Request<SubscribeBundleResultsRequest>,
+    ) -> Result<Response<Self::SubscribeBundleResultsStream>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        *self.state.subscriptions.lock().unwrap() += 1;
+        let receiver = self.state.results.subscribe();
+        let disconnect = self.state.disconnect.subscribe();
+        let results = stream::unfold(Some((receiver, disconnect)), |state| async move {
+            let (mut receiver, mut disconnect) = state?;
+            loop {
+                tokio::select! {
+                    _ = disconnect.recv() => {
+                        return Some((Err(Status::unavailable("disconnected by test")), None));
+                    }
+                    result = receiver.recv() => match result {
+                        Ok(result) => return Some((Ok(result), Some((receiver, disconnect)))),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    },
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(results)))
+    }
+
+    type SubscribeMempoolStream = ResponseStream<PendingTxNotification>;
+
+    async fn subscribe_mempool(
+        &self,
+        _request: Request<MempoolSubscription>,
+    ) -> Result<Response<Self::SubscribeMempoolStream>, Status> {
+        Err(Status::unimplemented("mempool is not mocked"))
+    }
+
+    async fn send_bundle(
+        &self,
+        request: Request<SendBundleRequest>,
+    ) -> Result<Response<SendBundleResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        let bundle = request
+            .into_inner()
+            .bundle
+            .ok_or_else(|| Status::invalid_argument("missing bundle"))?;
+        let transactions = bundle
+            .packets
+            .iter()
+            .map(versioned_tx_from_packet)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Status::invalid_argument("bundle contains an invalid transaction"))?;
+        if transactions.is_empty() || transactions.len() > 5 {
+            return Err(Status::invalid_argument(
+                "bundle must contain 1 to 5 transactions",
+            ));
+        }
+
+        let uuid = {
+            let mut bundles = self.state.bundles.lock().unwrap();
+            let uuid = format!("{:08x}-mock-bundle", bundles.len() + 1);
+            bundles.push(ReceivedBundle {
+                uuid: uuid.clone(),
+                transactions,
+            });
+            uuid
+        };
+        info!("Mock block engine received bundle {}", uuid);
+
+        let script = self
+            .state
+            .scripts
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| self.state.config.default_script.clone());
+        let results = self.state.results.clone();
+        let bundle_id = uuid.clone();
+        tokio::spawn(async move {
+            for scripted in script {
+                sleep(scripted.delay).await;
+                let _ = results.send(BundleResult {
+                    bundle_id: bundle_id.clone(),
+                    result: Some(scripted.result),
+                });
+            }
+        });
+
+        Ok(Response::new(SendBundleResponse { uuid }))
+    }
+
+    async fn get_next_scheduled_leader(
+        &self,
+        request: Request<NextScheduledLeaderRequest>,
+    ) -> Result<Response<NextScheduledLeaderResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        let current_slot = self.state.config.current_slot;
+        let mut next: Option<(u64, String, String)> = None;
+        for region in self.regions(request.into_inner().regions) {
+            let Some(validators) = self.state.config.connected_leaders.get(&region) else {
+                continue;
+            };
+            for (validator, slots) in validators {
+                for slot in slots.iter().filter(|slot| **slot >= current_slot) {
+                    if next.as_ref().is_none_or(|(next_slot, ..)| slot < next_slot) {
+                        next = Some((*slot, validator.clone(), region.clone()));
+                    }
+                }
+            }
+        }
+
+        let (next_leader_slot, next_leader_identity, next_leader_region) = next.unwrap_or_default();
+
+        Ok(Response::new(NextScheduledLeaderResponse {
+            current_slot,
+            next_leader_slot,
+            next_leader_identity,
+            next_leader_region,
+        }))
+    }
+
+    async fn get_connected_leaders(
+        &self,
+        request: Request<ConnectedLeadersRequest>,
+    ) -> Result<Response<ConnectedLeadersResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(
+            self.connected_leaders(&self.state.config.current_region),
+        ))
+    }
+
+    async fn get_connected_leaders_regioned(
+        &self,
+        request: Request<ConnectedLeadersRegionedRequest>,
+    ) -> Result<Response<ConnectedLeadersRegionedResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        let connected_validators = self
+            .regions(request.into_inner().regions)
+            .into_iter()
+            .map(|region| {
+                let leaders = self.connected_leaders(&region);
+                (region, leaders)
+            })
+            .collect();
+
+        Ok(Response::new(ConnectedLeadersRegionedResponse {
+            connected_validators,
+        }))
+    }
+
+    async fn get_tip_accounts(
+        &self,
+        request: Request<GetTipAccountsRequest>,
+    ) -> Result<Response<GetTipAccountsResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetTipAccountsResponse {
+            accounts: self.state.config.tip_accounts.clone(),
+        }))
+    }
+
+    async fn get_regions(
+        &self,
+        request: Request<GetRegionsRequest>,
+    ) -> Result<Response<GetRegionsResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetRegionsResponse {
+            current_region: self.state.config.current_region.clone(),
+            available_regions: self
+                .state
+                .config
+                .connected_leaders
+                .keys()
+                .cloned()
+                .collect(),
+        }))
+    }
+}
@@ -0,0 +1,241 @@
+use std::{future::pending, pin::Pin, sync::atomic::Ordering, sync::Arc};
+
+use futures_util::Stream;
+use solana_sdk::pubkey::Pubkey;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc},
+    time::interval,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use yellowstone_grpc_proto::{
+    convert_from,
+    geyser::{
+        geyser_server::Geyser, subscribe_update::UpdateOneof, GetBlockHeightRequest,
+        GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+        GetSlotRequest, GetSlotResponse, GetVersionRequest, GetVersionResponse,
+        IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
+        SubscribeRequest, SubscribeUpdate, SubscribeUpdatePing, SubscribeUpdatePong,
+    },
+};
+
+use crate::State;
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+pub(crate) struct MockGeyserService {
+    pub(crate) state: Arc<State>,
+}
+
+/// Names of the filters in `request` that `update` matches, `None` when it matches none.
+///
+/// Accounts match by address or owner and transactions by signature and included
+/// accounts; data and vote filters are not checked.
+fn matching_filters(request: &SubscribeRequest, update: &UpdateOneof) -> Option<Vec<String>> {
+    let filters: Vec<String> = match update {
+        UpdateOneof::Account(account) => {
+            let (pubkey, account) = account
+                .account
+                .clone()
+                .and_then(|info| convert_from::create_account(info).ok())?;
+            request
+                .accounts
+                .iter()
+                .filter(|(_, filter)| {
+                    (filter.account.is_empty() && filter.owner.is_empty())
+                        || filter.account.contains(&pubkey.to_string())
+                        || filter.owner.contains(&account.owner.to_string())
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        }
+        UpdateOneof::Transaction(transaction) => {
+            let info = transaction.transaction.as_ref()?;
+            let signature = convert_from::create_tx_versioned(info.transaction.clone()?)
+                .ok()?
+                .signatures[0]
+                .to_string();
+            let keys: Vec<String> = info
+                .transaction
+                .as_ref()?
+                .message
+                .as_ref()?
+                .account_keys
+                .iter()
+                .filter_map(|key| Pubkey::try_from(key.as_slice()).ok())
+                .map(|key| key.to_string())
+                .collect();
+
+            request
+                .transactions
+                .iter()
+                .filter(|(_, filter)| {
+                    filter
+                        .signature
+                        .as_ref()
+                        .is_none_or(|wanted| *wanted == signature)
+                        && (filter.account_include.is_empty()
+                            || filter.account_include.iter().any(|key| keys.contains(key)))
+                        && filter.account_required.iter().all(|key| keys.contains(key))
+                        && !filter.account_exclude.iter().any(|key| keys.contains(key))
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        }
+        UpdateOneof::Slot(_) => request.slots.keys().cloned().collect(),
+        UpdateOneof::Block(_) => request.blocks.keys().cloned().collect(),
+        UpdateOneof::BlockMeta(_) => request.blocks_meta.keys().cloned().collect(),
+        UpdateOneof::Entry(_) => request.entry.keys().cloned().collect(),
+        UpdateOneof::Ping(_) | UpdateOneof::Pong(_) => Vec::new(),
+    };
+
+    (!filters.is_empty()).then_some(filters)
+}
+
+#[tonic::async_trait]
+impl Geyser for MockGeyserService {
+    type SubscribeStream = ResponseStream<SubscribeUpdate>;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.state.authorize(request.metadata())?;
+        *self.state.subscriptions.lock().unwrap() += 1;
+
+        let mut requests = request.into_inner();
+        let state = self.state.clone();
+        let (tx, rx) = mpsc::channel(1024);
+
+        tokio::spawn(async move {
+            let mut filters = SubscribeRequest::default();
+            let mut updates = state.updates.subscribe();
+            let mut disconnect = state.disconnect.subscribe();
+            let mut ping = state.config.ping_interval.map(interval);
+
+            loop {
+                let paused = state.paused.load(Ordering::Relaxed);
+                let outgoing = tokio::select! {
+                    request = requests.message() => match request {
+                        Ok(Some(request)) => match request.ping {
+                            Some(ping) => {
+                                *state.pings.lock().unwrap() += 1;
+                                Some(SubscribeUpdate {
+                                    filters: Vec::new(),
+                                    update_oneof: Some(UpdateOneof::Pong(SubscribeUpdatePong {
+                                        id: ping.id,
+                                    })),
+                                })
+                            }
+                            None => {
+                                state.requests.lock().unwrap().push(request.clone());
+                                filters = request;
+                                None
+                            }
+                        },
+                        _ => break,
+                    },
+                    update = updates.recv() => match update {
+                        Ok(update) => update.update_oneof.and_then(|update_oneof| {
+                            let matched = matching_filters(&filters, &update_oneof)?;
+                            Some(SubscribeUpdate {
+                                filters: matched,
+                                update_oneof: Some(update_oneof),
+                            })
+                        }),
+                        Err(RecvError::Lagged(_)) => None,
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = disconnect.recv() => {
+                        let _ = tx.send(Err(Status::unavailable("disconnected by test"))).await;
+                        break;
+                    }
+                    _ = async {
+                        match &mut ping {
+                            Some(ping) => ping.tick().await,
+                            None => pending().await,
+                        }
+                    } => Some(SubscribeUpdate {
+                        filters: Vec::new(),
+                        update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+                    }),
+                    _ = tx.closed() => break,
+                };
+
+                if let Some(outgoing) = outgoing.filter(|_| !paused) {
+                    if tx.send(Ok(outgoing)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(PongResponse {
+            count: request.into_inner().count,
+        }))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        request: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetLatestBlockhashResponse {
+            slot: *self.state.slot.lock().unwrap(),
+            blockhash: self.state.config.blockhash.to_string(),
+            last_valid_block_height: self.state.config.block_height + 150,
+        }))
+    }
+
+    async fn get_block_height(
+        &self,
+        request: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetBlockHeightResponse {
+            block_height: self.state.config.block_height,
+        }))
+    }
+
+    async fn get_slot(
+        &self,
+        request: Request<GetSlotRequest>,
+    ) -> Result<Response<GetSlotResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetSlotResponse {
+            slot: *self.state.slot.lock().unwrap(),
+        }))
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        request: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(IsBlockhashValidResponse {
+            slot: *self.state.slot.lock().unwrap(),
+            valid: request.into_inner().blockhash == self.state.config.blockhash.to_string(),
+        }))
+    }
+
+    async fn get_version(
+        &self,
+        request: Request<GetVersionRequest>,
+    ) -> Result<Response<GetVersionResponse>, Status> {
+        self.state.authorize(request.metadata())?;
+
+        Ok(Response::new(GetVersionResponse {
+            version: self.state.config.version.clone(),
+        }))
+    }
+}
@@ -0,0 +1,300 @@
+//! An in-process Yellowstone geyser for tests that must not touch the network.
+//!
+//! Serves the `Geyser` service on a local port. Updates passed to
+//! [`MockGeyser::emit`] go to every subscriber whose filters match them, tagged
+//! with the matching filter names like the real server does.
+
+use std::{
+    error::Error,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::TransactionStatusMeta;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, oneshot},
+    task::JoinHandle,
+};
+use tonic::{
+    metadata::MetadataMap,
+    transport::{server::TcpIncoming, Server},
+    Status,
+};
+use yellowstone_grpc_proto::{
+    convert_to,
+    geyser::{
+        geyser_server::GeyserServer, subscribe_update::UpdateOneof, CommitmentLevel,
+        SubscribeRequest, SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+        SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+    },
+    solana::storage::confirmed_block,
+};
+
+use crate::geyser::MockGeyserService;
+
+mod geyser;
+
+/// Behaviour of a [`MockGeyser`].
+#[derive(Debug, Clone)]
+pub struct MockGeyserConfig {
+    /// Rejects calls without this `x-token`.
+    pub x_token: Option<String>,
+    pub slot: u64,
+    pub block_height: u64,
+    pub blockhash: Hash,
+    pub version: String,
+    /// Sends a ping update to every subscriber at this interval.
+    pub ping_interval: Option<Duration>,
+}
+
+impl Default for MockGeyserConfig {
+    fn default() -> Self {
+        Self {
+            x_token: None,
+            slot: 1_000,
+            block_height: 900,
+            blockhash: Hash::new_unique(),
+            version: "mock-geyser".to_string(),
+            ping_interval: None,
+        }
+    }
+}
+
+pub(crate) struct State {
+    pub(crate) config: MockGeyserConfig,
+    pub(crate) slot: Mutex<u64>,
+    pub(crate) updates: broadcast::Sender<SubscribeUpdate>,
+    /// Ends open subscriptions with an error when sent to.
+    pub(crate) disconnect: broadcast::Sender<()>,
+    /// Every non-ping request received on a subscription, in order.
+    pub(crate) requests: Mutex<Vec<SubscribeRequest>>,
+    pub(crate) pings: Mutex<usize>,
+    pub(crate) subscriptions: Mutex<usize>,
+    /// Holds every message back, including pongs, while set.
+    pub(crate) paused: AtomicBool,
+}
+
+impl State {
+    /// Checks the `x-token` of a request when one is required.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn authorize(&self, metadata: &MetadataMap) -> Result<(), Status> {
+        let Some(x_token) = &self.config.x_token else {
+            return Ok(());
+        };
+
+        match metadata
+            .get("x-token")
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(token) if token == x_token => Ok(()),
+            Some(_) => Err(Status::unauthenticated("invalid x-token")),
+            None => Err(Status::unauthenticated("missing x-token")),
+        }
+    }
+}
+
+/// A running mock geyser, stopped when dropped.
+pub struct MockGeyser {
+    state: Arc<State>,
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl MockGeyser {
+    /// Starts serving on a free localhost port.
+    pub async fn start(config: MockGeyserConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)?;
+
+        let (updates, _) = broadcast::channel(1024);
+        let (disconnect, _) = broadcast::channel(1);
+        let state = Arc::new(State {
+            slot: Mutex::new(config.slot),
+            config,
+            updates,
+            disconnect,
+            requests: Mutex::default(),
+            pings: Mutex::default(),
+            subscriptions: Mutex::default(),
+            paused: AtomicBool::new(false),
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = tokio::spawn(
+            Server::builder()
+                .add_service(GeyserServer::new(MockGeyserService {
+                    state: state.clone(),
+                }))
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = shutdown_rx.await;
+                }),
+        );
+
+        Ok(Self {
+            state,
+            addr,
+            shutdown: Some(shutdown),
+            server,
+        })
+    }
+
+    /// URL to pass as the geyser endpoint.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Sends `update` to every subscriber with a matching filter.
+    pub fn emit(&self, update: SubscribeUpdate) {
+        let _ = self.state.updates.send(update);
+    }
+
+    /// Slot reported by `GetSlot` from now on.
+    pub fn set_slot(&self, slot: u64) {
+        *self.state.slot.lock().unwrap() = slot;
+    }
+
+    /// Ends every open subscription with an `Unavailable` error.
+    pub fn disconnect_subscribers(&self) {
+        let _ = self.state.disconnect.send(());
+    }
+
+    /// Stops sending anything to subscribers, pongs included, until unpaused.
+    pub fn pause(&self, paused: bool) {
+        self.state.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Every subscribe request other than pings received so far, in order.
+    pub fn requests(&self) -> Vec<SubscribeRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// How many pings subscribers sent.
+    pub fn pings(&self) -> usize {
+        *self.state.pings.lock().unwrap()
+    }
+
+    /// How often `Subscribe` was called.
+    pub fn subscriptions(&self) -> usize {
+        *self.state.subscriptions.lock().unwrap()
+    }
+
+    /// Stops the server and waits for it to exit.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = (&mut self.server).await;
+    }
+}
+
+impl Drop for MockGeyser {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn update(update: UpdateOneof) -> SubscribeUpdate {
+    SubscribeUpdate {
+        filters: Vec::new(),
+        update_oneof: Some(update),
+    }
+}
+
+/// A write of `account` at `pubkey` in `slot`.
+pub fn account_update(pubkey: &Pubkey, account: &Account, slot: u64) -> SubscribeUpdate {
+    update(UpdateOneof::Account(SubscribeUpdateAccount {
+        account: Some(SubscribeUpdateAccountInfo {
+            pubkey: pubkey.to_bytes().to_vec(),
+            lamports: account.lamports,
+            owner: account.owner.to_bytes().to_vec(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.clone(),
+            write_version: slot,
+            txn_signature: None,
+        }),
+        slot,
+        is_startup: false,
+    }))
+}
+
+/// `transaction` executed first in `slot`, failed with `err` if given.
+pub fn transaction_update(
+    transaction: &VersionedTransaction,
+    slot: u64,
+    err: Option<TransactionError>,
+) -> SubscribeUpdate {
+    transaction_update_at(transaction, slot, 0, err)
+}
+
+/// `transaction` executed at position `index` of the block in `slot`.
+pub fn transaction_update_at(
+    transaction: &VersionedTransaction,
+    slot: u64,
+    index: u64,
+    err: Option<TransactionError>,
+) -> SubscribeUpdate {
+    let message = &transaction.message;
+    let (versioned, address_table_lookups) = match message {
+        VersionedMessage::Legacy(_) => (false, Vec::new()),
+        VersionedMessage::V0(message) => (
+            true,
+            convert_to::create_lookups(&message.address_table_lookups),
+        ),
+    };
+    let meta = TransactionStatusMeta {
+        status: err.map_or(Ok(()), Err),
+        fee: 5_000,
+        ..TransactionStatusMeta::default()
+    };
+
+    update(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+        transaction: Some(SubscribeUpdateTransactionInfo {
+            signature: transaction.signatures[0].as_ref().to_vec(),
+            is_vote: false,
+            transaction: Some(confirmed_block::Transaction {
+                signatures: transaction
+                    .signatures
+                    .iter()
+                    .map(|signature| signature.as_ref().to_vec())
+                    .collect(),
+                message: Some(confirmed_block::Message {
+                    header: Some(convert_to::create_header(message.header())),
+                    account_keys: convert_to::create_pubkeys(message.static_account_keys()),
+                    recent_blockhash: message.recent_blockhash().to_bytes().to_vec(),
+                    instructions: convert_to::create_instructions(message.instructions()),
+                    versioned,
+                    address_table_lookups,
+                }),
+            }),
+            meta: Some(convert_to::create_transaction_meta(&meta)),
+            index,
+        }),
+        slot,
+    }))
+}
+
+/// `slot` reaching `status`.
+pub fn slot_update(slot: u64, status: CommitmentLevel) -> SubscribeUpdate {
+    update(UpdateOneof::Slot(SubscribeUpdateSlot {
+        slot,
+        parent: slot.checked_sub(1),
+        status: status as i32,
+    }))
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{
+    bs58, hash::Hash, pubkey::Pubkey, signature::Keypair, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use thiserror::Error;
+use tokio::time::sleep;
+use tonic::Status;
+
+use crate::{
+    check_landed, confirm_bundle,
+    leader_schedule::{wait_for_leader, LeaderSchedule, LeaderScheduleError, LeaderWindow},
+    send_bundle_no_wait,
+    session::SearcherSession,
+    tip_accounts::get_tip_accounts,
+    tip_accounts::TipAccountError,
+    BlockEngineConnectionError, BlockEngineConnectionResult, BundleOutcome, BundleRejectionError,
+    SearcherClient,
+};
+
+/// How long the HTTP transport polls for a bundle's status.
+const HTTP_STATUS_TIMEOUT: Duration = Duration::from_secs(30);
+const HTTP_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Longest wait for the answer to one HTTP request.
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum BundleSendError {
+    #[error("block engine error {0}")]
+    BlockEngine(Box<Status>),
+    #[error("http error {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("failed to serialize transaction {0}")]
+    Serialization(#[from] bincode::Error),
+    #[error("json rpc error {code}: {message}")]
+    JsonRpc { code: i64, message: String },
+    #[error("rpc error {0}")]
+    Rpc(Box<ClientError>),
+    #[error("no block engine to send to")]
+    NoBlockEngine,
+    #[error("block engine connection error {0}")]
+    Connection(Box<BlockEngineConnectionError>),
+}
+
+impl From<Status> for BundleSendError {
+    fn from(status: Status) -> Self {
+        BundleSendError::BlockEngine(Box::new(status))
+    }
+}
+
+impl From<ClientError> for BundleSendError {
+    fn from(e: ClientError) -> Self {
+        BundleSendError::Rpc(Box::new(e))
+    }
+}
+
+/// Submits bundles to the block engine and reports what became of them.
+#[async_trait]
+pub trait BundleSender: Send {
+    /// Sends a bundle, returning the id the block engine assigned to it.
+    async fn send_bundle(
+        &mut self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError>;
+
+    /// Waits until the bundle with `bundle_id` landed, failed or timed out.
+    async fn confirm_bundle(
+        &mut self,
+        bundle_id: &str,
+        signatures: &[Signature],
+        rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError>;
+
+    /// The block engine's current tip accounts.
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError>;
+
+    /// Waits until a Jito leader is close, returning the leader expected to land the bundle.
+    ///
+    /// Transports without a schedule or without access to the leader schedule send right away.
+    async fn wait_for_leader(&mut self) -> Result<Option<LeaderWindow>, LeaderScheduleError> {
+        Ok(None)
+    }
+}
+
+/// Sends bundles over the searcher gRPC service.
+pub struct GrpcBundleSender {
+    session: SearcherSession,
+    schedule: Option<LeaderSchedule>,
+    /// Blockhash of every bundle sent but not confirmed yet, by UUID.
+    blockhashes: HashMap<String, Hash>,
+}
+
+impl GrpcBundleSender {
+    /// Connects and subscribes to the bundle results of this searcher.
+    pub async fn connect(
+        block_engine_url: &str,
+        auth_keypair: Option<&Arc<Keypair>>,
+    ) -> BlockEngineConnectionResult<Self> {
+        Ok(Self::from_session(
+            SearcherSession::connect(block_engine_url, auth_keypair).await?,
+        ))
+    }
+
+    pub fn from_session(session: SearcherSession) -> Self {
+        Self {
+            session,
+            schedule: None,
+            blockhashes: HashMap::new(),
+        }
+    }
+
+    /// Holds every bundle back until a Jito leader is within the schedule's range.
+    pub fn with_schedule(mut self, schedule: Option<LeaderSchedule>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn client(&mut self) -> &mut SearcherClient {
+        self.session.client()
+    }
+
+    pub fn session(&self) -> &SearcherSession {
+        &self.session
+    }
+}
+
+#[async_trait]
+impl BundleSender for GrpcBundleSender {
+    async fn send_bundle(
+        &mut self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError> {
+        if self.session.is_authenticated() {
+            let health = self.session.auth_health();
+            if !health.is_valid() {
+                warn!(
+                    "Block engine access token expired, last refresh error: {}",
+                    health.last_error.as_deref().unwrap_or("none")
+                );
+            }
+        }
+
+        // Results of a bundle sent while unsubscribed would be lost
+        self.session
+            .ensure_subscribed()
+            .await
+            .map_err(|e| BundleSendError::Connection(Box::new(e)))?;
+
+        let uuid = send_bundle_no_wait(transactions, self.session.client())
+            .await?
+            .into_inner()
+            .uuid;
+        info!("Bundle sent. UUID: {:?}", uuid);
+        if let Some(tx) = transactions.first() {
+            self.blockhashes
+                .insert(uuid.clone(), *tx.message.recent_blockhash());
+        }
+
+        Ok(uuid)
+    }
+
+    async fn confirm_bundle(
+        &mut self,
+        bundle_id: &str,
+        signatures: &[Signature],
+        rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError> {
+        let blockhash = self.blockhashes.remove(bundle_id);
+        let mut bundle = self.session.tracker().track(bundle_id);
+        Ok(confirm_bundle(signatures, blockhash.as_ref(), rpc_client, &mut bundle).await?)
+    }
+
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError> {
+        get_tip_accounts(self.session.client()).await
+    }
+
+    async fn wait_for_leader(&mut self) -> Result<Option<LeaderWindow>, LeaderScheduleError> {
+        match &self.schedule {
+            Some(schedule) => Ok(Some(
+                wait_for_leader(self.session.client(), schedule).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    value: T,
+}
+
+/// Entry of a `getBundleStatuses` response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BundleStatusInfo {
+    pub bundle_id: String,
+    pub transactions: Vec<String>,
+    pub slot: u64,
+    pub confirmation_status: Option<String>,
+    /// `{"Ok": null}` when the bundle succeeded.
+    pub err: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum InflightStatus {
+    /// Unknown to the block engine, or older than five minutes.
+    Invalid,
+    Pending,
+    /// Failed in every region.
+    Failed,
+    Landed,
+}
+
+/// Entry of a `getInflightBundleStatuses` response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InflightBundleStatus {
+    pub bundle_id: String,
+    pub status: InflightStatus,
+    pub landed_slot: Option<u64>,
+}
+
+fn http_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("failed to build the HTTP client")
+}
+
+/// Sends bundles to the block engine's JSON-RPC endpoint, which needs no authentication.
+pub struct HttpBundleSender {
+    http: reqwest::Client,
+    url: String,
+    next_id: u64,
+}
+
+impl HttpBundleSender {
+    /// Talks to `/api/v1/bundles` of `block_engine_url` unless the URL already names a path.
+    pub fn new(block_engine_url: &str) -> Self {
+        let url = if block_engine_url.contains("/api/") {
+            block_engine_url.to_string()
+        } else {
+            format!("{}/api/v1/bundles", block_engine_url.trim_end_matches('/'))
+        };
+
+        Self {
+            http: http_client(HTTP_REQUEST_TIMEOUT),
+            url,
+            next_id: 0,
+        }
+    }
+
+    /// Gives up on a request the block engine does not answer within `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.http = http_client(timeout);
+        self
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, BundleSendError> {
+        self.next_id += 1;
+        let response: JsonRpcResponse<T> = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": self.next_id,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(BundleSendError::JsonRpc {
+                code: error.code,
+                message: error.message,
+            }),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(BundleSendError::JsonRpc {
+                code: 0,
+                message: format!("{} returned no result", method),
+            }),
+        }
+    }
+
+    /// Statuses of landed bundles, `None` for bundles not found or not landed.
+    pub async fn bundle_statuses(
+        &mut self,
+        bundle_ids: &[String],
+    ) -> Result<Vec<Option<BundleStatusInfo>>, BundleSendError> {
+        let response: RpcResponse<Vec<Option<BundleStatusInfo>>> =
+            self.call("getBundleStatuses", json!([bundle_ids])).await?;
+        Ok(response.value)
+    }
+
+    /// Statuses of bundles sent in the last five minutes.
+    pub async fn inflight_bundle_statuses(
+        &mut self,
+        bundle_ids: &[String],
+    ) -> Result<Vec<Option<InflightBundleStatus>>, BundleSendError> {
+        let response: RpcResponse<Vec<Option<InflightBundleStatus>>> = self
+            .call("getInflightBundleStatuses", json!([bundle_ids]))
+            .await?;
+        Ok(response.value)
+    }
+}
+
+#[async_trait]
+impl BundleSender for HttpBundleSender {
+    async fn send_bundle(
+        &mut self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError> {
+        let encoded = transactions
+            .iter()
+            .map(|tx| Ok(bs58::encode(bincode::serialize(tx)?).into_string()))
+            .collect::<Result<Vec<_>, BundleSendError>>()?;
+
+        let bundle_id: String = self.call("sendBundle", json!([encoded])).await?;
+        info!("Bundle sent over HTTP. Bundle id: {:?}", bundle_id);
+
+        Ok(bundle_id)
+    }
+
+    async fn confirm_bundle(
+        &mut self,
+        bundle_id: &str,
+        signatures: &[Signature],
+        rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError> {
+        info!(
+            "Polling bundle status for up to {:?}...",
+            HTTP_STATUS_TIMEOUT
+        );
+        let bundle_ids = [bundle_id.to_string()];
+        let deadline = Instant::now() + HTTP_STATUS_TIMEOUT;
+
+        while Instant::now() < deadline {
+            let status = match self.inflight_bundle_statuses(&bundle_ids).await {
+                Ok(statuses) => statuses.into_iter().flatten().next(),
+                Err(e) => {
+                    warn!("Failed to get inflight bundle status: {}", e);
+                    None
+                }
+            };
+
+            match status.map(|status| (status.status, status.landed_slot)) {
+                Some((InflightStatus::Landed, landed_slot)) => {
+                    let slot = match self.bundle_statuses(&bundle_ids).await {
+                        Ok(statuses) => statuses.into_iter().flatten().next().map(|s| s.slot),
+                        Err(_) => None,
+                    };
+                    return Ok(
+                        check_landed(signatures, rpc_client, slot.or(landed_slot), None).await?,
+                    );
+                }
+                Some((InflightStatus::Failed, _)) => {
+                    return Ok(BundleOutcome::Rejected(
+                        BundleRejectionError::FailedInAllRegions,
+                    ))
+                }
+                _ => sleep(HTTP_POLL_INTERVAL).await,
+            }
+        }
+
+        Ok(check_landed(signatures, rpc_client, None, None).await?)
+    }
+
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError> {
+        let accounts: Vec<String> = self
+            .call("getTipAccounts", json!([]))
+            .await
+            .map_err(|e| TipAccountError::Transport(e.to_string()))?;
+
+        let accounts = accounts
+            .iter()
+            .map(|account| {
+                Pubkey::from_str(account)
+                    .map_err(|_| TipAccountError::InvalidAccount(account.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if accounts.is_empty() {
+            return Err(TipAccountError::Empty);
+        }
+
+        Ok(accounts)
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::StreamExt;
+use jito_protos::bundle::{
+    bundle_result::Result as BundleResultType, rejected::Reason, Accepted, BundleResult,
+    DroppedBundle, DroppedReason, InternalError, Processed, SimulationFailure,
+    StateAuctionBidRejected, WinningBatchBidRejected,
+};
+use log::{debug, warn};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+use tonic::Streaming;
+
+use crate::BundleRejectionError;
+
+/// How long results for a UUID nobody tracks yet are kept around.
+///
+/// Results can arrive before `SendBundle` has returned the UUID to the caller.
+const UNCLAIMED_TTL: Duration = Duration::from_secs(60);
+
+/// A lifecycle update for one bundle, as reported by the block engine.
+#[derive(Debug, Clone)]
+pub enum BundleStatus {
+    /// Forwarded to a validator, which may or may not include it.
+    Accepted { slot: u64, validator: String },
+    /// Not forwarded to any validator.
+    Rejected(BundleRejectionError),
+    /// Landed in a block.
+    Processed {
+        slot: u64,
+        validator: String,
+        /// Position of the bundle within the block.
+        bundle_index: u64,
+    },
+    /// The block containing the bundle was finalized.
+    Finalized,
+    /// Forwarded but never landed.
+    Dropped(DroppedReason),
+}
+
+impl BundleStatus {
+    /// Whether no further updates are expected for the bundle.
+    pub fn is_terminal(&self) -> bool {
+        !matches!(
+            self,
+            BundleStatus::Accepted { .. } | BundleStatus::Processed { .. }
+        )
+    }
+
+    fn from_result(result: BundleResultType) -> Option<Self> {
+        let status = match result {
+            BundleResultType::Accepted(Accepted {
+                slot,
+                validator_identity,
+            }) => BundleStatus::Accepted {
+                slot,
+                validator: validator_identity,
+            },
+            BundleResultType::Rejected(rejected) => {
+                let error = match rejected.reason? {
+                    Reason::WinningBatchBidRejected(WinningBatchBidRejected {
+                        auction_id,
+                        simulated_bid_lamports,
+                        msg,
+                    }) => BundleRejectionError::WinningBatchBidRejected {
+                        auction_id,
+                        simulated_bid_lamports,
+                        msg,
+                    },
+                    Reason::StateAuctionBidRejected(StateAuctionBidRejected {
+                        auction_id,
+                        simulated_bid_lamports,
+                        msg,
+                    }) => BundleRejectionError::StateAuctionBidRejected {
+                        auction_id,
+                        simulated_bid_lamports,
+                        msg,
+                    },
+                    Reason::SimulationFailure(SimulationFailure { tx_signature, msg }) => {
+                        BundleRejectionError::SimulationFailure { tx_signature, msg }
+                    }
+                    Reason::InternalError(InternalError { msg }) => {
+                        BundleRejectionError::InternalError { msg }
+                    }
+                    Reason::DroppedBundle(DroppedBundle { msg }) => {
+                        BundleRejectionError::DroppedBundle { msg }
+                    }
+                };
+                BundleStatus::Rejected(error)
+            }
+            BundleResultType::Processed(Processed {
+                validator_identity,
+                slot,
+                bundle_index,
+            }) => BundleStatus::Processed {
+                slot,
+                validator: validator_identity,
+                bundle_index,
+            },
+            BundleResultType::Finalized(_) => BundleStatus::Finalized,
+            BundleResultType::Dropped(dropped) => BundleStatus::Dropped(dropped.reason()),
+        };
+
+        Some(status)
+    }
+}
+
+#[derive(Default)]
+struct Routes {
+    tracked: HashMap<String, UnboundedSender<BundleStatus>>,
+    unclaimed: HashMap<String, (Instant, Vec<BundleStatus>)>,
+    closed: bool,
+}
+
+impl Routes {
+    fn route(&mut self, uuid: String, status: BundleStatus) {
+        if let Some(sender) = self.tracked.get(&uuid) {
+            // The receiver is gone when the caller stopped waiting
+            let _ = sender.send(status);
+            return;
+        }
+
+        self.unclaimed
+            .retain(|_, (received_at, _)| received_at.elapsed() < UNCLAIMED_TTL);
+        self.unclaimed
+            .entry(uuid)
+            .or_insert_with(|| (Instant::now(), Vec::new()))
+            .1
+            .push(status);
+    }
+}
+
+/// Routes the results of one bundle results subscription to the bundle they belong to.
+///
+/// The subscription reports every bundle sent by the searcher, so reading it
+/// directly attributes results to the wrong bundle once several are in flight.
+pub struct BundleTracker {
+    routes: Arc<Mutex<Routes>>,
+    reader: JoinHandle<()>,
+}
+
+impl BundleTracker {
+    /// Starts reading `bundle_results_subscription` in the background.
+    pub fn new(mut bundle_results_subscription: Streaming<BundleResult>) -> Self {
+        let routes = Arc::new(Mutex::new(Routes::default()));
+
+        let reader = tokio::spawn({
+            let routes = routes.clone();
+            async move {
+                while let Some(result) = bundle_results_subscription.next().await {
+                    let result = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            warn!("Bundle results subscription failed: {}", e);
+                            break;
+                        }
+                    };
+                    debug!("bundle results: {:?}", result);
+
+                    if let Some(status) = result.result.and_then(BundleStatus::from_result) {
+                        routes.lock().unwrap().route(result.bundle_id, status);
+                    }
+                }
+
+                // Dropping the senders ends every tracked bundle's stream
+                let mut routes = routes.lock().unwrap();
+                routes.closed = true;
+                routes.tracked.clear();
+            }
+        });
+
+        Self { routes, reader }
+    }
+
+    /// Starts following the bundle with `uuid`, including results received before this call.
+    pub fn track(&self, uuid: &str) -> TrackedBundle {
+        let (sender, receiver) = unbounded_channel();
+
+        let mut routes = self.routes.lock().unwrap();
+        if let Some((_, statuses)) = routes.unclaimed.remove(uuid) {
+            for status in statuses {
+                let _ = sender.send(status);
+            }
+        }
+        if !routes.closed {
+            routes.tracked.insert(uuid.to_string(), sender);
+        }
+
+        TrackedBundle {
+            uuid: uuid.to_string(),
+            receiver,
+            routes: self.routes.clone(),
+        }
+    }
+
+    /// Whether the subscription ended, after which no bundle gets further updates.
+    pub fn is_closed(&self) -> bool {
+        self.routes.lock().unwrap().closed
+    }
+}
+
+impl Drop for BundleTracker {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Updates for a single bundle, in the order the block engine sent them.
+pub struct TrackedBundle {
+    uuid: String,
+    receiver: UnboundedReceiver<BundleStatus>,
+    routes: Arc<Mutex<Routes>>,
+}
+
+impl TrackedBundle {
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// Waits for the next update, or `None` once the subscription ended.
+    pub async fn next(&mut self) -> Option<BundleStatus> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for TrackedBundle {
+    fn drop(&mut self) {
+        self.routes.lock().unwrap().tracked.remove(&self.uuid);
+    }
+}
@@ -0,0 +1,199 @@
+//! Confirming bundles from a Yellowstone transaction stream.
+
+use std::{collections::HashMap, pin::pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures_util::future::{select, Either};
+use geyser_client::{
+    CommitmentLevel, GeyserClient, SignatureWatch, SubscriptionConfig, TransactionLanding,
+};
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
+use tokio::time::Instant;
+
+use crate::{
+    bundle_sender::{BundleSendError, BundleSender},
+    check_landed,
+    leader_schedule::{LeaderScheduleError, LeaderWindow},
+    tip_accounts::TipAccountError,
+    BundleOutcome, LandedBundle, LandedTransaction,
+};
+
+/// How long a bundle is watched after it was sent, unless set with
+/// [`GeyserLandingSender::with_timeout`].
+const DEFAULT_LANDING_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to wait for the stream to catch up once the block engine saw a bundle land.
+const LANDED_GRACE: Duration = Duration::from_secs(2);
+
+/// Confirms the bundles of another sender from a geyser transaction stream.
+///
+/// The signatures of every bundle are subscribed to before it is sent. A bundle counts
+/// as landed the moment all of its transactions were seen, with the slot and block
+/// position of each, and is watched until the landing timeout even when the inner
+/// sender stopped waiting earlier.
+pub struct GeyserLandingSender {
+    inner: Box<dyn BundleSender>,
+    geyser: GeyserClient,
+    commitment: CommitmentLevel,
+    timeout: Duration,
+    watches: HashMap<String, SignatureWatch>,
+}
+
+impl GeyserLandingSender {
+    pub fn new(inner: Box<dyn BundleSender>, geyser: GeyserClient) -> Self {
+        Self {
+            inner,
+            geyser,
+            commitment: CommitmentLevel::Processed,
+            timeout: DEFAULT_LANDING_TIMEOUT,
+            watches: HashMap::new(),
+        }
+    }
+
+    /// Commitment a transaction must reach to count as landed, processed by default.
+    pub fn with_commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// A bundle whose transactions all landed without an error, `None` otherwise.
+fn landed_bundle(
+    landings: Vec<TransactionLanding>,
+    validator: Option<String>,
+) -> Option<LandedBundle> {
+    if landings.iter().any(|landing| landing.err.is_some()) {
+        return None;
+    }
+
+    Some(LandedBundle {
+        slot: landings.iter().map(|landing| landing.slot).max()?,
+        validator,
+        signatures: landings.iter().map(|landing| landing.signature).collect(),
+        transactions: landings
+            .into_iter()
+            .map(|landing| LandedTransaction {
+                signature: landing.signature,
+                slot: landing.slot,
+                index: Some(landing.index),
+                err: landing.err,
+            })
+            .collect(),
+    })
+}
+
+fn log_landing(landed: &LandedBundle) {
+    for transaction in &landed.transactions {
+        match transaction.index {
+            Some(index) => info!(
+                "Transaction {} landed in slot {} at index {}",
+                transaction.signature, transaction.slot, index
+            ),
+            None => info!(
+                "Transaction {} landed in slot {}",
+                transaction.signature, transaction.slot
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl BundleSender for GeyserLandingSender {
+    async fn send_bundle(
+        &mut self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError> {
+        let signatures: Vec<Signature> = transactions.iter().map(|tx| tx.signatures[0]).collect();
+        let watch = match self
+            .geyser
+            .watch_signatures(&signatures, self.commitment, SubscriptionConfig::default())
+            .await
+        {
+            Ok(watch) => Some(watch),
+            Err(e) => {
+                warn!("Confirming without the geyser, subscribing failed: {}", e);
+                None
+            }
+        };
+
+        let bundle_id = self.inner.send_bundle(transactions).await?;
+        if let Some(watch) = watch {
+            self.watches.insert(bundle_id.clone(), watch);
+        }
+
+        Ok(bundle_id)
+    }
+
+    async fn confirm_bundle(
+        &mut self,
+        bundle_id: &str,
+        signatures: &[Signature],
+        rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError> {
+        let Some(mut watch) = self.watches.remove(bundle_id) else {
+            return self
+                .inner
+                .confirm_bundle(bundle_id, signatures, rpc_client)
+                .await;
+        };
+        let deadline = Instant::now() + self.timeout;
+
+        let outcome = {
+            let confirmed = pin!(self.inner.confirm_bundle(bundle_id, signatures, rpc_client));
+            let streamed = pin!(watch.wait_all(deadline));
+            match select(confirmed, streamed).await {
+                Either::Right((landings, confirmed)) => {
+                    match landings.and_then(|landings| landed_bundle(landings, None)) {
+                        Some(landed) => {
+                            info!("Bundle {} seen landing in slot {}", bundle_id, landed.slot);
+                            log_landing(&landed);
+                            return Ok(BundleOutcome::Landed(landed));
+                        }
+                        None => return confirmed.await,
+                    }
+                }
+                Either::Left((outcome, _)) => outcome?,
+            }
+        };
+
+        match outcome {
+            // The block engine lost track of the bundle, it may still land
+            BundleOutcome::TimedOut => match watch.wait_all(deadline).await {
+                Some(landings) => match landed_bundle(landings, None) {
+                    Some(landed) => {
+                        log_landing(&landed);
+                        Ok(BundleOutcome::Landed(landed))
+                    }
+                    None => Ok(BundleOutcome::TimedOut),
+                },
+                None => Ok(check_landed(signatures, rpc_client, None, None).await?),
+            },
+            BundleOutcome::Landed(landed) => {
+                let grace = Instant::now() + LANDED_GRACE;
+                let landed = match watch.wait_all(grace.min(deadline)).await {
+                    Some(landings) => {
+                        landed_bundle(landings, landed.validator.clone()).unwrap_or(landed)
+                    }
+                    None => landed,
+                };
+                log_landing(&landed);
+                Ok(BundleOutcome::Landed(landed))
+            }
+            outcome => Ok(outcome),
+        }
+    }
+
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError> {
+        self.inner.tip_accounts().await
+    }
+
+    async fn wait_for_leader(&mut self) -> Result<Option<LeaderWindow>, LeaderScheduleError> {
+        self.inner.wait_for_leader().await
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use jito_protos::searcher::{
+    ConnectedLeadersRegionedRequest, ConnectedLeadersRequest, ConnectedLeadersResponse,
+    NextScheduledLeaderRequest,
+};
+use log::info;
+use thiserror::Error;
+use tokio::time::sleep;
+use tonic::Status;
+
+use crate::SearcherClient;
+
+/// Approximate slot time, used to sleep until a leader comes into range.
+const SLOT_TIME: Duration = Duration::from_millis(400);
+
+#[derive(Debug, Error)]
+pub enum LeaderScheduleError {
+    #[error("block engine error {0}")]
+    BlockEngine(Box<Status>),
+    #[error("no Jito leader within {slots_ahead} slots after {waited:?}")]
+    TimedOut { slots_ahead: u64, waited: Duration },
+}
+
+impl From<Status> for LeaderScheduleError {
+    fn from(status: Status) -> Self {
+        LeaderScheduleError::BlockEngine(Box::new(status))
+    }
+}
+
+/// When to send a bundle relative to the next Jito-connected leader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderSchedule {
+    /// Send once the next Jito leader is at most this many slots away.
+    pub slots_ahead: u64,
+    /// Regions to consider, all of them when empty.
+    pub regions: Vec<String>,
+    /// Give up waiting after this long.
+    pub timeout: Duration,
+}
+
+impl Default for LeaderSchedule {
+    fn default() -> Self {
+        Self {
+            slots_ahead: 2,
+            regions: Vec::new(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The next Jito-connected leader as seen by the block engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderWindow {
+    pub current_slot: u64,
+    /// First slot of the leader, where a bundle sent now is expected to land.
+    pub leader_slot: u64,
+    pub leader_identity: String,
+    pub region: String,
+}
+
+impl LeaderWindow {
+    pub fn slots_away(&self) -> u64 {
+        self.leader_slot.saturating_sub(self.current_slot)
+    }
+}
+
+pub async fn next_leader(
+    searcher_client: &mut SearcherClient,
+    regions: &[String],
+) -> Result<LeaderWindow, Status> {
+    let response = searcher_client
+        .get_next_scheduled_leader(NextScheduledLeaderRequest {
+            regions: regions.to_vec(),
+        })
+        .await?
+        .into_inner();
+
+    Ok(LeaderWindow {
+        current_slot: response.current_slot,
+        leader_slot: response.next_leader_slot,
+        leader_identity: response.next_leader_identity,
+        region: response.next_leader_region,
+    })
+}
+
+/// Waits until the next Jito leader is within `schedule.slots_ahead` slots.
+pub async fn wait_for_leader(
+    searcher_client: &mut SearcherClient,
+    schedule: &LeaderSchedule,
+) -> Result<LeaderWindow, LeaderScheduleError> {
+    let started = Instant::now();
+
+    loop {
+        let window = next_leader(searcher_client, &schedule.regions).await?;
+
+        // The block engine reports slot 0 when no connected leader is scheduled
+        let known = window.leader_slot >= window.current_slot && window.leader_slot > 0;
+        if known && window.slots_away() <= schedule.slots_ahead {
+            info!(
+                "Jito leader {} ({}) in {} slots, expecting slot {}",
+                window.leader_identity,
+                window.region,
+                window.slots_away(),
+                window.leader_slot
+            );
+            return Ok(window);
+        }
+
+        let waited = started.elapsed();
+        if waited >= schedule.timeout {
+            return Err(LeaderScheduleError::TimedOut {
+                slots_ahead: schedule.slots_ahead,
+                waited,
+            });
+        }
+
+        let wait = if known {
+            SLOT_TIME * (window.slots_away() - schedule.slots_ahead) as u32
+        } else {
+            SLOT_TIME * 4
+        };
+        sleep(wait.min(schedule.timeout - waited)).await;
+    }
+}
+
+fn leader_slots(response: ConnectedLeadersResponse) -> BTreeMap<String, Vec<u64>> {
+    response
+        .connected_validators
+        .into_iter()
+        .map(|(validator, slots)| (validator, slots.slots))
+        .collect()
+}
+
+/// Upcoming leader slots of the validators connected to this block engine, by validator.
+pub async fn connected_leaders(
+    searcher_client: &mut SearcherClient,
+) -> Result<BTreeMap<String, Vec<u64>>, Status> {
+    let response = searcher_client
+        .get_connected_leaders(ConnectedLeadersRequest {})
+        .await?
+        .into_inner();
+
+    Ok(leader_slots(response))
+}
+
+/// Upcoming leader slots of connected validators, by region and validator.
+pub async fn connected_leaders_regioned(
+    searcher_client: &mut SearcherClient,
+    regions: &[String],
+) -> Result<BTreeMap<String, BTreeMap<String, Vec<u64>>>, Status> {
+    let response = searcher_client
+        .get_connected_leaders_regioned(ConnectedLeadersRegionedRequest {
+            regions: regions.to_vec(),
+        })
+        .await?
+        .into_inner();
+
+    Ok(response
+        .connected_validators
+        .into_iter()
+        .map(|(region, leaders)| (region, leader_slots(leaders)))
+        .collect())
+}
@@ -3,39 +3,50 @@ use std::{
     time::{Duration, Instant},
 };
 
-use futures_util::StreamExt;
 use jito_protos::{
     auth::{auth_service_client::AuthServiceClient, Role},
-    bundle::{
-        bundle_result::Result as BundleResultType, rejected::Reason, Accepted, Bundle,
-        BundleResult, InternalError, SimulationFailure, StateAuctionBidRejected,
-        WinningBatchBidRejected,
-    },
+    bundle::{Bundle, DroppedReason},
     convert::proto_packet_from_versioned_tx,
     searcher::{
         searcher_service_client::SearcherServiceClient, SendBundleRequest, SendBundleResponse,
     },
 };
 use log::{info, warn};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::Hash,
     signature::{Keypair, Signature},
-    transaction::VersionedTransaction,
+    transaction::{TransactionError, VersionedTransaction},
 };
 use thiserror::Error;
-use tokio::time::timeout;
+use tokio::time::timeout_at;
 use tonic::{
     codegen::InterceptedService,
     transport,
     transport::{Channel, Endpoint},
-    Response, Status, Streaming,
+    Response, Status,
 };
 
-use crate::token_authenticator::ClientInterceptor;
+use crate::{
+    bundle_tracker::{BundleStatus, BundleTracker, TrackedBundle},
+    token_authenticator::ClientInterceptor,
+};
 
+pub mod bundle_sender;
+pub mod bundle_tracker;
+pub mod landing;
+pub mod leader_schedule;
+pub mod multi_region;
+pub mod retry;
+pub mod session;
+pub mod simulation;
+pub mod tip_accounts;
 pub mod token_authenticator;
 
+/// Searcher service client with the auth interceptor attached.
+pub type SearcherClient = SearcherServiceClient<InterceptedService<Channel, ClientInterceptor>>;
+
 #[derive(Debug, Error)]
 pub enum BlockEngineConnectionError {
     #[error("transport error {0}")]
@@ -44,38 +55,134 @@ pub enum BlockEngineConnectionError {
     ClientError(#[from] Status),
 }
 
-#[derive(Debug, Error)]
+/// Why the block engine refused to forward a bundle, one variant per `Rejected` reason.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum BundleRejectionError {
-    #[error("bundle lost state auction, auction: {0}, tip {1} lamports")]
-    StateAuctionBidRejected(String, u64),
-    #[error("bundle won state auction but failed global auction, auction {0}, tip {1} lamports")]
-    WinningBatchBidRejected(String, u64),
-    #[error("bundle simulation failure on tx {0}, message: {1:?}")]
-    SimulationFailure(String, Option<String>),
-    #[error("internal error {0}")]
-    InternalError(String),
+    #[error(
+        "bundle lost state auction, auction: {auction_id}, tip {simulated_bid_lamports} lamports"
+    )]
+    StateAuctionBidRejected {
+        auction_id: String,
+        simulated_bid_lamports: u64,
+        msg: Option<String>,
+    },
+    #[error("bundle won state auction but failed global auction, auction {auction_id}, tip {simulated_bid_lamports} lamports")]
+    WinningBatchBidRejected {
+        auction_id: String,
+        simulated_bid_lamports: u64,
+        msg: Option<String>,
+    },
+    #[error("bundle simulation failure on tx {tx_signature}, message: {msg:?}")]
+    SimulationFailure {
+        tx_signature: String,
+        msg: Option<String>,
+    },
+    #[error("internal error {msg}")]
+    InternalError { msg: String },
+    /// Reported by the JSON-RPC API, which does not say why.
+    #[error("bundle failed in every region it was sent to")]
+    FailedInAllRegions,
+    #[error("bundle dropped by the block engine, message: {msg}")]
+    DroppedBundle { msg: String },
+}
+
+/// A bundle that landed on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LandedBundle {
+    pub slot: u64,
+    /// Identity of the leader that included the bundle, when the block engine reported it.
+    pub validator: Option<String>,
+    pub signatures: Vec<Signature>,
+    /// Where each transaction landed, in bundle order.
+    pub transactions: Vec<LandedTransaction>,
+}
+
+/// One transaction of a landed bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LandedTransaction {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Position in the block, only known when confirmed from a transaction stream.
+    pub index: Option<u64>,
+    pub err: Option<TransactionError>,
+}
+
+/// What became of a sent bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleOutcome {
+    Landed(LandedBundle),
+    Rejected(BundleRejectionError),
+    /// Forwarded to a leader but never landed.
+    Dropped(DroppedReason),
+    /// Neither a result nor the transactions showed up before the deadline.
+    TimedOut,
+}
+
+impl BundleOutcome {
+    pub fn is_landed(&self) -> bool {
+        matches!(self, BundleOutcome::Landed(_))
+    }
+
+    /// The landed bundle, or why it did not land.
+    pub fn into_result(self) -> Result<LandedBundle, BundleError> {
+        match self {
+            BundleOutcome::Landed(landed) => Ok(landed),
+            BundleOutcome::Rejected(e) => Err(BundleError::Rejected(e)),
+            BundleOutcome::Dropped(reason) => Err(BundleError::Dropped(reason)),
+            BundleOutcome::TimedOut => Err(BundleError::TimedOut),
+        }
+    }
+}
+
+/// A bundle that did not land, for callers that treat that as an error.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BundleError {
+    #[error(transparent)]
+    Rejected(#[from] BundleRejectionError),
+    #[error("bundle forwarded but not landed, reason: {0:?}")]
+    Dropped(DroppedReason),
+    #[error("bundle did not land in time")]
+    TimedOut,
 }
 
 pub type BlockEngineConnectionResult<T> = Result<T, BlockEngineConnectionError>;
 
+/// Connects to the block engine's searcher service.
+///
+/// With an `auth_keypair` the client runs the challenge flow and keeps its token
+/// fresh; without one it connects unauthenticated.
 pub async fn get_searcher_client(
     block_engine_url: &str,
-    auth_keypair: &Arc<Keypair>,
+    auth_keypair: Option<&Arc<Keypair>>,
 ) -> BlockEngineConnectionResult<
     SearcherServiceClient<InterceptedService<Channel, ClientInterceptor>>,
 > {
-    let auth_channel = create_grpc_channel(block_engine_url).await?;
-    let client_interceptor = ClientInterceptor::new(
-        AuthServiceClient::new(auth_channel),
-        auth_keypair,
-        Role::Searcher,
-    )
-    .await?;
+    let (searcher_client, _) = connect_searcher(block_engine_url, auth_keypair).await?;
+    Ok(searcher_client)
+}
+
+/// Like [`get_searcher_client`], also returning the interceptor to watch its token health.
+pub async fn connect_searcher(
+    block_engine_url: &str,
+    auth_keypair: Option<&Arc<Keypair>>,
+) -> BlockEngineConnectionResult<(SearcherClient, ClientInterceptor)> {
+    let client_interceptor = match auth_keypair {
+        Some(auth_keypair) => {
+            let auth_channel = create_grpc_channel(block_engine_url).await?;
+            ClientInterceptor::new(
+                AuthServiceClient::new(auth_channel),
+                auth_keypair,
+                Role::Searcher,
+            )
+            .await?
+        }
+        None => ClientInterceptor::unauthenticated(),
+    };
 
     let searcher_channel = create_grpc_channel(block_engine_url).await?;
     let searcher_client =
-        SearcherServiceClient::with_interceptor(searcher_channel, client_interceptor);
-    Ok(searcher_client)
+        SearcherServiceClient::with_interceptor(searcher_channel, client_interceptor.clone());
+    Ok((searcher_client, client_interceptor))
 }
 
 pub async fn create_grpc_channel(url: &str) -> BlockEngineConnectionResult<Channel> {
@@ -86,12 +193,14 @@ pub async fn create_grpc_channel(url: &str) -> BlockEngineConnectionResult<Chann
     Ok(endpoint.connect().await?)
 }
 
+/// Sends a bundle and waits for it to land, returning the block engine's bundle UUID
+/// and what became of the bundle.
 pub async fn send_bundle_with_confirmation(
     transactions: &[VersionedTransaction],
     rpc_client: &Arc<RpcClient>,
     searcher_client: &mut SearcherServiceClient<InterceptedService<Channel, ClientInterceptor>>,
-    bundle_results_subscription: &mut Streaming<BundleResult>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    bundle_tracker: &BundleTracker,
+) -> Result<(String, BundleOutcome), Box<dyn std::error::Error>> {
     let bundle_signatures: Vec<Signature> =
         transactions.iter().map(|tx| tx.signatures[0]).collect();
 
@@ -101,78 +210,141 @@ pub async fn send_bundle_with_confirmation(
     let uuid = result.into_inner().uuid;
     info!("Bundle sent. UUID: {:?}", uuid);
 
-    info!("Waiting for 10 seconds to hear results...");
-    let mut time_left = 10000;
-    while let Ok(Some(Ok(results))) = timeout(
-        Duration::from_millis(time_left),
-        bundle_results_subscription.next(),
+    let mut bundle = bundle_tracker.track(&uuid);
+    let outcome = confirm_bundle(
+        &bundle_signatures,
+        Some(transactions[0].message.recent_blockhash()),
+        rpc_client,
+        &mut bundle,
     )
-    .await
-    {
-        let instant = Instant::now();
-        info!("bundle results: {:?}", results);
-        match results.result {
-            Some(BundleResultType::Accepted(Accepted {
-                slot: _s,
-                validator_identity: _v,
-            })) => {}
-            Some(BundleResultType::Rejected(rejected)) => {
-                match rejected.reason {
-                    Some(Reason::WinningBatchBidRejected(WinningBatchBidRejected {
-                        auction_id,
-                        simulated_bid_lamports,
-                        msg: _,
-                    })) => {
-                        return Err(Box::new(BundleRejectionError::WinningBatchBidRejected(
-                            auction_id,
-                            simulated_bid_lamports,
-                        )))
-                    }
-                    Some(Reason::StateAuctionBidRejected(StateAuctionBidRejected {
-                        auction_id,
-                        simulated_bid_lamports,
-                        msg: _,
-                    })) => {
-                        return Err(Box::new(BundleRejectionError::StateAuctionBidRejected(
-                            auction_id,
-                            simulated_bid_lamports,
-                        )))
-                    }
-                    Some(Reason::SimulationFailure(SimulationFailure { tx_signature, msg })) => {
-                        return Err(Box::new(BundleRejectionError::SimulationFailure(
-                            tx_signature,
-                            msg,
-                        )))
-                    }
-                    Some(Reason::InternalError(InternalError { msg })) => {
-                        return Err(Box::new(BundleRejectionError::InternalError(msg)))
-                    }
-                    _ => {}
-                };
+    .await?;
+
+    Ok((uuid, outcome))
+}
+
+/// How long to wait for the results of a bundle.
+const RESULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait for an accepted bundle, its blockhash expires well before.
+const ACCEPTED_TIMEOUT: Duration = Duration::from_secs(120);
+const BLOCKHASH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Waits for the results of a sent bundle and checks that every transaction landed.
+///
+/// A bundle a validator accepted may land until `blockhash` expires, so it is waited
+/// for that long instead of timing out. Only fails when the RPC node cannot be asked
+/// for the transactions' status.
+pub async fn confirm_bundle(
+    bundle_signatures: &[Signature],
+    blockhash: Option<&Hash>,
+    rpc_client: &Arc<RpcClient>,
+    bundle: &mut TrackedBundle,
+) -> Result<BundleOutcome, ClientError> {
+    info!(
+        "Waiting up to {:?} for results, longer once a validator accepted the bundle",
+        RESULT_TIMEOUT
+    );
+    let mut validator = None;
+    let mut processed_slot = None;
+    let started = Instant::now();
+    let mut deadline = started + RESULT_TIMEOUT;
+    loop {
+        let status = match timeout_at(deadline.into(), bundle.next()).await {
+            Ok(Some(status)) => status,
+            Ok(None) => break,
+            Err(_) => match (&validator, blockhash) {
+                (Some(_), Some(blockhash))
+                    if started.elapsed() < ACCEPTED_TIMEOUT
+                        && blockhash_valid(rpc_client, blockhash).await =>
+                {
+                    deadline = Instant::now() + BLOCKHASH_POLL_INTERVAL;
+                    continue;
+                }
+                _ => break,
+            },
+        };
+        info!("bundle {} status: {:?}", bundle.uuid(), status);
+        match status {
+            BundleStatus::Accepted {
+                validator: accepted_by,
+                ..
+            } => validator = Some(accepted_by),
+            BundleStatus::Rejected(e) => return Ok(BundleOutcome::Rejected(e)),
+            BundleStatus::Dropped(reason) => return Ok(BundleOutcome::Dropped(reason)),
+            // Landed, the signature check below confirms it
+            BundleStatus::Processed {
+                slot,
+                validator: processed_by,
+                ..
+            } => {
+                validator = Some(processed_by);
+                processed_slot = Some(slot);
+                break;
             }
-            _ => {}
+            BundleStatus::Finalized => break,
         }
-        time_left -= instant.elapsed().as_millis() as u64;
     }
 
-    let futs: Vec<_> = bundle_signatures
+    check_landed(bundle_signatures, rpc_client, processed_slot, validator).await
+}
+
+/// Whether a bundle signed with `blockhash` can still land. Gives up waiting when the
+/// RPC node cannot tell.
+async fn blockhash_valid(rpc_client: &RpcClient, blockhash: &Hash) -> bool {
+    match rpc_client
+        .is_blockhash_valid(blockhash, CommitmentConfig::processed())
+        .await
+    {
+        Ok(valid) => valid,
+        Err(e) => {
+            warn!("Failed to check blockhash {}: {}", blockhash, e);
+            false
+        }
+    }
+}
+
+/// Checks on chain that every transaction of a bundle landed.
+///
+/// `slot` and `validator` are what the block engine reported, if anything.
+pub(crate) async fn check_landed(
+    bundle_signatures: &[Signature],
+    rpc_client: &Arc<RpcClient>,
+    slot: Option<u64>,
+    validator: Option<String>,
+) -> Result<BundleOutcome, ClientError> {
+    let statuses = rpc_client
+        .get_signature_statuses(bundle_signatures)
+        .await?
+        .value;
+    let transactions: Option<Vec<LandedTransaction>> = statuses
         .iter()
-        .map(|sig| {
-            rpc_client.get_signature_status_with_commitment(sig, CommitmentConfig::processed())
+        .zip(bundle_signatures)
+        .map(|(status, signature)| match status {
+            Some(status) if status.err.is_none() => Some(LandedTransaction {
+                signature: *signature,
+                slot: status.slot,
+                index: None,
+                err: None,
+            }),
+            _ => None,
         })
         .collect();
-    let results = futures_util::future::join_all(futs).await;
-    if !results.iter().all(|r| matches!(r, Ok(Some(Ok(()))))) {
+    let Some(transactions) = transactions else {
         warn!("Transactions in bundle did not land");
-        return Err(Box::new(BundleRejectionError::InternalError(
-            "Searcher service did not provide bundle status in time".into(),
-        )));
-    }
+        return Ok(BundleOutcome::TimedOut);
+    };
+
     info!("Bundle landed successfully");
     for sig in bundle_signatures.iter() {
         info!("https://solscan.io/tx/{}", sig);
     }
-    Ok(())
+    Ok(BundleOutcome::Landed(LandedBundle {
+        slot: slot
+            .or_else(|| transactions.iter().map(|tx| tx.slot).max())
+            .unwrap_or_default(),
+        validator,
+        signatures: bundle_signatures.to_vec(),
+        transactions,
+    }))
 }
 
 pub async fn send_bundle_no_wait(
@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures_util::{future::join_all, stream::FuturesUnordered, StreamExt};
+use jito_protos::searcher::GetRegionsRequest;
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
+use tonic::Status;
+
+use crate::{
+    bundle_sender::{BundleSendError, BundleSender},
+    leader_schedule::{LeaderScheduleError, LeaderWindow},
+    tip_accounts::TipAccountError,
+    BundleOutcome, BundleRejectionError, SearcherClient,
+};
+
+/// A block engine in one region.
+pub struct RegionSender {
+    pub region: String,
+    pub sender: Box<dyn BundleSender>,
+    latency: Option<Duration>,
+}
+
+impl RegionSender {
+    pub fn new(region: impl Into<String>, sender: Box<dyn BundleSender>) -> Self {
+        Self {
+            region: region.into(),
+            sender,
+            latency: None,
+        }
+    }
+
+    /// Round trip of the last latency probe, `None` if it failed or never ran.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+}
+
+/// Sends every bundle to several regional block engines and merges what they report.
+///
+/// Each region assigns its own bundle id, callers only ever see the id of the first
+/// region that accepted the bundle.
+pub struct MultiRegionSender {
+    regions: Vec<RegionSender>,
+    fanout: Option<usize>,
+    /// Region index and region-local bundle id of every bundle sent, by the id returned.
+    sent: HashMap<String, Vec<(usize, String)>>,
+}
+
+impl MultiRegionSender {
+    pub fn new(regions: Vec<RegionSender>) -> Self {
+        Self {
+            regions,
+            fanout: None,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Sends to the `fanout` fastest regions only, all of them when `None`.
+    pub fn with_fanout(mut self, fanout: Option<usize>) -> Self {
+        self.fanout = fanout;
+        self
+    }
+
+    /// Times a tip account request to every region and orders them fastest first.
+    ///
+    /// Regions that fail the probe go last.
+    pub async fn measure_latency(&mut self) {
+        join_all(self.regions.iter_mut().map(|region| async move {
+            let started = Instant::now();
+            region.latency = match region.sender.tip_accounts().await {
+                Ok(_) => Some(started.elapsed()),
+                Err(e) => {
+                    warn!("Latency probe to {} failed: {}", region.region, e);
+                    None
+                }
+            };
+        }))
+        .await;
+
+        self.regions
+            .sort_by_key(|region| region.latency.unwrap_or(Duration::MAX));
+        for region in &self.regions {
+            info!(
+                "Block engine {} latency {:?}",
+                region.region, region.latency
+            );
+        }
+    }
+
+    pub fn regions(&self) -> &[RegionSender] {
+        &self.regions
+    }
+
+    fn targets(&self) -> usize {
+        self.fanout
+            .unwrap_or(self.regions.len())
+            .clamp(1, self.regions.len().max(1))
+    }
+}
+
+/// Ranks the outcomes of regions that did not land the bundle, the most cautious one first.
+///
+/// A region that never heard back may still land its copy, so its outcome wins over a
+/// rejection elsewhere and the bundle is only resent once its blockhash expired.
+fn outcome_rank(outcome: &BundleOutcome) -> u8 {
+    match outcome {
+        BundleOutcome::Landed(_) => 0,
+        BundleOutcome::TimedOut => 1,
+        BundleOutcome::Rejected(BundleRejectionError::InternalError { .. }) => 2,
+        BundleOutcome::Dropped(_) => 3,
+        BundleOutcome::Rejected(_) => 4,
+    }
+}
+
+#[async_trait]
+impl BundleSender for MultiRegionSender {
+    async fn send_bundle(
+        &mut self,
+        transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError> {
+        let targets = self.targets();
+        let results = join_all(
+            self.regions
+                .iter_mut()
+                .take(targets)
+                .map(|region| region.sender.send_bundle(transactions)),
+        )
+        .await;
+
+        let mut sent = Vec::new();
+        let mut first_error = None;
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(bundle_id) => sent.push((index, bundle_id)),
+                Err(e) => {
+                    warn!("Sending to {} failed: {}", self.regions[index].region, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        let Some((_, bundle_id)) = sent.first().cloned() else {
+            return Err(first_error.unwrap_or(BundleSendError::NoBlockEngine));
+        };
+        info!(
+            "Bundle {} sent to {} of {} regions",
+            bundle_id,
+            sent.len(),
+            targets
+        );
+        self.sent.insert(bundle_id.clone(), sent);
+
+        Ok(bundle_id)
+    }
+
+    /// Returns as soon as one region saw the bundle land, otherwise once every region gave up.
+    ///
+    /// A region that could not be asked counts as timed out when others answered, its
+    /// copy may still land.
+    async fn confirm_bundle(
+        &mut self,
+        bundle_id: &str,
+        signatures: &[Signature],
+        rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError> {
+        let sent = self.sent.remove(bundle_id).unwrap_or_else(|| {
+            (0..self.targets())
+                .map(|index| (index, bundle_id.to_string()))
+                .collect()
+        });
+
+        let mut pending: FuturesUnordered<_> = self
+            .regions
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, region)| {
+                let (_, region_bundle_id) = sent.iter().find(|(sent_to, _)| *sent_to == index)?;
+                Some(async move {
+                    let outcome = region
+                        .sender
+                        .confirm_bundle(region_bundle_id, signatures, rpc_client)
+                        .await;
+                    (region.region.as_str(), outcome)
+                })
+            })
+            .collect();
+
+        let mut best: Option<BundleOutcome> = None;
+        let mut first_error = None;
+        while let Some((region, outcome)) = pending.next().await {
+            match outcome {
+                Ok(outcome @ BundleOutcome::Landed(_)) => {
+                    info!("Bundle {} landed through {}", bundle_id, region);
+                    return Ok(outcome);
+                }
+                Ok(outcome) => {
+                    info!("Bundle {} in {}: {:?}", bundle_id, region, outcome);
+                    if best
+                        .as_ref()
+                        .is_none_or(|best| outcome_rank(&outcome) < outcome_rank(best))
+                    {
+                        best = Some(outcome);
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Confirming bundle {} in {} failed: {}",
+                        bundle_id, region, e
+                    );
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match (best, first_error) {
+            (Some(_), Some(_)) => Ok(BundleOutcome::TimedOut),
+            (Some(outcome), None) => Ok(outcome),
+            (None, Some(e)) => Err(e),
+            (None, None) => Ok(BundleOutcome::TimedOut),
+        }
+    }
+
+    /// Asks the regions in order until one answers.
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError> {
+        let mut last_error = TipAccountError::Empty;
+        for region in &mut self.regions {
+            match region.sender.tip_accounts().await {
+                Ok(accounts) => return Ok(accounts),
+                Err(e) => {
+                    warn!("Tip accounts from {} unavailable: {}", region.region, e);
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Waits on the schedule of the fastest region.
+    async fn wait_for_leader(&mut self) -> Result<Option<LeaderWindow>, LeaderScheduleError> {
+        match self.regions.first_mut() {
+            Some(region) => region.sender.wait_for_leader().await,
+            None => Ok(None),
+        }
+    }
+}
+
+/// Name of the region a block engine URL points at, its first host label.
+///
+/// `https://ny.mainnet.block-engine.jito.wtf` is in region `ny`.
+pub fn region_name(block_engine_url: &str) -> String {
+    let host = block_engine_url
+        .split_once("://")
+        .map_or(block_engine_url, |(_, rest)| rest);
+    let host = host.split(['/', ':']).next().unwrap_or(host);
+
+    host.split('.').next().unwrap_or(host).to_string()
+}
+
+/// URL of `region` on the same network as `block_engine_url`, which is in region `current`.
+///
+/// `None` when the URL does not name its region in the first host label.
+pub fn region_url(block_engine_url: &str, current: &str, region: &str) -> Option<String> {
+    let (scheme, rest) = block_engine_url.split_once("://")?;
+    let rest = rest.strip_prefix(current)?.strip_prefix('.')?;
+
+    Some(format!("{}://{}.{}", scheme, region, rest))
+}
+
+/// Region name and URL of every online region, as reported by `GetRegions`.
+///
+/// Only the connected region is returned when the other URLs cannot be derived from
+/// `block_engine_url`.
+pub async fn discover_regions(
+    searcher_client: &mut SearcherClient,
+    block_engine_url: &str,
+) -> Result<Vec<(String, String)>, Status> {
+    let response = searcher_client
+        .get_regions(GetRegionsRequest {})
+        .await?
+        .into_inner();
+
+    let mut regions = vec![(
+        response.current_region.clone(),
+        block_engine_url.to_string(),
+    )];
+    for region in response.available_regions {
+        if region == response.current_region {
+            continue;
+        }
+        match region_url(block_engine_url, &response.current_region, &region) {
+            Some(url) => regions.push((region, url)),
+            None => {
+                warn!(
+                    "Cannot derive the URL of region {} from {}",
+                    region, block_engine_url
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(regions)
+}
@@ -0,0 +1,340 @@
+use std::{
+    error::Error,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jito_protos::bundle::DroppedReason;
+use log::{info, warn};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use tokio::time::sleep;
+
+use crate::{
+    bundle_sender::BundleSender, check_landed, simulation::preflight, BundleError, BundleOutcome,
+    BundleRejectionError, LandedBundle, LandedTransaction,
+};
+
+/// How the tip grows between attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TipEscalation {
+    /// Every attempt pays the base tip.
+    Fixed,
+    /// Adds `step` lamports per retry.
+    Linear { step: u64 },
+    /// Multiplies the tip by `factor` per retry.
+    Exponential { factor: f64 },
+}
+
+/// Decides how often a bundle is resubmitted and how much it may tip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first one.
+    pub max_attempts: u32,
+    pub escalation: TipEscalation,
+    /// Upper bound for the escalated tip, in lamports.
+    pub max_tip: u64,
+    /// Simulates every bundle before sending it and gives up if it fails, see
+    /// [`preflight`](crate::simulation::preflight). On by default.
+    pub simulate: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            escalation: TipEscalation::Exponential { factor: 1.5 },
+            max_tip: 100_000_000,
+            simulate: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends once and never retries.
+    pub fn once() -> Self {
+        Self {
+            max_attempts: 1,
+            escalation: TipEscalation::Fixed,
+            max_tip: u64::MAX,
+            simulate: false,
+        }
+    }
+
+    /// Tip paid on `attempt`, counting from 0. Never below `base_tip`, capped at `max_tip`.
+    pub fn tip_for_attempt(&self, base_tip: u64, attempt: u32) -> u64 {
+        let tip = match self.escalation {
+            TipEscalation::Fixed => base_tip,
+            TipEscalation::Linear { step } => {
+                base_tip.saturating_add(step.saturating_mul(attempt as u64))
+            }
+            TipEscalation::Exponential { factor } => {
+                (base_tip as f64 * factor.powi(attempt as i32)).min(u64::MAX as f64) as u64
+            }
+        };
+
+        tip.max(base_tip).min(self.max_tip.max(base_tip))
+    }
+}
+
+/// One submission of a bundle.
+#[derive(Debug, Clone)]
+pub struct BundleAttempt {
+    /// Attempt number, counting from 1.
+    pub attempt: u32,
+    pub tip: u64,
+    /// UUID assigned by the block engine, missing when it refused the bundle outright.
+    pub uuid: Option<String>,
+    pub signatures: Vec<Signature>,
+    pub landed: bool,
+    /// First slot of the Jito leader the bundle was timed for.
+    pub expected_slot: Option<u64>,
+    /// Slot the bundle landed in.
+    pub slot: Option<u64>,
+    /// Where each transaction landed, empty unless the bundle landed.
+    pub transactions: Vec<LandedTransaction>,
+    pub error: Option<String>,
+}
+
+/// Every attempt made by [`send_bundle_with_retry`] and the final result.
+pub struct RetryOutcome {
+    pub attempts: Vec<BundleAttempt>,
+    /// UUID and landing of the bundle that landed, or the error of the last attempt.
+    pub result: Result<(String, LandedBundle), Box<dyn Error>>,
+}
+
+/// Longest wait for a failed bundle's blockhash to expire before resending it.
+const BLOCKHASH_EXPIRY_TIMEOUT: Duration = Duration::from_secs(120);
+const BLOCKHASH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// When a failed bundle may be resent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    /// The bundle provably cannot land anymore.
+    Now,
+    /// The bundle may still land until its blockhash expires.
+    AfterExpiry,
+    Never,
+}
+
+/// Only auction losses, expired blockhashes, engine errors and bundles that silently did not
+/// land are worth paying more for. Of those, only auction losses, bundles failed in every
+/// region and expired blockhashes say the old bundle can no longer land.
+fn retry_kind(error: &(dyn Error + 'static)) -> Retry {
+    match error.downcast_ref::<BundleError>() {
+        Some(
+            BundleError::Rejected(
+                BundleRejectionError::StateAuctionBidRejected { .. }
+                | BundleRejectionError::WinningBatchBidRejected { .. }
+                // Failed bundles were never forwarded to a leader, auction losses are the
+                // usual cause
+                | BundleRejectionError::FailedInAllRegions,
+            )
+            | BundleError::Dropped(DroppedReason::BlockhashExpired),
+        ) => Retry::Now,
+        Some(
+            BundleError::Rejected(BundleRejectionError::InternalError { .. })
+            | BundleError::TimedOut,
+        ) => Retry::AfterExpiry,
+        _ => Retry::Never,
+    }
+}
+
+/// Waits until the bundle signed with `blockhash` can no longer land, so resending it
+/// cannot land it twice. The blockhash has to be expired at finalized commitment, where no
+/// fork can bring it back.
+///
+/// Returns the bundle if it landed after all, and fails when the blockhash is still valid
+/// after [`BLOCKHASH_EXPIRY_TIMEOUT`] or only part of the bundle is on chain.
+async fn wait_for_expiry(
+    rpc_client: &Arc<RpcClient>,
+    blockhash: &Hash,
+    signatures: &[Signature],
+) -> Result<Option<LandedBundle>, Box<dyn Error + Send + Sync>> {
+    let deadline = Instant::now() + BLOCKHASH_EXPIRY_TIMEOUT;
+    info!(
+        "Waiting for blockhash {} to expire before resending",
+        blockhash
+    );
+    while rpc_client
+        .is_blockhash_valid(blockhash, CommitmentConfig::finalized())
+        .await?
+    {
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "blockhash {} still valid after {:?}, not resending",
+                blockhash, BLOCKHASH_EXPIRY_TIMEOUT
+            )
+            .into());
+        }
+        sleep(BLOCKHASH_POLL_INTERVAL).await;
+    }
+
+    // Landed transactions outlive the blockhash, look for them once it expired
+    let statuses = rpc_client.get_signature_statuses(signatures).await?.value;
+    if statuses.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    match check_landed(signatures, rpc_client, None, None).await? {
+        BundleOutcome::Landed(landed) => Ok(Some(landed)),
+        _ => Err("part of the bundle is on chain, not resending".into()),
+    }
+}
+
+/// Sends the bundle produced by `build` until it lands or `policy` gives up.
+///
+/// `build` is called with the tip of each attempt and has to return a freshly
+/// signed bundle, fetching a new blockhash itself. Simulation failures and build
+/// errors end the retries immediately. A bundle whose fate is unknown is only resent
+/// once its blockhash expired without it landing, so the two can never both land.
+pub async fn send_bundle_with_retry<F, Fut, E>(
+    policy: &RetryPolicy,
+    base_tip: u64,
+    mut build: F,
+    rpc_client: &Arc<RpcClient>,
+    sender: &mut dyn BundleSender,
+) -> RetryOutcome
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Vec<VersionedTransaction>, E>>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    let mut attempts = Vec::new();
+
+    for attempt in 0..policy.max_attempts.max(1) {
+        let tip = policy.tip_for_attempt(base_tip, attempt);
+
+        // Wait before building so the blockhash is as fresh as possible when sent
+        let leader = match sender.wait_for_leader().await {
+            Ok(leader) => leader,
+            Err(e) => {
+                warn!("Sending without waiting for a Jito leader: {}", e);
+                None
+            }
+        };
+
+        let transactions = match build(tip).await {
+            Ok(transactions) => transactions,
+            Err(e) => {
+                let e: Box<dyn Error + Send + Sync> = e.into();
+                return RetryOutcome {
+                    attempts,
+                    result: Err(e),
+                };
+            }
+        };
+        let signatures: Vec<Signature> = transactions.iter().map(|tx| tx.signatures[0]).collect();
+
+        info!(
+            "Sending bundle, attempt {}/{} with {} lamports tip",
+            attempt + 1,
+            policy.max_attempts,
+            tip
+        );
+
+        let checked = match policy.simulate {
+            true => preflight(rpc_client, &transactions).await.map(|_| ()),
+            false => Ok(()),
+        };
+
+        let (uuid, result) = match checked {
+            // A bundle that cannot land is not worth a tip or a retry
+            Err(e) => (None, Err(Box::new(e) as Box<dyn Error + Send + Sync>)),
+            Ok(()) => match sender.send_bundle(&transactions).await {
+                Ok(uuid) => {
+                    let result = match sender.confirm_bundle(&uuid, &signatures, rpc_client).await {
+                        Ok(outcome) => outcome
+                            .into_result()
+                            .map_err(|e| Box::new(e) as Box<dyn Error + Send + Sync>),
+                        Err(e) => Err(Box::new(e) as Box<dyn Error + Send + Sync>),
+                    };
+                    (Some(uuid), result)
+                }
+                Err(e) => (None, Err(Box::new(e) as Box<dyn Error + Send + Sync>)),
+            },
+        };
+
+        attempts.push(BundleAttempt {
+            attempt: attempt + 1,
+            tip,
+            uuid: uuid.clone(),
+            signatures,
+            landed: result.is_ok(),
+            expected_slot: leader.map(|leader| leader.leader_slot),
+            slot: result.as_ref().ok().map(|landed| landed.slot),
+            transactions: result
+                .as_ref()
+                .map(|landed| landed.transactions.clone())
+                .unwrap_or_default(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+
+        let e = match result {
+            Ok(landed) => {
+                return RetryOutcome {
+                    attempts,
+                    result: Ok((uuid.unwrap_or_default(), landed)),
+                }
+            }
+            Err(e) if attempt + 1 >= policy.max_attempts => {
+                return RetryOutcome {
+                    attempts,
+                    result: Err(e),
+                }
+            }
+            Err(e) => e,
+        };
+
+        match retry_kind(e.as_ref()) {
+            Retry::Now => {}
+            Retry::AfterExpiry => {
+                let blockhash = *transactions[0].message.recent_blockhash();
+                match wait_for_expiry(
+                    rpc_client,
+                    &blockhash,
+                    &attempts[attempts.len() - 1].signatures,
+                )
+                .await
+                {
+                    Ok(None) => {}
+                    Ok(Some(landed)) => {
+                        info!("Bundle attempt {} landed after all", attempt + 1);
+                        let last = attempts.last_mut().unwrap();
+                        last.landed = true;
+                        last.slot = Some(landed.slot);
+                        last.transactions = landed.transactions.clone();
+                        last.error = None;
+                        return RetryOutcome {
+                            attempts,
+                            result: Ok((uuid.unwrap_or_default(), landed)),
+                        };
+                    }
+                    Err(expiry_error) => {
+                        warn!(
+                            "Not retrying bundle attempt {}: {}",
+                            attempt + 1,
+                            expiry_error
+                        );
+                        return RetryOutcome {
+                            attempts,
+                            result: Err(e),
+                        };
+                    }
+                }
+            }
+            Retry::Never => {
+                return RetryOutcome {
+                    attempts,
+                    result: Err(e),
+                }
+            }
+        }
+        warn!("Bundle attempt {} failed, retrying: {}", attempt + 1, e);
+    }
+
+    unreachable!("the last attempt always returns")
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use jito_protos::searcher::SubscribeBundleResultsRequest;
+use log::{info, warn};
+use solana_sdk::signature::Keypair;
+
+use crate::{
+    bundle_tracker::BundleTracker,
+    connect_searcher,
+    token_authenticator::{AuthHealth, ClientInterceptor},
+    BlockEngineConnectionResult, SearcherClient,
+};
+
+/// A long-lived connection to one block engine.
+///
+/// Owns the authenticated channel, whose interceptor keeps the access token fresh, and a
+/// single bundle results subscription shared by every bundle sent through it. A broken
+/// subscription is replaced before the next bundle goes out, reconnecting and
+/// re-authenticating if the channel itself is gone.
+pub struct SearcherSession {
+    block_engine_url: String,
+    auth_keypair: Option<Arc<Keypair>>,
+    client: SearcherClient,
+    interceptor: ClientInterceptor,
+    tracker: BundleTracker,
+    reconnects: usize,
+}
+
+impl SearcherSession {
+    pub async fn connect(
+        block_engine_url: &str,
+        auth_keypair: Option<&Arc<Keypair>>,
+    ) -> BlockEngineConnectionResult<Self> {
+        let (mut client, interceptor) = connect_searcher(block_engine_url, auth_keypair).await?;
+        let tracker = subscribe(&mut client).await?;
+
+        Ok(Self {
+            block_engine_url: block_engine_url.to_string(),
+            auth_keypair: auth_keypair.cloned(),
+            client,
+            interceptor,
+            tracker,
+            reconnects: 0,
+        })
+    }
+
+    pub fn block_engine_url(&self) -> &str {
+        &self.block_engine_url
+    }
+
+    pub fn client(&mut self) -> &mut SearcherClient {
+        &mut self.client
+    }
+
+    pub fn tracker(&self) -> &BundleTracker {
+        &self.tracker
+    }
+
+    /// Token state of the session, always invalid when connected unauthenticated.
+    pub fn auth_health(&self) -> AuthHealth {
+        self.interceptor.health()
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.auth_keypair.is_some()
+    }
+
+    /// Stops refreshing the session's tokens.
+    pub fn shutdown(&self) {
+        self.interceptor.shutdown();
+    }
+
+    /// How often the results subscription had to be replaced.
+    pub fn reconnects(&self) -> usize {
+        self.reconnects
+    }
+
+    /// Resubscribes to bundle results if the stream ended, reconnecting when that fails.
+    pub async fn ensure_subscribed(&mut self) -> BlockEngineConnectionResult<()> {
+        if !self.tracker.is_closed() {
+            return Ok(());
+        }
+
+        warn!(
+            "Bundle results stream from {} closed, resubscribing",
+            self.block_engine_url
+        );
+        self.tracker = match subscribe(&mut self.client).await {
+            Ok(tracker) => tracker,
+            Err(e) => {
+                warn!("Resubscribing failed ({}), reconnecting", e);
+                (self.client, self.interceptor) =
+                    connect_searcher(&self.block_engine_url, self.auth_keypair.as_ref()).await?;
+                subscribe(&mut self.client).await?
+            }
+        };
+        self.reconnects += 1;
+        info!("Resubscribed to {}", self.block_engine_url);
+
+        Ok(())
+    }
+}
+
+async fn subscribe(client: &mut SearcherClient) -> BlockEngineConnectionResult<BundleTracker> {
+    let results = client
+        .subscribe_bundle_results(SubscribeBundleResultsRequest {})
+        .await?
+        .into_inner();
+
+    Ok(BundleTracker::new(results))
+}
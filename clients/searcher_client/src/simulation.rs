@@ -0,0 +1,332 @@
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSimulateTransactionConfig, rpc_request::RpcRequest, rpc_response::Response,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::InstructionError,
+    signature::Signature,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::UiTransactionEncoding;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SimulationError {
+    #[error("rpc error {0}")]
+    Rpc(Box<ClientError>),
+    #[error("bundle failed simulation\n{0}")]
+    Failed(BundleSimulation),
+}
+
+impl From<ClientError> for SimulationError {
+    fn from(e: ClientError) -> Self {
+        SimulationError::Rpc(Box::new(e))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// The whole bundle in order with `simulateBundle`, each transaction seeing the
+    /// effects of the ones before it.
+    Bundle,
+    /// Every transaction on its own with `simulateTransaction`, against the state before
+    /// the bundle.
+    Sequential,
+}
+
+/// Result of simulating one transaction of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSimulation {
+    pub signature: Signature,
+    /// `false` when an earlier transaction of the bundle failed first.
+    pub executed: bool,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// The decoded error, see [`describe_error`].
+    pub error: Option<String>,
+}
+
+/// Per-transaction report of a bundle simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSimulation {
+    pub mode: SimulationMode,
+    pub transactions: Vec<TransactionSimulation>,
+}
+
+impl BundleSimulation {
+    /// The first failure that proves the bundle cannot land as built.
+    ///
+    /// Sequential simulations run later transactions without the effects of earlier
+    /// ones, so only a failing first transaction is conclusive there.
+    pub fn conclusive_failure(&self) -> Option<(usize, &TransactionSimulation)> {
+        let (index, transaction) = self
+            .transactions
+            .iter()
+            .enumerate()
+            .find(|(_, transaction)| transaction.error.is_some())?;
+
+        match self.mode {
+            SimulationMode::Bundle => Some((index, transaction)),
+            SimulationMode::Sequential if index == 0 => Some((index, transaction)),
+            SimulationMode::Sequential => None,
+        }
+    }
+
+    pub fn units_consumed(&self) -> u64 {
+        self.transactions
+            .iter()
+            .filter_map(|transaction| transaction.units_consumed)
+            .sum()
+    }
+}
+
+impl fmt::Display for BundleSimulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            SimulationMode::Bundle => "as a bundle",
+            SimulationMode::Sequential => "one by one",
+        };
+        write!(
+            f,
+            "Simulated {} transactions {}, {} CU",
+            self.transactions.len(),
+            mode,
+            self.units_consumed()
+        )?;
+
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            write!(f, "\n  #{} {}: ", index, transaction.signature)?;
+            match (&transaction.error, transaction.executed) {
+                (_, false) => write!(f, "not executed")?,
+                (None, true) => write!(f, "ok")?,
+                (Some(error), true) => write!(f, "failed, {}", error)?,
+            }
+            if let Some(units) = transaction.units_consumed {
+                write!(f, " ({} CU)", units)?;
+            }
+            if transaction.error.is_some() {
+                for log in &transaction.logs {
+                    write!(f, "\n      {}", log)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatedBundle {
+    summary: Value,
+    transaction_results: Vec<SimulatedTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatedTransaction {
+    err: Option<TransactionError>,
+    logs: Option<Vec<String>>,
+    units_consumed: Option<u64>,
+}
+
+/// Simulates `transactions` in order with `simulateBundle`, falling back to simulating
+/// them one by one when the RPC does not support it.
+pub async fn simulate_bundle(
+    rpc_client: &RpcClient,
+    transactions: &[VersionedTransaction],
+) -> Result<BundleSimulation, SimulationError> {
+    match simulate_as_bundle(rpc_client, transactions).await {
+        Ok(simulation) => Ok(simulation),
+        Err(e) => {
+            info!("simulateBundle unavailable ({}), simulating one by one", e);
+            simulate_sequentially(rpc_client, transactions).await
+        }
+    }
+}
+
+/// Simulates the whole bundle with a Jito RPC's `simulateBundle`.
+pub async fn simulate_as_bundle(
+    rpc_client: &RpcClient,
+    transactions: &[VersionedTransaction],
+) -> Result<BundleSimulation, SimulationError> {
+    let encoded: Vec<String> = transactions
+        .iter()
+        .map(|tx| STANDARD.encode(bincode::serialize(tx).unwrap()))
+        .collect();
+    let no_accounts = vec![Value::Null; transactions.len()];
+
+    let response: Response<SimulatedBundle> = rpc_client
+        .send(
+            RpcRequest::Custom {
+                method: "simulateBundle",
+            },
+            json!([
+                { "encodedTransactions": encoded },
+                {
+                    "preExecutionAccountsConfigs": no_accounts,
+                    "postExecutionAccountsConfigs": no_accounts,
+                    "transactionEncoding": "base64",
+                    "skipSigVerify": true,
+                    "replaceRecentBlockhash": false,
+                }
+            ]),
+        )
+        .await?;
+    let bundle = response.value;
+
+    // A failed bundle only has results for the transactions executed before the failure
+    let failure = bundle.summary.get("failed");
+    let failed_signature = failure
+        .and_then(|failed| failed.get("tx_signature"))
+        .and_then(Value::as_str);
+
+    let mut results = bundle.transaction_results.into_iter();
+    let transactions = transactions
+        .iter()
+        .map(|tx| {
+            let signature = tx.signatures[0];
+            let result = results.next();
+            let executed = result.is_some();
+            let (err, logs, units_consumed) = match result {
+                Some(result) => (
+                    result.err,
+                    result.logs.unwrap_or_default(),
+                    result.units_consumed,
+                ),
+                None => (None, Vec::new(), None),
+            };
+
+            let error = match err {
+                Some(err) => Some(describe_error(&err, &logs)),
+                None if failed_signature == Some(signature.to_string().as_str()) => {
+                    Some(summary_error(failure))
+                }
+                None => None,
+            };
+
+            TransactionSimulation {
+                signature,
+                executed: executed || error.is_some(),
+                logs,
+                units_consumed,
+                error,
+            }
+        })
+        .collect();
+
+    Ok(BundleSimulation {
+        mode: SimulationMode::Bundle,
+        transactions,
+    })
+}
+
+/// Simulates every transaction on its own against the current state.
+pub async fn simulate_sequentially(
+    rpc_client: &RpcClient,
+    transactions: &[VersionedTransaction],
+) -> Result<BundleSimulation, SimulationError> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: false,
+        commitment: Some(CommitmentConfig::processed()),
+        encoding: Some(UiTransactionEncoding::Base64),
+        ..RpcSimulateTransactionConfig::default()
+    };
+
+    let mut simulated = Vec::with_capacity(transactions.len());
+    for tx in transactions {
+        let result = rpc_client
+            .simulate_transaction_with_config(tx, config.clone())
+            .await?
+            .value;
+        let logs = result.logs.unwrap_or_default();
+
+        simulated.push(TransactionSimulation {
+            signature: tx.signatures[0],
+            executed: true,
+            error: result.err.map(|err| describe_error(&err, &logs)),
+            logs,
+            units_consumed: result.units_consumed,
+        });
+    }
+
+    Ok(BundleSimulation {
+        mode: SimulationMode::Sequential,
+        transactions: simulated,
+    })
+}
+
+/// Simulates the bundle and fails if it provably cannot land.
+///
+/// Simulation being unavailable is not an error, the bundle is sent unchecked then.
+pub async fn preflight(
+    rpc_client: &RpcClient,
+    transactions: &[VersionedTransaction],
+) -> Result<Option<BundleSimulation>, SimulationError> {
+    let simulation = match simulate_bundle(rpc_client, transactions).await {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            warn!("Could not simulate bundle, sending unchecked: {}", e);
+            return Ok(None);
+        }
+    };
+
+    if simulation.conclusive_failure().is_some() {
+        return Err(SimulationError::Failed(simulation));
+    }
+    info!("{}", simulation);
+
+    Ok(Some(simulation))
+}
+
+/// Message of a `simulateBundle` failure summary, e.g.
+/// `{"failed": {"error": {"TransactionFailure": [[...], "message"]}, "tx_signature": "..."}}`.
+fn summary_error(failure: Option<&Value>) -> String {
+    let error = failure.and_then(|failed| failed.get("error"));
+    error
+        .and_then(|error| error.get("TransactionFailure"))
+        .and_then(|failure| failure.get(1))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| error.map(Value::to_string))
+        .unwrap_or_else(|| "bundle simulation failed".to_string())
+}
+
+/// Describes `err` with the failing program and its last log message, where known.
+pub fn describe_error(err: &TransactionError, logs: &[String]) -> String {
+    let mut message = match err {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => format!(
+            "instruction {} failed with custom program error {} (0x{:x})",
+            index, code, code
+        ),
+        TransactionError::InstructionError(index, error) => {
+            format!("instruction {} failed: {}", index, error)
+        }
+        error => error.to_string(),
+    };
+
+    let failed_program = logs.iter().rev().find_map(|log| {
+        let (program, _) = log.strip_prefix("Program ")?.split_once(" failed: ")?;
+        Some(program)
+    });
+    if let Some(program) = failed_program {
+        message.push_str(&format!(" in program {}", program));
+    }
+    if let Some(log) = logs
+        .iter()
+        .rev()
+        .find_map(|log| log.strip_prefix("Program log: "))
+    {
+        message.push_str(&format!(": {}", log));
+    }
+
+    message
+}
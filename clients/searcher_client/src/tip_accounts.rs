@@ -0,0 +1,127 @@
+use std::{
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use jito_protos::searcher::GetTipAccountsRequest;
+use log::{info, warn};
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+use tonic::Status;
+
+use crate::{bundle_sender::BundleSender, SearcherClient};
+
+#[derive(Debug, Error)]
+pub enum TipAccountError {
+    #[error("block engine error {0}")]
+    BlockEngine(Box<Status>),
+    #[error("block engine request failed {0}")]
+    Transport(String),
+    #[error("block engine returned invalid tip account {0}")]
+    InvalidAccount(String),
+    #[error("block engine returned no tip accounts")]
+    Empty,
+    #[error("{0} is not a tip account")]
+    UnknownTipAccount(Pubkey),
+}
+
+/// Fetches the current tip accounts from the block engine.
+pub async fn get_tip_accounts(
+    searcher_client: &mut SearcherClient,
+) -> Result<Vec<Pubkey>, TipAccountError> {
+    let accounts = searcher_client
+        .get_tip_accounts(GetTipAccountsRequest {})
+        .await
+        .map_err(|e| TipAccountError::BlockEngine(Box::new(e)))?
+        .into_inner()
+        .accounts;
+
+    let accounts = accounts
+        .iter()
+        .map(|account| {
+            Pubkey::from_str(account).map_err(|_| TipAccountError::InvalidAccount(account.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if accounts.is_empty() {
+        return Err(TipAccountError::Empty);
+    }
+
+    Ok(accounts)
+}
+
+/// Caches the block engine's tip accounts for `ttl`.
+///
+/// When the block engine cannot be reached and nothing is cached, the `fallback`
+/// accounts are used instead. Fallback lists are never cached, so the next call
+/// tries the block engine again.
+pub struct TipAccountCache {
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, Vec<Pubkey>)>>,
+}
+
+impl TipAccountCache {
+    pub const fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Returns the live tip accounts, refreshing them once they are older than the ttl.
+    pub async fn get(
+        &self,
+        sender: &mut dyn BundleSender,
+        fallback: impl FnOnce() -> Vec<Pubkey>,
+    ) -> Vec<Pubkey> {
+        if let Some((fetched_at, accounts)) = self.cached.lock().unwrap().as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return accounts.clone();
+            }
+        }
+
+        match sender.tip_accounts().await {
+            Ok(accounts) => {
+                info!(
+                    "Fetched {} tip accounts from the block engine",
+                    accounts.len()
+                );
+                *self.cached.lock().unwrap() = Some((Instant::now(), accounts.clone()));
+                accounts
+            }
+            Err(e) => {
+                // A stale live list is still better than the derived one
+                if let Some((_, accounts)) = self.cached.lock().unwrap().as_ref() {
+                    warn!("Failed to refresh tip accounts, using cached list: {}", e);
+                    return accounts.clone();
+                }
+                warn!("Failed to fetch tip accounts, using derived list: {}", e);
+                fallback()
+            }
+        }
+    }
+
+    /// The last live tip account list, regardless of its age.
+    pub fn cached(&self) -> Option<Vec<Pubkey>> {
+        self.cached
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(_, accounts)| accounts.clone())
+    }
+
+    /// Checks `destination` against the live list, or `fallback` when nothing was fetched yet.
+    pub fn validate(
+        &self,
+        destination: &Pubkey,
+        fallback: impl FnOnce() -> Vec<Pubkey>,
+    ) -> Result<(), TipAccountError> {
+        let accounts = self.cached().unwrap_or_else(fallback);
+        if accounts.contains(destination) {
+            Ok(())
+        } else {
+            Err(TipAccountError::UnknownTipAccount(*destination))
+        }
+    }
+}
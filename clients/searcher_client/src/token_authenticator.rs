@@ -7,23 +7,99 @@ use jito_protos::auth::{
     auth_service_client::AuthServiceClient, GenerateAuthChallengeRequest,
     GenerateAuthTokensRequest, RefreshAccessTokenRequest, Role, Token,
 };
-use prost_types::Timestamp;
+use log::{info, warn};
 use solana_metrics::datapoint_info;
 use solana_sdk::signature::{Keypair, Signer};
 use tokio::{task::JoinHandle, time::sleep};
-use tonic::{service::Interceptor, transport::Channel, Request, Status};
+use tonic::{service::Interceptor, transport::Channel, Code, Request, Status};
 
 use crate::BlockEngineConnectionResult;
 
 const AUTHORIZATION_HEADER: &str = "authorization";
 const BEARER: &str = "Bearer ";
 
+/// Replace tokens this long before they expire, or halfway through shorter lifetimes.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// Longest sleep between token checks.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest wait before retrying a failed refresh.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+/// Assumed lifetime of a token the block engine sent without an expiry.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// State of the interceptor's tokens, for callers about to rely on them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthHealth {
+    /// `None` when the interceptor is unauthenticated.
+    pub access_token_expires_at: Option<SystemTime>,
+    pub refresh_token_expires_at: Option<SystemTime>,
+    /// Last time a token was obtained, by refresh or the full challenge flow.
+    pub last_refreshed_at: Option<SystemTime>,
+    /// Error of the last refresh attempt, cleared once a refresh succeeds.
+    pub last_error: Option<String>,
+    /// Failed refresh attempts since the last success.
+    pub consecutive_failures: u32,
+}
+
+impl AuthHealth {
+    /// True while the access token in use has not expired.
+    pub fn is_valid(&self) -> bool {
+        self.access_token_expires_at
+            .is_some_and(|expires_at| expires_at > SystemTime::now())
+    }
+}
+
+/// A token and when it was received, to know when it is due for replacement.
+struct TrackedToken {
+    value: String,
+    received_at: SystemTime,
+    expires_at: SystemTime,
+}
+
+impl TrackedToken {
+    /// A token without an expiry is assumed to live for [`DEFAULT_TOKEN_LIFETIME`], so it is
+    /// replaced regularly instead of on every check.
+    fn new(token: Token) -> Self {
+        let received_at = SystemTime::now();
+        let expires_at = token
+            .expires_at_utc
+            .and_then(|expires_at| SystemTime::try_from(expires_at).ok())
+            .unwrap_or(received_at + DEFAULT_TOKEN_LIFETIME);
+
+        Self {
+            value: token.value,
+            received_at,
+            expires_at,
+        }
+    }
+
+    fn due_at(&self) -> SystemTime {
+        let lifetime = self
+            .expires_at
+            .duration_since(self.received_at)
+            .unwrap_or_default();
+        self.expires_at - REFRESH_MARGIN.min(lifetime / 2)
+    }
+}
+
+/// Aborts the refresh task once the last interceptor clone is dropped.
+struct RefreshTask(JoinHandle<()>);
+
+impl Drop for RefreshTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
 /// Adds the token to each requests' authorization header.
-/// Manages refreshing the token in a separate thread.
+/// Manages refreshing the token in a separate task, stopped by [`ClientInterceptor::shutdown`]
+/// or when the last clone is dropped.
 #[derive(Clone)]
 pub struct ClientInterceptor {
     /// The token added to each request header.
     bearer_token: Arc<RwLock<String>>,
+    health: Arc<RwLock<AuthHealth>>,
+    refresh_task: Option<Arc<RefreshTask>>,
 }
 
 impl ClientInterceptor {
@@ -34,19 +110,61 @@ impl ClientInterceptor {
     ) -> BlockEngineConnectionResult<Self> {
         let (access_token, refresh_token) =
             Self::auth(&mut auth_service_client, keypair, role).await?;
+        let (access_token, refresh_token) = (
+            TrackedToken::new(access_token),
+            TrackedToken::new(refresh_token),
+        );
 
         let bearer_token = Arc::new(RwLock::new(access_token.value.clone()));
+        let health = Arc::new(RwLock::new(AuthHealth {
+            access_token_expires_at: Some(access_token.expires_at),
+            refresh_token_expires_at: Some(refresh_token.expires_at),
+            last_refreshed_at: Some(access_token.received_at),
+            ..AuthHealth::default()
+        }));
 
-        let _refresh_token_thread = Self::spawn_token_refresh_thread(
+        let refresh_task = Self::spawn_token_refresh_task(
             auth_service_client,
             bearer_token.clone(),
+            health.clone(),
+            access_token,
             refresh_token,
-            access_token.expires_at_utc.unwrap(),
             keypair.clone(),
             role,
         );
 
-        Ok(Self { bearer_token })
+        Ok(Self {
+            bearer_token,
+            health,
+            refresh_task: Some(Arc::new(RefreshTask(refresh_task))),
+        })
+    }
+
+    /// An interceptor that never adds an authorization header.
+    pub fn unauthenticated() -> Self {
+        Self {
+            bearer_token: Arc::new(RwLock::new(String::new())),
+            health: Arc::default(),
+            refresh_task: None,
+        }
+    }
+
+    pub fn health(&self) -> AuthHealth {
+        self.health.read().unwrap().clone()
+    }
+
+    /// Whether tokens are still being refreshed.
+    pub fn is_refreshing(&self) -> bool {
+        self.refresh_task
+            .as_ref()
+            .is_some_and(|task| !task.0.is_finished())
+    }
+
+    /// Stops refreshing tokens, for this and every clone. Requests keep the current token.
+    pub fn shutdown(&self) {
+        if let Some(task) = &self.refresh_task {
+            task.0.abort();
+        }
     }
 
     async fn auth(
@@ -73,79 +191,110 @@ impl ClientInterceptor {
             .await?
             .into_inner();
 
-        Ok((tokens.access_token.unwrap(), tokens.refresh_token.unwrap()))
+        match (tokens.access_token, tokens.refresh_token) {
+            (Some(access_token), Some(refresh_token)) => Ok((access_token, refresh_token)),
+            _ => Err(Status::internal("block engine returned no auth tokens").into()),
+        }
     }
 
-    fn spawn_token_refresh_thread(
+    async fn refresh(
+        auth_service_client: &mut AuthServiceClient<Channel>,
+        refresh_token: &TrackedToken,
+    ) -> Result<Token, Status> {
+        auth_service_client
+            .refresh_access_token(RefreshAccessTokenRequest {
+                refresh_token: refresh_token.value.clone(),
+            })
+            .await?
+            .into_inner()
+            .access_token
+            .ok_or_else(|| Status::internal("block engine returned no access token"))
+    }
+
+    fn spawn_token_refresh_task(
         mut auth_service_client: AuthServiceClient<Channel>,
         bearer_token: Arc<RwLock<String>>,
-        refresh_token: Token,
-        access_token_expiration: Timestamp,
+        health: Arc<RwLock<AuthHealth>>,
+        mut access_token: TrackedToken,
+        mut refresh_token: TrackedToken,
         keypair: Arc<Keypair>,
         role: Role,
-    ) -> JoinHandle<BlockEngineConnectionResult<()>> {
+    ) -> JoinHandle<()> {
         tokio::spawn(async move {
-            let mut refresh_token = refresh_token;
-            let mut access_token_expiration = access_token_expiration;
-
             loop {
-                let access_token_ttl = SystemTime::try_from(access_token_expiration.clone())
-                    .unwrap()
-                    .duration_since(SystemTime::now())
-                    .unwrap_or_else(|_| Duration::from_secs(0));
-                let refresh_token_ttl =
-                    SystemTime::try_from(refresh_token.expires_at_utc.as_ref().unwrap().clone())
-                        .unwrap()
-                        .duration_since(SystemTime::now())
-                        .unwrap_or_else(|_| Duration::from_secs(0));
-
-                let does_access_token_expire_soon = access_token_ttl < Duration::from_secs(5 * 60);
-                let does_refresh_token_expire_soon =
-                    refresh_token_ttl < Duration::from_secs(5 * 60);
-
-                match (
-                    does_refresh_token_expire_soon,
-                    does_access_token_expire_soon,
-                ) {
-                    // re-run entire auth workflow is refresh token expiring soon
-                    (true, _) => {
-                        let is_error = {
-                            if let Ok((new_access_token, new_refresh_token)) =
-                                Self::auth(&mut auth_service_client, &keypair, role).await
-                            {
-                                *bearer_token.write().unwrap() = new_access_token.value.clone();
-                                access_token_expiration = new_access_token.expires_at_utc.unwrap();
-                                refresh_token = new_refresh_token;
-                                false
-                            } else {
-                                true
-                            }
-                        };
-                        datapoint_info!("searcher-full-auth", ("is_error", is_error, bool));
-                    }
-                    // re-up the access token if it expires soon
-                    (_, true) => {
-                        let is_error = {
-                            if let Ok(refresh_resp) = auth_service_client
-                                .refresh_access_token(RefreshAccessTokenRequest {
-                                    refresh_token: refresh_token.value.clone(),
-                                })
-                                .await
-                            {
-                                let access_token = refresh_resp.into_inner().access_token.unwrap();
-                                *bearer_token.write().unwrap() = access_token.value.clone();
-                                access_token_expiration = access_token.expires_at_utc.unwrap();
-                                false
-                            } else {
-                                true
-                            }
-                        };
+                let now = SystemTime::now();
+                let refresh_token_due = refresh_token.due_at();
+                let next_due = refresh_token_due.min(access_token.due_at());
+                if now < next_due {
+                    let until_due = next_due.duration_since(now).unwrap_or_default();
+                    sleep(until_due.min(CHECK_INTERVAL)).await;
+                    continue;
+                }
 
-                        datapoint_info!("searcher-refresh-auth", ("is_error", is_error, bool));
+                // re-up the access token, or re-run the entire auth workflow if the refresh
+                // token expires soon or was rejected
+                let mut full_auth = now >= refresh_token_due;
+                let mut result = Err(String::new());
+                if !full_auth {
+                    let refreshed = Self::refresh(&mut auth_service_client, &refresh_token).await;
+                    datapoint_info!(
+                        "searcher-refresh-auth",
+                        ("is_error", refreshed.is_err(), bool)
+                    );
+                    match refreshed {
+                        Ok(token) => result = Ok((token, None)),
+                        Err(status) if status.code() == Code::Unauthenticated => {
+                            warn!("Refresh token rejected ({}), re-authenticating", status);
+                            full_auth = true;
+                        }
+                        Err(status) => result = Err(status.to_string()),
                     }
-                    _ => {
-                        sleep(Duration::from_secs(60)).await;
+                }
+                if full_auth {
+                    let authed = Self::auth(&mut auth_service_client, &keypair, role).await;
+                    datapoint_info!("searcher-full-auth", ("is_error", authed.is_err(), bool));
+                    result = authed
+                        .map(|(access, refresh)| (access, Some(refresh)))
+                        .map_err(|e| e.to_string());
+                }
+
+                let backoff = {
+                    let mut health = health.write().unwrap();
+                    match result {
+                        Ok((new_access_token, new_refresh_token)) => {
+                            access_token = TrackedToken::new(new_access_token);
+                            if let Some(new_refresh_token) = new_refresh_token {
+                                refresh_token = TrackedToken::new(new_refresh_token);
+                            }
+                            *bearer_token.write().unwrap() = access_token.value.clone();
+                            *health = AuthHealth {
+                                access_token_expires_at: Some(access_token.expires_at),
+                                refresh_token_expires_at: Some(refresh_token.expires_at),
+                                last_refreshed_at: Some(access_token.received_at),
+                                last_error: None,
+                                consecutive_failures: 0,
+                            };
+                            if full_auth {
+                                info!("Re-authenticated to the block engine");
+                            }
+                            None
+                        }
+                        Err(e) => {
+                            health.consecutive_failures += 1;
+                            let backoff =
+                                Duration::from_secs(1 << health.consecutive_failures.min(6))
+                                    .min(MAX_RETRY_BACKOFF);
+                            warn!(
+                                "Block engine token refresh failed ({} in a row), retrying in {:?}: {}",
+                                health.consecutive_failures, backoff, e
+                            );
+                            health.last_error = Some(e);
+                            Some(backoff)
+                        }
                     }
+                };
+                if let Some(backoff) = backoff {
+                    sleep(backoff).await;
                 }
             }
         })
@@ -0,0 +1,209 @@
+use std::{sync::Arc, time::Duration};
+
+use jito_mock_block_engine::{JsonRpcReply, MockJsonRpc};
+use jito_searcher_client::{
+    bundle_sender::{BundleSendError, BundleSender, HttpBundleSender},
+    retry::{send_bundle_with_retry, RetryPolicy},
+    BundleOutcome, BundleRejectionError,
+};
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    bs58,
+    hash::Hash,
+    signature::{Keypair, Signer},
+    system_transaction,
+    transaction::VersionedTransaction,
+};
+
+fn transfer(payer: &Keypair) -> VersionedTransaction {
+    system_transaction::transfer(payer, &Keypair::new().pubkey(), 1, Hash::default()).into()
+}
+
+fn inflight(bundle_id: &str, status: &str, landed_slot: Option<u64>) -> JsonRpcReply {
+    JsonRpcReply::Result(json!({
+        "context": { "slot": 100 },
+        "value": [{ "bundle_id": bundle_id, "status": status, "landed_slot": landed_slot }],
+    }))
+}
+
+fn landed_status(bundle_id: &str, slot: u64) -> JsonRpcReply {
+    JsonRpcReply::Result(json!({
+        "context": { "slot": 100 },
+        "value": [{
+            "bundle_id": bundle_id,
+            "transactions": [],
+            "slot": slot,
+            "confirmation_status": "confirmed",
+            "err": { "Ok": null },
+        }],
+    }))
+}
+
+#[tokio::test]
+async fn sends_bundle_as_base58_transactions() {
+    let engine = MockJsonRpc::start().await.unwrap();
+    engine.script("sendBundle", vec![JsonRpcReply::Result(json!("bundle-1"))]);
+    let mut sender = HttpBundleSender::new(&engine.url());
+
+    let payer = Keypair::new();
+    let bundle = vec![transfer(&payer), transfer(&payer)];
+    let bundle_id = sender.send_bundle(&bundle).await.unwrap();
+
+    assert_eq!(bundle_id, "bundle-1");
+    let calls = engine.calls_of("sendBundle");
+    assert_eq!(calls.len(), 1);
+    let sent: Vec<VersionedTransaction> = calls[0].params[0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|encoded| {
+            let bytes = bs58::decode(encoded.as_str().unwrap()).into_vec().unwrap();
+            bincode::deserialize(&bytes).unwrap()
+        })
+        .collect();
+    assert_eq!(sent, bundle);
+}
+
+#[tokio::test]
+async fn polls_pending_bundle_until_landed() {
+    let engine = MockJsonRpc::start().await.unwrap();
+    engine.script(
+        "getInflightBundleStatuses",
+        vec![
+            inflight("bundle-1", "Pending", None),
+            inflight("bundle-1", "Landed", Some(50)),
+        ],
+    );
+    engine.script("getBundleStatuses", vec![landed_status("bundle-1", 51)]);
+    let mut sender = HttpBundleSender::new(&engine.url());
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let signatures = vec![transfer(&Keypair::new()).signatures[0]];
+    let outcome = sender
+        .confirm_bundle("bundle-1", &signatures, &rpc_client)
+        .await
+        .unwrap();
+
+    let BundleOutcome::Landed(landed) = outcome else {
+        panic!("expected landed bundle, got {:?}", outcome);
+    };
+    assert_eq!(landed.slot, 51);
+    assert_eq!(landed.signatures, signatures);
+    assert_eq!(engine.calls_of("getInflightBundleStatuses").len(), 2);
+    assert_eq!(
+        engine.calls_of("getBundleStatuses")[0].params,
+        json!([["bundle-1"]])
+    );
+}
+
+#[tokio::test]
+async fn reports_bundle_failed_in_every_region() {
+    let engine = MockJsonRpc::start().await.unwrap();
+    engine.script(
+        "getInflightBundleStatuses",
+        vec![inflight("bundle-1", "Failed", None)],
+    );
+    let mut sender = HttpBundleSender::new(&engine.url());
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let signatures = vec![transfer(&Keypair::new()).signatures[0]];
+    let outcome = sender
+        .confirm_bundle("bundle-1", &signatures, &rpc_client)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        outcome,
+        BundleOutcome::Rejected(BundleRejectionError::FailedInAllRegions)
+    );
+    assert!(engine.calls_of("getBundleStatuses").is_empty());
+}
+
+#[tokio::test]
+async fn resends_bundle_that_failed_in_every_region() {
+    let engine = MockJsonRpc::start().await.unwrap();
+    engine.script(
+        "sendBundle",
+        vec![
+            JsonRpcReply::Result(json!("bundle-1")),
+            JsonRpcReply::Result(json!("bundle-2")),
+        ],
+    );
+    engine.script(
+        "getInflightBundleStatuses",
+        vec![
+            inflight("bundle-1", "Failed", None),
+            inflight("bundle-2", "Landed", Some(60)),
+        ],
+    );
+    engine.script("getBundleStatuses", vec![landed_status("bundle-2", 60)]);
+    let mut sender = HttpBundleSender::new(&engine.url());
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::default(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    let (bundle_id, landed) = outcome.result.unwrap();
+    assert_eq!(bundle_id, "bundle-2");
+    assert_eq!(landed.slot, 60);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert_eq!(engine.calls_of("sendBundle").len(), 2);
+}
+
+#[tokio::test]
+async fn surfaces_json_rpc_errors() {
+    let engine = MockJsonRpc::start().await.unwrap();
+    engine.script(
+        "sendBundle",
+        vec![JsonRpcReply::Error {
+            code: -32602,
+            message: "bundle contains an expired blockhash".to_string(),
+        }],
+    );
+    let mut sender = HttpBundleSender::new(&engine.url());
+
+    let err = sender
+        .send_bundle(&[transfer(&Keypair::new())])
+        .await
+        .unwrap_err();
+
+    let BundleSendError::JsonRpc { code, message } = err else {
+        panic!("expected a JSON-RPC error, got {}", err);
+    };
+    assert_eq!(code, -32602);
+    assert_eq!(message, "bundle contains an expired blockhash");
+    assert_eq!(engine.calls()[0].params.as_array().map(Vec::len), Some(1));
+    assert!(engine.calls_of("getInflightBundleStatuses").is_empty());
+}
+
+#[tokio::test]
+async fn gives_up_on_unresponsive_block_engine() {
+    // Accepts connections and never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+    let mut sender = HttpBundleSender::new(&url).with_timeout(Duration::from_millis(200));
+
+    let err = sender
+        .send_bundle(&[transfer(&Keypair::new())])
+        .await
+        .unwrap_err();
+
+    let BundleSendError::Http(e) = err else {
+        panic!("expected an HTTP error, got {}", err);
+    };
+    assert!(e.is_timeout());
+}
@@ -0,0 +1,796 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use jito_mock_block_engine::{MockBlockEngine, MockConfig, ScriptedResult};
+use jito_protos::{
+    bundle::{
+        rejected::Reason, BundleResult, InternalError, SimulationFailure, StateAuctionBidRejected,
+    },
+    searcher::{GetTipAccountsRequest, SubscribeBundleResultsRequest},
+};
+use jito_searcher_client::{
+    bundle_sender::{BundleSender, GrpcBundleSender},
+    bundle_tracker::BundleTracker,
+    connect_searcher, get_searcher_client,
+    landing::GeyserLandingSender,
+    leader_schedule::{wait_for_leader, LeaderSchedule, LeaderScheduleError},
+    multi_region::{MultiRegionSender, RegionSender},
+    retry::{send_bundle_with_retry, RetryPolicy},
+    send_bundle_with_confirmation,
+    simulation::{SimulationError, SimulationMode},
+    BundleOutcome, BundleRejectionError, LandedBundle, SearcherClient,
+};
+use mock_geyser::{transaction_update_at, MockGeyser, MockGeyserConfig};
+use serde_json::json;
+use solana_client::{
+    nonblocking::rpc_client::{Mocks, RpcClient},
+    rpc_request::RpcRequest,
+};
+use solana_sdk::{
+    hash::Hash,
+    signature::{Keypair, Signer},
+    system_transaction,
+    transaction::VersionedTransaction,
+};
+use tonic::Code;
+
+fn transfer(payer: &Keypair) -> VersionedTransaction {
+    system_transaction::transfer(payer, &Keypair::new().pubkey(), 1, Hash::default()).into()
+}
+
+async fn tracked_client(engine: &MockBlockEngine) -> (SearcherClient, BundleTracker) {
+    let mut client = get_searcher_client(&engine.url(), None).await.unwrap();
+    let tracker = BundleTracker::new(
+        client
+            .subscribe_bundle_results(SubscribeBundleResultsRequest {})
+            .await
+            .unwrap()
+            .into_inner(),
+    );
+    (client, tracker)
+}
+
+#[tokio::test]
+async fn authenticates_with_challenge_flow() {
+    let engine = MockBlockEngine::start(MockConfig {
+        require_auth: true,
+        tip_accounts: vec![Keypair::new().pubkey().to_string()],
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+
+    let mut unauthenticated = get_searcher_client(&engine.url(), None).await.unwrap();
+    let status = unauthenticated
+        .get_tip_accounts(GetTipAccountsRequest {})
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let keypair = Arc::new(Keypair::new());
+    let mut client = get_searcher_client(&engine.url(), Some(&keypair))
+        .await
+        .unwrap();
+    let accounts = client
+        .get_tip_accounts(GetTipAccountsRequest {})
+        .await
+        .unwrap()
+        .into_inner()
+        .accounts;
+
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(engine.full_auths(), 1);
+}
+
+#[tokio::test]
+async fn reports_landed_bundle() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(ScriptedResult::landed(42, "leader"));
+    let (mut client, tracker) = tracked_client(&engine).await;
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let bundle = vec![transfer(&payer), transfer(&payer)];
+    let (uuid, outcome) =
+        send_bundle_with_confirmation(&bundle, &rpc_client, &mut client, &tracker)
+            .await
+            .unwrap();
+
+    let BundleOutcome::Landed(landed) = outcome else {
+        panic!("expected landed bundle, got {:?}", outcome);
+    };
+    assert_eq!(landed.slot, 42);
+    assert_eq!(landed.validator.as_deref(), Some("leader"));
+    assert_eq!(landed.signatures.len(), 2);
+    assert_eq!(engine.bundles()[0].uuid, uuid);
+}
+
+#[tokio::test]
+async fn waits_for_accepted_bundle_while_blockhash_is_valid() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(vec![
+        ScriptedResult::accepted(42, "leader"),
+        // Past the 10 second result window
+        ScriptedResult::processed(42, "leader", 0).after(Duration::from_secs(11)),
+    ]);
+    let (mut client, tracker) = tracked_client(&engine).await;
+    let mocks = Mocks::from([(
+        RpcRequest::IsBlockhashValid,
+        json!({ "context": { "slot": 1 }, "value": true }),
+    )]);
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+
+    let payer = Keypair::new();
+    let (_, outcome) =
+        send_bundle_with_confirmation(&[transfer(&payer)], &rpc_client, &mut client, &tracker)
+            .await
+            .unwrap();
+
+    let BundleOutcome::Landed(landed) = outcome else {
+        panic!("expected landed bundle, got {:?}", outcome);
+    };
+    assert_eq!(landed.slot, 42);
+}
+
+#[tokio::test]
+async fn ignores_results_of_other_bundles() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(vec![ScriptedResult::rejected(Reason::SimulationFailure(
+        SimulationFailure {
+            tx_signature: "sig".to_string(),
+            msg: Some("custom program error".to_string()),
+        },
+    ))]);
+    let (mut client, tracker) = tracked_client(&engine).await;
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    // A result for a bundle sent by someone else must not end this one
+    engine.emit(BundleResult {
+        bundle_id: "other".to_string(),
+        result: Some(ScriptedResult::finalized().result),
+    });
+
+    let payer = Keypair::new();
+    let (_, outcome) =
+        send_bundle_with_confirmation(&[transfer(&payer)], &rpc_client, &mut client, &tracker)
+            .await
+            .unwrap();
+
+    assert_eq!(
+        outcome,
+        BundleOutcome::Rejected(BundleRejectionError::SimulationFailure {
+            tx_signature: "sig".to_string(),
+            msg: Some("custom program error".to_string()),
+        })
+    );
+}
+
+#[tokio::test]
+async fn retries_lost_auction_with_higher_tip() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(vec![ScriptedResult::rejected(
+        Reason::StateAuctionBidRejected(StateAuctionBidRejected {
+            auction_id: "auction".to_string(),
+            simulated_bid_lamports: 1_000,
+            msg: None,
+        }),
+    )]);
+    engine.push_script(ScriptedResult::landed(7, "leader"));
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::default(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    let (_, landed) = outcome.result.unwrap();
+    assert_eq!(landed.slot, 7);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert!(!outcome.attempts[0].landed);
+    assert!(outcome.attempts[1].tip > outcome.attempts[0].tip);
+    assert_eq!(engine.bundles().len(), 2);
+}
+
+fn internal_error() -> Vec<ScriptedResult> {
+    vec![ScriptedResult::rejected(Reason::InternalError(
+        InternalError {
+            msg: "engine restarted".to_string(),
+        },
+    ))]
+}
+
+#[tokio::test]
+async fn resends_after_internal_error_once_blockhash_expired() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(internal_error());
+    engine.push_script(ScriptedResult::landed(9, "leader"));
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+    let mocks = Mocks::from([
+        (
+            RpcRequest::IsBlockhashValid,
+            json!({ "context": { "slot": 1 }, "value": false }),
+        ),
+        (
+            RpcRequest::GetSignatureStatuses,
+            json!({ "context": { "slot": 1 }, "value": [null] }),
+        ),
+    ]);
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::default(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    let (_, landed) = outcome.result.unwrap();
+    assert_eq!(landed.slot, 9);
+    assert_eq!(outcome.attempts.len(), 2);
+    assert_eq!(engine.bundles().len(), 2);
+}
+
+#[tokio::test]
+async fn keeps_bundle_that_landed_despite_internal_error() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(internal_error());
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+    let mocks = Mocks::from([(
+        RpcRequest::IsBlockhashValid,
+        json!({ "context": { "slot": 1 }, "value": false }),
+    )]);
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::default(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    assert!(outcome.result.is_ok());
+    assert_eq!(outcome.attempts.len(), 1);
+    assert!(outcome.attempts[0].landed);
+    assert_eq!(engine.bundles().len(), 1);
+}
+
+fn leaders(
+    region: &str,
+    validator: &str,
+    slots: Vec<u64>,
+) -> BTreeMap<String, BTreeMap<String, Vec<u64>>> {
+    BTreeMap::from([(
+        region.to_string(),
+        BTreeMap::from([(validator.to_string(), slots)]),
+    )])
+}
+
+#[tokio::test]
+async fn times_bundle_for_next_leader() {
+    let engine = MockBlockEngine::start(MockConfig {
+        connected_leaders: leaders("ny", "leader", vec![1_001, 1_002]),
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    engine.push_script(ScriptedResult::landed(1_001, "leader"));
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap()
+        .with_schedule(Some(LeaderSchedule::default()));
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::once(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    assert!(outcome.result.is_ok());
+    assert_eq!(outcome.attempts[0].expected_slot, Some(1_001));
+}
+
+#[tokio::test]
+async fn gives_up_waiting_for_distant_leader() {
+    let engine = MockBlockEngine::start(MockConfig {
+        connected_leaders: leaders("ny", "leader", vec![2_000]),
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    let mut client = get_searcher_client(&engine.url(), None).await.unwrap();
+
+    let schedule = LeaderSchedule {
+        timeout: Duration::from_millis(500),
+        ..LeaderSchedule::default()
+    };
+    let err = wait_for_leader(&mut client, &schedule).await.unwrap_err();
+
+    assert!(matches!(
+        err,
+        LeaderScheduleError::TimedOut { slots_ahead: 2, .. }
+    ));
+}
+
+async fn region(engine: &MockBlockEngine, name: &str) -> RegionSender {
+    RegionSender::new(
+        name,
+        Box::new(
+            GrpcBundleSender::connect(&engine.url(), None)
+                .await
+                .unwrap(),
+        ),
+    )
+}
+
+fn with_tip_accounts() -> MockConfig {
+    MockConfig {
+        tip_accounts: vec![Keypair::new().pubkey().to_string()],
+        ..MockConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn lands_through_any_region() {
+    let ny = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    let amsterdam = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    ny.push_script(vec![ScriptedResult::rejected(
+        Reason::StateAuctionBidRejected(StateAuctionBidRejected {
+            auction_id: "auction".to_string(),
+            simulated_bid_lamports: 1_000,
+            msg: None,
+        }),
+    )]);
+    amsterdam.push_script(ScriptedResult::landed(9, "leader"));
+    let mut sender = MultiRegionSender::new(vec![
+        region(&ny, "ny").await,
+        region(&amsterdam, "amsterdam").await,
+    ]);
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::once(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    let (_, landed) = outcome.result.unwrap();
+    assert_eq!(landed.slot, 9);
+    assert_eq!(ny.bundles().len(), 1);
+    assert_eq!(amsterdam.bundles().len(), 1);
+}
+
+#[tokio::test]
+async fn waits_for_silent_region_before_resending_rejected_bundle() {
+    let ny = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    let amsterdam = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    ny.push_script(vec![ScriptedResult::rejected(
+        Reason::StateAuctionBidRejected(StateAuctionBidRejected {
+            auction_id: "auction".to_string(),
+            simulated_bid_lamports: 1_000,
+            msg: None,
+        }),
+    )]);
+    let mut sender = MultiRegionSender::new(vec![
+        region(&ny, "ny").await,
+        region(&amsterdam, "amsterdam").await,
+    ]);
+    // Amsterdam never reports, its copy shows up on chain once the blockhash expired
+    let mocks = Mocks::from([
+        (
+            RpcRequest::GetSignatureStatuses,
+            json!({ "context": { "slot": 1 }, "value": [null] }),
+        ),
+        (
+            RpcRequest::IsBlockhashValid,
+            json!({ "context": { "slot": 1 }, "value": false }),
+        ),
+    ]);
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::default(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    assert!(outcome.result.is_ok());
+    assert_eq!(outcome.attempts.len(), 1);
+    assert!(outcome.attempts[0].landed);
+    assert_eq!(ny.bundles().len(), 1);
+    assert_eq!(amsterdam.bundles().len(), 1);
+}
+
+#[tokio::test]
+async fn sends_to_fastest_regions_only() {
+    let ny = MockBlockEngine::start(with_tip_accounts()).await.unwrap();
+    let frankfurt = MockBlockEngine::start(with_tip_accounts()).await.unwrap();
+    ny.push_script(ScriptedResult::landed(5, "leader"));
+    frankfurt.push_script(ScriptedResult::landed(5, "leader"));
+    let mut sender = MultiRegionSender::new(vec![
+        region(&ny, "ny").await,
+        region(&frankfurt, "frankfurt").await,
+    ])
+    .with_fanout(Some(1));
+    sender.measure_latency().await;
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    assert!(sender
+        .regions()
+        .iter()
+        .all(|region| region.latency().is_some()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy::once(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    assert!(outcome.result.is_ok());
+    assert_eq!(ny.bundles().len() + frankfurt.bundles().len(), 1);
+}
+
+async fn send_once(
+    sender: &mut GrpcBundleSender,
+    rpc_client: &Arc<RpcClient>,
+) -> Result<(String, LandedBundle), Box<dyn std::error::Error>> {
+    let payer = Keypair::new();
+    send_bundle_with_retry(
+        &RetryPolicy::once(),
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer)]) },
+        rpc_client,
+        sender,
+    )
+    .await
+    .result
+}
+
+#[tokio::test]
+async fn resubscribes_after_results_stream_error() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(ScriptedResult::landed(3, "leader"));
+    engine.push_script(ScriptedResult::landed(4, "leader"));
+    engine.push_script(ScriptedResult::landed(5, "leader"));
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    assert!(send_once(&mut sender, &rpc_client).await.is_ok());
+    assert!(send_once(&mut sender, &rpc_client).await.is_ok());
+    assert_eq!(engine.subscriptions(), 1);
+
+    engine.disconnect_subscribers();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (_, landed) = send_once(&mut sender, &rpc_client).await.unwrap();
+    assert_eq!(landed.slot, 5);
+    assert_eq!(engine.subscriptions(), 2);
+    assert_eq!(sender.session().reconnects(), 1);
+}
+
+fn short_lived_tokens() -> MockConfig {
+    MockConfig {
+        require_auth: true,
+        access_token_ttl: Duration::from_secs(2),
+        tip_accounts: vec![Keypair::new().pubkey().to_string()],
+        ..MockConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn refreshes_access_token_before_expiry() {
+    let engine = MockBlockEngine::start(short_lived_tokens()).await.unwrap();
+    let keypair = Arc::new(Keypair::new());
+    let (mut client, interceptor) = connect_searcher(&engine.url(), Some(&keypair))
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(2_500)).await;
+
+    assert!(engine.refreshes() >= 1);
+    assert_eq!(engine.full_auths(), 1);
+    let health = interceptor.health();
+    assert!(health.is_valid());
+    assert_eq!(health.last_error, None);
+    client
+        .get_tip_accounts(GetTipAccountsRequest {})
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reauthenticates_when_refresh_token_rejected() {
+    let engine = MockBlockEngine::start(short_lived_tokens()).await.unwrap();
+    let keypair = Arc::new(Keypair::new());
+    let (_client, interceptor) = connect_searcher(&engine.url(), Some(&keypair))
+        .await
+        .unwrap();
+    engine.expire_refresh_tokens();
+
+    tokio::time::sleep(Duration::from_millis(1_500)).await;
+
+    assert_eq!(engine.full_auths(), 2);
+    assert!(interceptor.health().is_valid());
+}
+
+#[tokio::test]
+async fn shutdown_stops_token_refresh() {
+    let engine = MockBlockEngine::start(short_lived_tokens()).await.unwrap();
+    let keypair = Arc::new(Keypair::new());
+    let (_client, interceptor) = connect_searcher(&engine.url(), Some(&keypair))
+        .await
+        .unwrap();
+    assert!(interceptor.is_refreshing());
+
+    interceptor.shutdown();
+    tokio::time::sleep(Duration::from_millis(1_500)).await;
+
+    assert!(!interceptor.is_refreshing());
+    assert_eq!(engine.refreshes(), 0);
+}
+
+#[tokio::test]
+async fn keeps_tokens_without_expiry() {
+    let engine = MockBlockEngine::start(MockConfig {
+        require_auth: true,
+        omit_token_expiry: true,
+        ..MockConfig::default()
+    })
+    .await
+    .unwrap();
+    let keypair = Arc::new(Keypair::new());
+    let (_client, interceptor) = connect_searcher(&engine.url(), Some(&keypair))
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(1_500)).await;
+
+    assert_eq!(engine.refreshes(), 0);
+    assert_eq!(engine.full_auths(), 1);
+    assert!(interceptor.health().is_valid());
+}
+
+#[tokio::test]
+async fn skips_bundle_that_fails_simulation() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+
+    let payer = Keypair::new();
+    let bundle = vec![transfer(&payer), transfer(&payer)];
+    let failing = bundle[1].signatures[0].to_string();
+    let mocks = Mocks::from([(
+        RpcRequest::Custom {
+            method: "simulateBundle",
+        },
+        json!({
+            "context": { "slot": 1 },
+            "value": {
+                "summary": {
+                    "failed": {
+                        "error": { "TransactionFailure": [[], "custom program error: 0x1"] },
+                        "tx_signature": failing,
+                    }
+                },
+                "transactionResults": [
+                    { "err": null, "logs": [], "unitsConsumed": 150 },
+                    {
+                        "err": { "InstructionError": [0, { "Custom": 1 }] },
+                        "logs": [
+                            "Program log: Error: insufficient funds",
+                            "Program Tokenkeg failed: custom program error: 0x1",
+                        ],
+                        "unitsConsumed": 4_000,
+                    },
+                ],
+            },
+        }),
+    )]);
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy {
+            simulate: true,
+            ..RetryPolicy::default()
+        },
+        1_000,
+        |_tip| {
+            let bundle = bundle.clone();
+            async move { Ok::<_, std::io::Error>(bundle) }
+        },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    let err = outcome.result.unwrap_err();
+    let Some(SimulationError::Failed(simulation)) = err.downcast_ref::<SimulationError>() else {
+        panic!("expected a failed simulation, got {}", err);
+    };
+    assert_eq!(simulation.mode, SimulationMode::Bundle);
+    assert_eq!(simulation.transactions[0].units_consumed, Some(150));
+    assert_eq!(
+        simulation.transactions[1].error.as_deref(),
+        Some(
+            "instruction 0 failed with custom program error 1 (0x1) in program Tokenkeg: \
+             Error: insufficient funds"
+        )
+    );
+    assert_eq!(outcome.attempts.len(), 1);
+    assert!(engine.bundles().is_empty());
+}
+
+#[tokio::test]
+async fn simulates_one_by_one_without_simulate_bundle() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(ScriptedResult::landed(8, "leader"));
+    let mut sender = GrpcBundleSender::connect(&engine.url(), None)
+        .await
+        .unwrap();
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let outcome = send_bundle_with_retry(
+        &RetryPolicy {
+            simulate: true,
+            ..RetryPolicy::once()
+        },
+        1_000,
+        |_tip| async { Ok::<_, std::io::Error>(vec![transfer(&payer), transfer(&payer)]) },
+        &rpc_client,
+        &mut sender,
+    )
+    .await;
+
+    assert!(outcome.result.is_ok());
+    assert_eq!(engine.bundles().len(), 1);
+}
+
+async fn geyser_sender(engine: &MockBlockEngine, geyser: &MockGeyser) -> GeyserLandingSender {
+    GeyserLandingSender::new(
+        Box::new(
+            GrpcBundleSender::connect(&engine.url(), None)
+                .await
+                .unwrap(),
+        ),
+        geyser_client::connect(&geyser.url(), None).await.unwrap(),
+    )
+}
+
+/// Waits until the mock geyser saw the bundle's subscription, so emitted updates reach it.
+async fn wait_for_watch(geyser: &MockGeyser) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while geyser.requests().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("bundle signatures never subscribed");
+}
+
+#[tokio::test]
+async fn confirms_landing_from_geyser_stream() {
+    // The block engine never reports a result
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let mut sender = geyser_sender(&engine, &geyser).await;
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let bundle = vec![transfer(&payer), transfer(&payer)];
+    let signatures: Vec<_> = bundle.iter().map(|tx| tx.signatures[0]).collect();
+    let uuid = sender.send_bundle(&bundle).await.unwrap();
+    wait_for_watch(&geyser).await;
+
+    geyser.emit(transaction_update_at(&bundle[0], 77, 4, None));
+    geyser.emit(transaction_update_at(&bundle[1], 77, 5, None));
+
+    let outcome = tokio::time::timeout(
+        Duration::from_secs(5),
+        sender.confirm_bundle(&uuid, &signatures, &rpc_client),
+    )
+    .await
+    .expect("landing not reported before the block engine window")
+    .unwrap();
+
+    let BundleOutcome::Landed(landed) = outcome else {
+        panic!("expected landed bundle, got {:?}", outcome);
+    };
+    assert_eq!(landed.slot, 77);
+    assert_eq!(landed.signatures, signatures);
+    assert_eq!(
+        landed
+            .transactions
+            .iter()
+            .map(|tx| (tx.signature, tx.slot, tx.index, tx.err.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (signatures[0], 77, Some(4), None),
+            (signatures[1], 77, Some(5), None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn adds_stream_positions_to_block_engine_landing() {
+    let engine = MockBlockEngine::start(MockConfig::default()).await.unwrap();
+    engine.push_script(ScriptedResult::landed(42, "leader"));
+    let geyser = MockGeyser::start(MockGeyserConfig::default())
+        .await
+        .unwrap();
+    let mut sender = geyser_sender(&engine, &geyser).await;
+    let rpc_client = Arc::new(RpcClient::new_mock("succeeds".to_string()));
+
+    let payer = Keypair::new();
+    let bundle = vec![transfer(&payer)];
+    let signatures: Vec<_> = bundle.iter().map(|tx| tx.signatures[0]).collect();
+    let uuid = sender.send_bundle(&bundle).await.unwrap();
+    wait_for_watch(&geyser).await;
+
+    let confirming = sender.confirm_bundle(&uuid, &signatures, &rpc_client);
+    let emitting = async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        geyser.emit(transaction_update_at(&bundle[0], 42, 9, None));
+    };
+    let (outcome, _) = tokio::join!(confirming, emitting);
+
+    let BundleOutcome::Landed(landed) = outcome.unwrap() else {
+        panic!("expected landed bundle");
+    };
+    assert_eq!(landed.slot, 42);
+    assert_eq!(landed.validator.as_deref(), Some("leader"));
+    assert_eq!(landed.transactions[0].index, Some(9));
+}
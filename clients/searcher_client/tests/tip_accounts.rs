@@ -0,0 +1,126 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use jito_searcher_client::{
+    bundle_sender::{BundleSendError, BundleSender},
+    tip_accounts::{TipAccountCache, TipAccountError},
+    BundleOutcome,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTransaction};
+
+/// Answers `tip_accounts` from a script, an exhausted script fails like an unreachable engine.
+struct FakeSender {
+    tip_accounts: VecDeque<Result<Vec<Pubkey>, TipAccountError>>,
+    calls: usize,
+}
+
+impl FakeSender {
+    fn new(tip_accounts: Vec<Result<Vec<Pubkey>, TipAccountError>>) -> Self {
+        Self {
+            tip_accounts: tip_accounts.into(),
+            calls: 0,
+        }
+    }
+}
+
+#[async_trait]
+impl BundleSender for FakeSender {
+    async fn send_bundle(
+        &mut self,
+        _transactions: &[VersionedTransaction],
+    ) -> Result<String, BundleSendError> {
+        unimplemented!("only tip accounts are fetched")
+    }
+
+    async fn confirm_bundle(
+        &mut self,
+        _bundle_id: &str,
+        _signatures: &[Signature],
+        _rpc_client: &Arc<RpcClient>,
+    ) -> Result<BundleOutcome, BundleSendError> {
+        unimplemented!("only tip accounts are fetched")
+    }
+
+    async fn tip_accounts(&mut self) -> Result<Vec<Pubkey>, TipAccountError> {
+        self.calls += 1;
+        self.tip_accounts
+            .pop_front()
+            .unwrap_or_else(|| Err(TipAccountError::Transport("unreachable".to_string())))
+    }
+}
+
+fn accounts() -> Vec<Pubkey> {
+    (0..8).map(|_| Pubkey::new_unique()).collect()
+}
+
+#[tokio::test]
+async fn caches_tip_accounts_for_ttl() {
+    let live = accounts();
+    let mut sender = FakeSender::new(vec![Ok(live.clone()), Ok(accounts())]);
+    let cache = TipAccountCache::new(Duration::from_secs(60));
+
+    assert_eq!(cache.get(&mut sender, accounts).await, live);
+    assert_eq!(cache.get(&mut sender, accounts).await, live);
+    assert_eq!(sender.calls, 1);
+    assert_eq!(cache.cached(), Some(live));
+}
+
+#[tokio::test]
+async fn refreshes_tip_accounts_after_ttl() {
+    let (first, second) = (accounts(), accounts());
+    let mut sender = FakeSender::new(vec![Ok(first.clone()), Ok(second.clone())]);
+    let cache = TipAccountCache::new(Duration::from_millis(50));
+
+    assert_eq!(cache.get(&mut sender, accounts).await, first);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(cache.get(&mut sender, accounts).await, second);
+    assert_eq!(sender.calls, 2);
+    assert_eq!(cache.cached(), Some(second));
+}
+
+#[tokio::test]
+async fn keeps_stale_list_when_refresh_fails() {
+    let live = accounts();
+    let mut sender = FakeSender::new(vec![Ok(live.clone())]);
+    let cache = TipAccountCache::new(Duration::ZERO);
+    cache.get(&mut sender, accounts).await;
+
+    let fallback = accounts();
+    assert_eq!(cache.get(&mut sender, || fallback.clone()).await, live);
+    assert_eq!(sender.calls, 2);
+    assert_eq!(cache.cached(), Some(live));
+}
+
+#[tokio::test]
+async fn never_caches_fallback_list() {
+    let (fallback, live) = (accounts(), accounts());
+    let mut sender = FakeSender::new(vec![Err(TipAccountError::Empty), Ok(live.clone())]);
+    let cache = TipAccountCache::new(Duration::from_secs(60));
+
+    assert_eq!(cache.get(&mut sender, || fallback.clone()).await, fallback);
+    assert_eq!(cache.cached(), None);
+
+    // The next call asks the block engine again instead of reusing the fallback
+    assert_eq!(cache.get(&mut sender, || fallback.clone()).await, live);
+    assert_eq!(sender.calls, 2);
+    assert_eq!(cache.cached(), Some(live));
+}
+
+#[tokio::test]
+async fn validates_against_live_list_once_fetched() {
+    let (fallback, live) = (accounts(), accounts());
+    let mut sender = FakeSender::new(vec![Ok(live.clone())]);
+    let cache = TipAccountCache::new(Duration::from_secs(60));
+
+    assert!(cache.validate(&fallback[0], || fallback.clone()).is_ok());
+    assert!(matches!(
+        cache.validate(&live[0], || fallback.clone()),
+        Err(TipAccountError::UnknownTipAccount(account)) if account == live[0]
+    ));
+
+    cache.get(&mut sender, || fallback.clone()).await;
+    assert!(cache.validate(&live[0], || fallback.clone()).is_ok());
+    assert!(cache.validate(&fallback[0], || fallback.clone()).is_err());
+}
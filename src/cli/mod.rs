@@ -0,0 +1,368 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use log::info;
+use solana_sdk::{native_token::sol_to_lamports, pubkey::Pubkey, signer::Signer};
+
+use crate::{
+    env::{encrypt_settings, read_settings},
+    raydium::{
+        atas::wrap_sol::wrap_wallets,
+        bundler::launch_pool,
+        dashboard::run_dashboard,
+        distribution::{rand::check_distribution, sol_distribution::distribute_sol},
+        instruction::pool_ixs::LaunchParams,
+        launch::{run_launch, LaunchPlan, LaunchStep},
+        lut::extend_lut::create_wallets_lut,
+        manifest::LaunchManifest,
+        report::write_report,
+        wallets::{encrypt_wallets, read_wallets, save_wallets},
+    },
+};
+
+/// Raydium launch bundler. Starts the interactive menu when no command is given.
+#[derive(Debug, Parser)]
+#[command(name = "bundler", version)]
+pub struct Cli {
+    /// Settings file used by every command
+    #[arg(short, long, global = true, default_value = "settings.json")]
+    pub config: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Manage wallet folders
+    #[command(subcommand)]
+    Wallets(WalletsCommand),
+    /// Manage lookup tables
+    #[command(subcommand)]
+    Lut(LutCommand),
+    /// Distribute SOL from the buyer wallet to a wallet folder
+    Distribute(DistributeArgs),
+    /// Create ATAs and wrap SOL for a wallet folder
+    Wrap(WalletFolderArgs),
+    /// Create the pool and bundle the buys
+    Launch(LaunchArgs),
+    /// Run every launch step in order, resuming after the last finished one
+    Run(RunArgs),
+    /// Show which launch steps of a wallet folder are finished
+    Status(WalletFolderArgs),
+    /// Report what a landed bundle did, from the block it landed in
+    Report(ReportArgs),
+    /// Show the live balances of a wallet folder
+    Dashboard(DashboardArgs),
+    /// Manage encrypted keys
+    #[command(subcommand)]
+    Keystore(KeystoreCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WalletsCommand {
+    /// Generate new wallets into a folder
+    Generate {
+        /// Folder the wallets are written to
+        #[arg(long)]
+        folder: String,
+        /// Number of wallets to generate
+        #[arg(long)]
+        count: u64,
+        /// Write the wallets as encrypted keystore entries
+        #[arg(long)]
+        encrypt: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeystoreCommand {
+    /// Encrypt plaintext keys in wallet folders and the settings file
+    Migrate {
+        /// Wallet folder to encrypt, may be repeated
+        #[arg(long)]
+        wallets: Vec<String>,
+        /// Also encrypt the private keys in the settings file
+        #[arg(long)]
+        settings: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LutCommand {
+    /// Create and extend the LUT for a wallet folder
+    Create(WalletFolderArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct WalletFolderArgs {
+    /// Wallet folder to operate on
+    #[arg(long)]
+    pub wallets: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub folder: WalletFolderArgs,
+    /// Step whose last landed bundle is reported, e.g. `distribute` or `launch`
+    #[arg(long, default_value = "launch")]
+    pub step: String,
+    /// JSON output file, `<folder>/reports/<step>.json` by default
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DashboardArgs {
+    #[command(flatten)]
+    pub folder: WalletFolderArgs,
+    /// CSV file rewritten with the balances on every change
+    #[arg(long)]
+    pub csv: Option<PathBuf>,
+    /// Show the balances once instead of following them
+    #[arg(long)]
+    pub once: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DistributeArgs {
+    #[command(flatten)]
+    pub folder: WalletFolderArgs,
+    /// Total SOL to distribute
+    #[arg(long)]
+    pub total: f64,
+    /// Minimum SOL per wallet
+    #[arg(long)]
+    pub min: f64,
+    /// Maximum SOL per wallet
+    #[arg(long)]
+    pub max: f64,
+    /// Bundle tip in SOL
+    #[arg(long)]
+    pub tip: f64,
+}
+
+#[derive(Debug, Args)]
+pub struct LaunchArgs {
+    #[command(flatten)]
+    pub folder: WalletFolderArgs,
+    /// SOL liquidity added to the pool
+    #[arg(long)]
+    pub liquidity: f64,
+    /// Percentage of the deployer's tokens added to the pool
+    #[arg(long)]
+    pub token_percentage: f64,
+    /// Bundle tip in SOL
+    #[arg(long)]
+    pub tip: f64,
+    /// Unix timestamp the pool opens for swaps, 0 opens it immediately
+    #[arg(long, default_value_t = 0)]
+    pub open_time: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub folder: WalletFolderArgs,
+    /// Number of wallets generated when the folder has none
+    #[arg(long, default_value_t = 27)]
+    pub count: u64,
+    /// Write generated wallets as encrypted keystore entries
+    #[arg(long)]
+    pub encrypt: bool,
+    /// Total SOL to distribute
+    #[arg(long)]
+    pub total: f64,
+    /// Minimum SOL per wallet
+    #[arg(long)]
+    pub min: f64,
+    /// Maximum SOL per wallet
+    #[arg(long)]
+    pub max: f64,
+    /// Distribution bundle tip in SOL
+    #[arg(long)]
+    pub distribute_tip: f64,
+    /// SOL liquidity added to the pool
+    #[arg(long)]
+    pub liquidity: f64,
+    /// Percentage of the deployer's tokens added to the pool
+    #[arg(long)]
+    pub token_percentage: f64,
+    /// Launch bundle tip in SOL
+    #[arg(long)]
+    pub tip: f64,
+    /// Unix timestamp the pool opens for swaps, 0 opens it immediately
+    #[arg(long, default_value_t = 0)]
+    pub open_time: u64,
+}
+
+/// Total, minimum and maximum distribution amounts in lamports, from the SOL flags.
+fn distribution_amounts(total: f64, min: f64, max: f64) -> eyre::Result<(u64, u64, u64)> {
+    for (flag, value) in [("--total", total), ("--min", min), ("--max", max)] {
+        if !value.is_finite() || value < 0.0 {
+            return Err(eyre::eyre!("{} must be a SOL amount of 0 or more", flag));
+        }
+    }
+    if min > max {
+        return Err(eyre::eyre!("--min {} is above --max {}", min, max));
+    }
+    if total < min {
+        return Err(eyre::eyre!("--total {} is below --min {}", total, min));
+    }
+
+    Ok((
+        sol_to_lamports(total),
+        sol_to_lamports(min),
+        sol_to_lamports(max),
+    ))
+}
+
+pub async fn run(config: PathBuf, command: Command) -> eyre::Result<()> {
+    match command {
+        Command::Wallets(WalletsCommand::Generate {
+            folder,
+            count,
+            encrypt,
+        }) => {
+            save_wallets(&folder, count, encrypt)?;
+        }
+        Command::Lut(LutCommand::Create(args)) => {
+            let settings = read_settings(&config)?;
+            let wallets = read_wallets(&args.wallets)?;
+            let mut manifest = LaunchManifest::load(&args.wallets)?;
+
+            let lut = create_wallets_lut(settings, &wallets, &mut manifest).await?;
+
+            info!("Lut Account:  {}", lut);
+        }
+        Command::Distribute(args) => {
+            let settings = read_settings(&config)?;
+            let wallets = read_wallets(&args.folder.wallets)?;
+            let mut manifest = LaunchManifest::load(&args.folder.wallets)?;
+
+            let (total, min, max) = distribution_amounts(args.total, args.min, args.max)?;
+            check_distribution(total, wallets.len(), min, max)?;
+
+            distribute_sol(
+                settings,
+                &wallets,
+                &mut manifest,
+                total,
+                min,
+                max,
+                sol_to_lamports(args.tip),
+            )
+            .await?;
+        }
+        Command::Wrap(args) => {
+            let settings = read_settings(&config)?;
+            let wallets = read_wallets(&args.wallets)?;
+            let mut manifest = LaunchManifest::load(&args.wallets)?;
+
+            wrap_wallets(settings, &wallets, &mut manifest).await?;
+        }
+        Command::Launch(args) => {
+            let settings = read_settings(&config)?;
+            let wallets = read_wallets(&args.folder.wallets)?;
+            let mut manifest = LaunchManifest::load(&args.folder.wallets)?;
+
+            let params = LaunchParams {
+                sol_amount: sol_to_lamports(args.liquidity),
+                token_percentage: args.token_percentage / 100.0,
+                bundle_tip: sol_to_lamports(args.tip),
+                open_time: args.open_time,
+            };
+
+            launch_pool(settings, &wallets, &mut manifest, params).await?;
+        }
+        Command::Run(args) => {
+            let (total_amount, min_amount, max_amount) =
+                distribution_amounts(args.total, args.min, args.max)?;
+            let settings = read_settings(&config)?;
+
+            let plan = LaunchPlan {
+                wallet_count: args.count,
+                encrypt: args.encrypt,
+                total_amount,
+                min_amount,
+                max_amount,
+                distribution_tip: sol_to_lamports(args.distribute_tip),
+                params: LaunchParams {
+                    sol_amount: sol_to_lamports(args.liquidity),
+                    token_percentage: args.token_percentage / 100.0,
+                    bundle_tip: sol_to_lamports(args.tip),
+                    open_time: args.open_time,
+                },
+            };
+
+            run_launch(settings, &args.folder.wallets, &plan).await?;
+        }
+        Command::Status(args) => {
+            let manifest = LaunchManifest::load(&args.wallets)?;
+
+            for step in LaunchStep::ALL {
+                let state = if manifest.is_complete(step) {
+                    "done"
+                } else {
+                    "pending"
+                };
+                info!("{:<18} {}", step.to_string(), state);
+            }
+        }
+        Command::Report(args) => {
+            let settings = read_settings(&config)?;
+
+            write_report(&settings, &args.folder.wallets, &args.step, args.out).await?;
+        }
+        Command::Dashboard(args) => {
+            let settings = read_settings(&config)?;
+            let wallets: Vec<Pubkey> = read_wallets(&args.folder.wallets)?
+                .iter()
+                .map(|wallet| wallet.pubkey())
+                .collect();
+
+            run_dashboard(
+                &settings,
+                &args.folder.wallets,
+                &wallets,
+                args.csv,
+                args.once,
+            )
+            .await?;
+        }
+        Command::Keystore(KeystoreCommand::Migrate { wallets, settings }) => {
+            for folder in wallets {
+                let count = encrypt_wallets(&folder)?;
+                info!("Encrypted {} wallets in {} folder", count, folder);
+            }
+            if settings {
+                let count = encrypt_settings(&config)?;
+                info!("Encrypted {} keys in {}", count, config.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_inconsistent_distribution_flags() {
+        assert_eq!(
+            distribution_amounts(10.0, 0.5, 1.5).unwrap(),
+            (
+                sol_to_lamports(10.0),
+                sol_to_lamports(0.5),
+                sol_to_lamports(1.5)
+            )
+        );
+        assert!(distribution_amounts(10.0, 1.5, 0.5).is_err());
+        assert!(distribution_amounts(0.1, 0.5, 1.5).is_err());
+        assert!(distribution_amounts(10.0, -0.5, 1.5).is_err());
+        assert!(distribution_amounts(f64::NAN, 0.5, 1.5).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Per-network program IDs and endpoints.
+//!
+//! Selected with `CLUSTER` in the settings file. Localnet uses the mainnet program
+//! IDs since a local validator is expected to clone the mainnet programs.
+
+use std::{fmt, str::FromStr};
+
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClusterKind {
+    #[default]
+    Mainnet,
+    Devnet,
+    Localnet,
+    Custom,
+}
+
+impl FromStr for ClusterKind {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "" | "mainnet" | "mainnet-beta" => Ok(ClusterKind::Mainnet),
+            "devnet" => Ok(ClusterKind::Devnet),
+            "localnet" | "localhost" => Ok(ClusterKind::Localnet),
+            "custom" => Ok(ClusterKind::Custom),
+            _ => Err(eyre::eyre!(
+                "Unknown cluster {}, expected mainnet, devnet, localnet or custom",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ClusterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ClusterKind::Mainnet => "mainnet",
+            ClusterKind::Devnet => "devnet",
+            ClusterKind::Localnet => "localnet",
+            ClusterKind::Custom => "custom",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Program IDs and default endpoints of one network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    pub kind: ClusterKind,
+    /// Raydium AMM v4 program
+    pub amm_program: Pubkey,
+    /// OpenBook market program
+    pub market_program: Pubkey,
+    /// Account receiving Raydium's pool creation fee
+    pub create_fee_destination: Pubkey,
+    pub rpc_url: String,
+    /// Empty when the network has no public block engine.
+    pub block_engine_url: String,
+}
+
+impl Cluster {
+    pub fn mainnet() -> Self {
+        Cluster {
+            kind: ClusterKind::Mainnet,
+            amm_program: pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
+            market_program: pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"),
+            create_fee_destination: pubkey!("7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5"),
+            rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            block_engine_url: "https://ny.mainnet.block-engine.jito.wtf".to_string(),
+        }
+    }
+
+    pub fn devnet() -> Self {
+        Cluster {
+            kind: ClusterKind::Devnet,
+            amm_program: pubkey!("HWy1jotHpo6UqeQxx49dpYYdQB8wizRFMPj5s4ZxnDRz"),
+            market_program: pubkey!("EoTcMgcDRTJVZDMZWBoU6rhYHZfkNTVEAfz3uUJRcYGj"),
+            create_fee_destination: pubkey!("3XMrhbv989VxAMi3DErLV9eJht1pHppW5LbKxe9fkEFR"),
+            rpc_url: "https://api.devnet.solana.com".to_string(),
+            block_engine_url: String::new(),
+        }
+    }
+
+    pub fn localnet() -> Self {
+        Cluster {
+            kind: ClusterKind::Localnet,
+            rpc_url: "http://127.0.0.1:8899".to_string(),
+            block_engine_url: String::new(),
+            ..Cluster::mainnet()
+        }
+    }
+
+    /// Builds the cluster named `kind`, replacing any program ID given in `overrides`.
+    ///
+    /// A custom cluster has no defaults, so every override is required.
+    pub fn resolve(kind: ClusterKind, overrides: ProgramOverrides) -> eyre::Result<Self> {
+        let mut cluster = match kind {
+            ClusterKind::Mainnet => Cluster::mainnet(),
+            ClusterKind::Devnet => Cluster::devnet(),
+            ClusterKind::Localnet => Cluster::localnet(),
+            ClusterKind::Custom => {
+                let (Some(amm_program), Some(market_program), Some(create_fee_destination)) = (
+                    overrides.amm_program,
+                    overrides.market_program,
+                    overrides.create_fee_destination,
+                ) else {
+                    return Err(eyre::eyre!(
+                        "Custom cluster needs AMM-PROGRAM, MARKET-PROGRAM and CREATE-FEE-DESTINATION"
+                    ));
+                };
+
+                return Ok(Cluster {
+                    kind,
+                    amm_program,
+                    market_program,
+                    create_fee_destination,
+                    rpc_url: String::new(),
+                    block_engine_url: String::new(),
+                });
+            }
+        };
+
+        if let Some(amm_program) = overrides.amm_program {
+            cluster.amm_program = amm_program;
+        }
+        if let Some(market_program) = overrides.market_program {
+            cluster.market_program = market_program;
+        }
+        if let Some(create_fee_destination) = overrides.create_fee_destination {
+            cluster.create_fee_destination = create_fee_destination;
+        }
+
+        Ok(cluster)
+    }
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Cluster::mainnet()
+    }
+}
+
+/// Program IDs set explicitly in the settings file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgramOverrides {
+    pub amm_program: Option<Pubkey>,
+    pub market_program: Option<Pubkey>,
+    pub create_fee_destination: Option<Pubkey>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cluster_names() {
+        for (name, kind) in [
+            ("", ClusterKind::Mainnet),
+            ("mainnet-beta", ClusterKind::Mainnet),
+            ("Devnet", ClusterKind::Devnet),
+            ("localhost", ClusterKind::Localnet),
+            ("custom", ClusterKind::Custom),
+        ] {
+            assert_eq!(ClusterKind::from_str(name).unwrap(), kind, "{}", name);
+        }
+        assert!(ClusterKind::from_str("testnet").is_err());
+    }
+
+    #[test]
+    fn resolves_defaults_of_named_clusters() {
+        let devnet = Cluster::resolve(ClusterKind::Devnet, ProgramOverrides::default()).unwrap();
+        assert_eq!(devnet, Cluster::devnet());
+        assert!(devnet.block_engine_url.is_empty());
+
+        let localnet =
+            Cluster::resolve(ClusterKind::Localnet, ProgramOverrides::default()).unwrap();
+        assert_eq!(localnet.amm_program, Cluster::mainnet().amm_program);
+        assert_eq!(localnet.rpc_url, "http://127.0.0.1:8899");
+    }
+
+    #[test]
+    fn overrides_program_ids() {
+        let amm_program = Pubkey::new_unique();
+        let cluster = Cluster::resolve(
+            ClusterKind::Mainnet,
+            ProgramOverrides {
+                amm_program: Some(amm_program),
+                ..ProgramOverrides::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(cluster.amm_program, amm_program);
+        assert_eq!(cluster.market_program, Cluster::mainnet().market_program);
+    }
+
+    #[test]
+    fn resolves_custom_cluster_from_overrides() {
+        let overrides = ProgramOverrides {
+            amm_program: Some(Pubkey::new_unique()),
+            market_program: Some(Pubkey::new_unique()),
+            create_fee_destination: Some(Pubkey::new_unique()),
+        };
+
+        let cluster = Cluster::resolve(ClusterKind::Custom, overrides).unwrap();
+
+        assert_eq!(cluster.kind, ClusterKind::Custom);
+        assert_eq!(Some(cluster.amm_program), overrides.amm_program);
+        assert_eq!(Some(cluster.market_program), overrides.market_program);
+        assert_eq!(
+            Some(cluster.create_fee_destination),
+            overrides.create_fee_destination
+        );
+        assert!(cluster.rpc_url.is_empty());
+        assert!(cluster.block_engine_url.is_empty());
+    }
+
+    #[test]
+    fn rejects_custom_cluster_without_every_override() {
+        let overrides = ProgramOverrides {
+            amm_program: Some(Pubkey::new_unique()),
+            market_program: Some(Pubkey::new_unique()),
+            create_fee_destination: None,
+        };
+
+        assert!(Cluster::resolve(ClusterKind::Custom, overrides).is_err());
+    }
+}
@@ -21,6 +21,12 @@ pub async fn private_key_input(key: &str) -> Result<String, Box<dyn Error>> {
     }
 }
 
+pub fn passphrase_input(title: &str) -> String {
+    let t = Input::new(title).password(true).prompt("Passphrase: ");
+
+    t.run().expect("error running input")
+}
+
 fn is_valid_private_key(private_key: &str) -> bool {
     let decoded = bs58::decode(private_key)
         .into_vec()
@@ -68,9 +74,7 @@ pub fn liq_amount() -> u64 {
 
     let tokens = t.run().expect("error running input");
 
-    let tokens = sol_to_lamports(tokens.parse::<f64>().unwrap());
-
-    tokens
+    sol_to_lamports(tokens.parse::<f64>().unwrap())
 }
 
 pub async fn bundle_priority_tip() -> u64 {
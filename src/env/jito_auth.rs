@@ -1,44 +1,321 @@
-use std::str::FromStr;
+use std::{collections::HashSet, fmt, path::Path, str::FromStr, sync::Arc, time::Duration};
 
+use jito_searcher_client::{
+    bundle_sender::{BundleSender, GrpcBundleSender, HttpBundleSender},
+    landing::GeyserLandingSender,
+    leader_schedule::LeaderSchedule,
+    multi_region::{discover_regions, region_name, MultiRegionSender, RegionSender},
+    tip_accounts::TipAccountCache,
+};
+
+use log::{info, warn};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 use solana_sdk::{
-    instruction::Instruction, pubkey::Pubkey, signature::Keypair, system_instruction,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    system_instruction,
 };
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use zeroize::Zeroizing;
+
+use super::{keystore, PoolDataSettings};
+
+/// Environment variable holding the block engine auth key, overriding `JITO-AUTH-KEY`.
+pub const JITO_AUTH_KEY_ENV: &str = "BUNDLER_JITO_AUTH_KEY";
+
+/// Resolves the identity used to authenticate to the block engine.
+///
+/// `BUNDLER_JITO_AUTH_KEY` takes precedence over the `JITO-AUTH-KEY` setting. Either
+/// can hold a base58 key, a keystore entry or the path to a keypair file. Returns
+/// `None` when neither is set, in which case the searcher client connects
+/// unauthenticated.
+pub fn auth_keypair(settings: &PoolDataSettings) -> eyre::Result<Option<Arc<Keypair>>> {
+    let configured = match std::env::var(JITO_AUTH_KEY_ENV) {
+        Ok(value) if !value.is_empty() => keystore::reveal(&value)?,
+        _ => settings.jito_auth_key.clone(),
+    };
+
+    if configured.is_empty() {
+        info!("No Jito auth key configured, connecting to the block engine unauthenticated");
+        return Ok(None);
+    }
+
+    let keypair = if Path::new(configured.expose()).is_file() {
+        read_keypair_file(configured.expose())
+            .map_err(|e| eyre::eyre!("Failed to read Jito auth keypair file: {}", e))?
+    } else {
+        let bytes = Zeroizing::new(bs58::decode(configured.expose()).into_vec().map_err(|_| {
+            eyre::eyre!("Jito auth key is neither a keypair file nor a base58 key")
+        })?);
+        Keypair::from_bytes(&bytes)?
+    };
+
+    info!("Authenticating to the block engine as {}", keypair.pubkey());
+
+    Ok(Some(Arc::new(keypair)))
+}
+
+/// How bundles reach the block engine, selected with `BUNDLE-TRANSPORT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BundleTransport {
+    #[default]
+    Grpc,
+    /// JSON-RPC over HTTP, for networks where gRPC or auth is unavailable.
+    Http,
+}
 
-pub fn auth_keypair() -> Keypair {
-    let bytes_auth_vec = vec![
-        170, 102, 199, 216, 226, 201, 23, 43, 26, 120, 207, 73, 110, 164, 116, 178, 255, 140, 255,
-        218, 189, 56, 60, 156, 217, 54, 187, 126, 163, 9, 162, 105, 7, 82, 19, 78, 31, 45, 211, 21,
-        169, 244, 1, 88, 110, 145, 211, 13, 133, 99, 16, 32, 105, 253, 55, 213, 94, 124, 237, 195,
-        235, 255, 7, 72,
-    ];
-    let bytes_auth = bytes_auth_vec.as_slice();
-    let auth_keypair = Keypair::from_bytes(bytes_auth).unwrap();
-    auth_keypair
+impl FromStr for BundleTransport {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "" | "grpc" => Ok(BundleTransport::Grpc),
+            "http" | "json-rpc" => Ok(BundleTransport::Http),
+            _ => Err(eyre::eyre!(
+                "Unknown bundle transport {}, expected grpc or http",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BundleTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BundleTransport::Grpc => "grpc",
+            BundleTransport::Http => "http",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Connects to the block engine at `url` over the transport selected in the settings.
+async fn connect_region(
+    settings: &PoolDataSettings,
+    url: &str,
+    auth_keypair: Option<&Arc<Keypair>>,
+) -> eyre::Result<Box<dyn BundleSender>> {
+    info!("Connecting to {} over {}", url, settings.bundle_transport);
+    let sender: Box<dyn BundleSender> = match settings.bundle_transport {
+        BundleTransport::Grpc => Box::new(
+            GrpcBundleSender::connect(url, auth_keypair)
+                .await?
+                .with_schedule(settings.leader_schedule.clone()),
+        ),
+        BundleTransport::Http => Box::new(HttpBundleSender::new(url)),
+    };
+
+    Ok(sender)
+}
+
+/// What a [`bundle_sender`] was connected with, to tell when it can be reused.
+#[derive(PartialEq)]
+struct SessionKey {
+    block_engine_url: String,
+    bundle_transport: BundleTransport,
+    auth: Option<Pubkey>,
+    leader_schedule: Option<LeaderSchedule>,
+    block_engine_regions: Vec<String>,
+    discover_regions: bool,
+    region_fanout: Option<usize>,
+    geyser_url: Option<String>,
+}
+
+type Session = Arc<Mutex<Box<dyn BundleSender>>>;
+
+/// Block engine session of this process, shared by every mode.
+static SESSION: Mutex<Option<(SessionKey, Session)>> = Mutex::const_new(None);
+
+/// Exclusive use of the shared block engine session, released when dropped.
+pub type SharedBundleSender = OwnedMutexGuard<Box<dyn BundleSender>>;
+
+/// The block engine session for `settings`, connecting on first use.
+///
+/// Every mode shares one authenticated connection and bundle results subscription for
+/// as long as the block engine settings stay the same. Callers hold the session
+/// exclusively until the returned guard is dropped.
+pub async fn bundle_sender(settings: &PoolDataSettings) -> eyre::Result<SharedBundleSender> {
+    if settings.block_engine_url.is_empty() {
+        return Err(eyre::eyre!(
+            "BLOCK-ENGINE-URL is required for a {} cluster",
+            settings.cluster.kind
+        ));
+    }
+
+    let auth_keypair = match settings.bundle_transport {
+        BundleTransport::Grpc => auth_keypair(settings)?,
+        BundleTransport::Http => None,
+    };
+    let key = SessionKey {
+        block_engine_url: settings.block_engine_url.clone(),
+        bundle_transport: settings.bundle_transport,
+        auth: auth_keypair.as_ref().map(|keypair| keypair.pubkey()),
+        leader_schedule: settings.leader_schedule.clone(),
+        block_engine_regions: settings.block_engine_regions.clone(),
+        discover_regions: settings.discover_regions,
+        region_fanout: settings.region_fanout,
+        geyser_url: settings.geyser_url.clone(),
+    };
+
+    let session = {
+        let mut shared = SESSION.lock().await;
+        match &*shared {
+            Some((shared_key, session)) if *shared_key == key => {
+                info!("Reusing the block engine session");
+                session.clone()
+            }
+            _ => {
+                let sender =
+                    confirm_with_geyser(settings, connect(settings, auth_keypair.as_ref()).await?)
+                        .await;
+                let session = Arc::new(Mutex::new(sender));
+                *shared = Some((key, session.clone()));
+                session
+            }
+        }
+    };
+
+    Ok(session.lock_owned().await)
+}
+
+/// Confirms the bundles of `sender` from the geyser in the settings, if there is one.
+async fn confirm_with_geyser(
+    settings: &PoolDataSettings,
+    sender: Box<dyn BundleSender>,
+) -> Box<dyn BundleSender> {
+    let Some(url) = &settings.geyser_url else {
+        return sender;
+    };
+
+    match geyser_client::connect(url, settings.geyser_x_token.as_deref()).await {
+        Ok(geyser) => {
+            info!("Confirming bundles through the geyser at {}", url);
+            Box::new(GeyserLandingSender::new(sender, geyser))
+        }
+        Err(e) => {
+            warn!("Confirming bundles without the geyser: {}", e);
+            sender
+        }
+    }
+}
+
+/// Connects to the block engine over the transport selected in the settings.
+///
+/// With `BLOCK-ENGINE-REGIONS` or `DISCOVER-REGIONS` set, every bundle goes to all
+/// regions reachable, or to the `REGION-FANOUT` fastest of them.
+async fn connect(
+    settings: &PoolDataSettings,
+    auth_keypair: Option<&Arc<Keypair>>,
+) -> eyre::Result<Box<dyn BundleSender>> {
+    if settings.bundle_transport == BundleTransport::Http && settings.leader_schedule.is_some() {
+        warn!("LEADER-SLOTS-AHEAD needs the grpc transport, sending right away");
+    }
+
+    let mut regions = Vec::new();
+    let mut urls = vec![settings.block_engine_url.clone()];
+    if settings.discover_regions {
+        match settings.bundle_transport {
+            BundleTransport::Grpc => {
+                let url = &settings.block_engine_url;
+                info!("Connecting to {} over grpc", url);
+                let mut primary = GrpcBundleSender::connect(url, auth_keypair)
+                    .await?
+                    .with_schedule(settings.leader_schedule.clone());
+                let discovered = discover_regions(primary.client(), url).await?;
+                regions.push(RegionSender::new(region_name(url), Box::new(primary)));
+                urls.extend(discovered.into_iter().map(|(_, url)| url));
+            }
+            BundleTransport::Http => {
+                warn!("DISCOVER-REGIONS needs the grpc transport, using the listed regions only")
+            }
+        }
+    }
+    urls.extend(settings.block_engine_regions.iter().cloned());
+
+    let mut seen = HashSet::new();
+    urls.retain(|url| seen.insert(url.trim_end_matches('/').to_string()));
+
+    if urls.len() == 1 {
+        return match regions.pop() {
+            Some(primary) => Ok(primary.sender),
+            None => connect_region(settings, &urls[0], auth_keypair).await,
+        };
+    }
+
+    // The primary is already connected when regions were discovered through it
+    for url in urls.iter().skip(regions.len()) {
+        match connect_region(settings, url, auth_keypair).await {
+            Ok(sender) => regions.push(RegionSender::new(region_name(url), sender)),
+            Err(e) => warn!("Skipping block engine {}: {}", url, e),
+        }
+    }
+    if regions.is_empty() {
+        return Err(eyre::eyre!("Could not connect to any block engine region"));
+    }
+
+    let mut sender = MultiRegionSender::new(regions).with_fanout(settings.region_fanout);
+    sender.measure_latency().await;
+
+    Ok(Box::new(sender))
 }
 
 pub fn jito_tip_inx(source: Pubkey, destination: Pubkey, priority: u64) -> Instruction {
-    let ix = system_instruction::transfer(&source, &destination, priority);
-    ix
+    system_instruction::transfer(&source, &destination, priority)
 }
 
 pub fn tip_txn(source: Pubkey, destination: Pubkey, priority: u64) -> Instruction {
-    let ix = system_instruction::transfer(&source, &destination, priority);
-    ix
+    system_instruction::transfer(&source, &destination, priority)
 }
 
 pub fn tip_program_id() -> Pubkey {
-    let auth = Pubkey::from_str("T1pyyaTNZsKv2WcRAB8oVnk93mLJw2XzjtVYqCsaHqt").unwrap();
-
-    return auth;
+    Pubkey::from_str("T1pyyaTNZsKv2WcRAB8oVnk93mLJw2XzjtVYqCsaHqt").unwrap()
 }
 
-pub fn jito_tip_acc() -> Pubkey {
-    let tip_accounts = generate_tip_accounts(&tip_program_id());
+/// Live tip accounts, refreshed every ten minutes.
+static TIP_ACCOUNTS: TipAccountCache = TipAccountCache::new(Duration::from_secs(600));
+
+/// Picks a random tip account from the block engine's list.
+///
+/// Falls back to the accounts derived from the tip program when the block engine
+/// cannot be asked.
+pub async fn jito_tip_acc(sender: &mut dyn BundleSender) -> Pubkey {
+    let tip_accounts = TIP_ACCOUNTS
+        .get(sender, || generate_tip_accounts(&tip_program_id()))
+        .await;
     let mut rng = StdRng::from_entropy();
-    let jito_tip_acc = tip_accounts[rng.gen_range(0..tip_accounts.len())];
 
-    return jito_tip_acc;
+    tip_accounts[rng.gen_range(0..tip_accounts.len())]
+}
+
+/// Tip accounts a landed bundle may have paid, the block engine's list when it can be had.
+///
+/// Uses the cached list, otherwise asks the block engine once. Falls back to the accounts
+/// derived from the tip program.
+pub async fn known_tip_accounts(settings: &PoolDataSettings) -> Vec<Pubkey> {
+    if let Some(accounts) = TIP_ACCOUNTS.cached() {
+        return accounts;
+    }
+
+    match bundle_sender(settings).await {
+        Ok(mut sender) => {
+            TIP_ACCOUNTS
+                .get(sender.as_mut(), || generate_tip_accounts(&tip_program_id()))
+                .await
+        }
+        Err(e) => {
+            warn!("Cannot ask the block engine for tip accounts: {}", e);
+            generate_tip_accounts(&tip_program_id())
+        }
+    }
+}
+
+/// Fails unless `destination` is one of the known tip accounts.
+pub fn validate_tip_account(destination: &Pubkey) -> eyre::Result<()> {
+    TIP_ACCOUNTS.validate(destination, || generate_tip_accounts(&tip_program_id()))?;
+
+    Ok(())
 }
 
 pub fn generate_tip_accounts(tip_program_pubkey: &Pubkey) -> Vec<Pubkey> {
@@ -0,0 +1,220 @@
+//! Passphrase based encryption for private keys stored on disk.
+//!
+//! Encrypted keys are stored as `keystore:v1:<base64>` strings in place of the
+//! plaintext base58 key, so `settings.json` and `wallet_N.json` keep their layout.
+//! The payload is `salt | nonce | ciphertext`, keyed with PBKDF2-HMAC-SHA256 and
+//! sealed with AES-256-GCM-SIV.
+//!
+//! The passphrase is read once per process, from `BUNDLER_KEYSTORE_PASSPHRASE`
+//! or a prompt, and every derived key is cached for the rest of the session.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use aes_gcm_siv::{
+    aead::{Aead, NewAead},
+    Aes256GcmSiv, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use super::{input::passphrase_input, secret::SecretString};
+
+/// Environment variable holding the keystore passphrase for non-interactive runs.
+pub const KEYSTORE_PASSPHRASE_ENV: &str = "BUNDLER_KEYSTORE_PASSPHRASE";
+
+const KEYSTORE_PREFIX: &str = "keystore:v1:";
+const PBKDF2_ROUNDS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+struct Session {
+    passphrase: SecretString,
+    /// Salt used for everything encrypted during this session.
+    salt: [u8; SALT_LEN],
+    keys: HashMap<[u8; SALT_LEN], [u8; KEY_LEN]>,
+}
+
+impl Session {
+    fn key(&mut self, salt: &[u8; SALT_LEN]) -> [u8; KEY_LEN] {
+        let passphrase = &self.passphrase;
+        *self.keys.entry(*salt).or_insert_with(|| {
+            let mut key = [0u8; KEY_LEN];
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(
+                passphrase.expose().as_bytes(),
+                salt,
+                PBKDF2_ROUNDS,
+                &mut key,
+            );
+            key
+        })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for key in self.keys.values_mut() {
+            key.zeroize();
+        }
+    }
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Returns true when `value` is an encrypted keystore entry.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(KEYSTORE_PREFIX)
+}
+
+/// Unlocks the keystore for this session. Only the first call reads the passphrase.
+///
+/// With `confirm` set, a prompted passphrase has to be typed twice; use it before
+/// encrypting anything so a typo cannot lock the keys away.
+pub fn unlock(confirm: bool) -> eyre::Result<()> {
+    let mut session = SESSION.lock().unwrap();
+    if session.is_some() {
+        return Ok(());
+    }
+
+    let passphrase = match std::env::var(KEYSTORE_PASSPHRASE_ENV) {
+        Ok(passphrase) => SecretString::new(passphrase),
+        Err(_) => loop {
+            let passphrase = SecretString::new(passphrase_input("Keystore Passphrase"));
+            if !confirm || passphrase == SecretString::new(passphrase_input("Confirm Passphrase")) {
+                break passphrase;
+            }
+            println!("Passphrases do not match. Please try again.");
+        },
+    };
+    if passphrase.is_empty() {
+        return Err(eyre::eyre!("Keystore passphrase cannot be empty"));
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    *session = Some(Session {
+        passphrase,
+        salt,
+        keys: HashMap::new(),
+    });
+
+    Ok(())
+}
+
+/// Encrypts a plaintext secret into a keystore entry.
+pub fn encrypt(secret: &str) -> eyre::Result<String> {
+    unlock(true)?;
+    let mut guard = SESSION.lock().unwrap();
+    let session = guard.as_mut().unwrap();
+
+    let salt = session.salt;
+    let key = session.key(&salt);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = Aes256GcmSiv::new(Key::from_slice(&key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+        .map_err(|_| eyre::eyre!("Failed to encrypt keystore entry"))?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(format!("{}{}", KEYSTORE_PREFIX, STANDARD.encode(payload)))
+}
+
+/// Decrypts a keystore entry. Plaintext values are returned unchanged.
+pub fn reveal(value: &str) -> eyre::Result<SecretString> {
+    let Some(encoded) = value.strip_prefix(KEYSTORE_PREFIX) else {
+        return Ok(SecretString::new(value.to_string()));
+    };
+
+    let payload = STANDARD.decode(encoded)?;
+    if payload.len() <= SALT_LEN + NONCE_LEN {
+        return Err(eyre::eyre!("Keystore entry is truncated"));
+    }
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    unlock(false)?;
+    let mut guard = SESSION.lock().unwrap();
+    let key = guard.as_mut().unwrap().key(salt.try_into()?);
+
+    let cipher = Aes256GcmSiv::new(Key::from_slice(&key));
+    match cipher.decrypt(Nonce::from_slice(nonce), ciphertext) {
+        Ok(plaintext) => Ok(SecretString::new(String::from_utf8(plaintext)?)),
+        Err(_) => {
+            // Forget the passphrase so the next attempt asks again
+            *guard = None;
+            Err(eyre::eyre!("Wrong keystore passphrase"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests share the process wide session, so they take turns.
+    static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+    fn start_session(passphrase: &str) {
+        *SESSION.lock().unwrap() = Some(Session {
+            passphrase: SecretString::new(passphrase.to_string()),
+            salt: [7; SALT_LEN],
+            keys: HashMap::new(),
+        });
+    }
+
+    #[test]
+    fn round_trips_encrypted_entry() {
+        let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        start_session("correct horse battery staple");
+
+        let entry = encrypt("4wBqpZM9xaSheZzJSMawUHDgZ7miWfSsxmfVF5jJpYP").unwrap();
+        assert!(is_encrypted(&entry));
+        assert!(!entry.contains("4wBqpZM9xaSheZzJSMawUHDgZ7miWfSsxmfVF5jJpYP"));
+
+        let secret = reveal(&entry).unwrap();
+        assert_eq!(
+            secret.expose(),
+            "4wBqpZM9xaSheZzJSMawUHDgZ7miWfSsxmfVF5jJpYP"
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        start_session("correct horse battery staple");
+        let entry = encrypt("secret").unwrap();
+
+        start_session("wrong passphrase");
+        let err = reveal(&entry).unwrap_err();
+        assert_eq!(err.to_string(), "Wrong keystore passphrase");
+        // The wrong passphrase is forgotten so the next reveal asks again
+        assert!(SESSION.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn passes_plaintext_through() {
+        let plaintext = "4wBqpZM9xaSheZzJSMawUHDgZ7miWfSsxmfVF5jJpYP";
+        assert!(!is_encrypted(plaintext));
+        assert_eq!(reveal(plaintext).unwrap().expose(), plaintext);
+    }
+
+    #[test]
+    fn rejects_truncated_entry() {
+        let entry = format!("{}{}", KEYSTORE_PREFIX, STANDARD.encode([0u8; SALT_LEN]));
+        assert_eq!(
+            reveal(&entry).unwrap_err().to_string(),
+            "Keystore entry is truncated"
+        );
+    }
+}
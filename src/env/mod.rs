@@ -1,15 +1,32 @@
+pub mod cluster;
 pub mod input;
 pub mod jito_auth;
+pub mod keystore;
+pub mod secret;
 
 use std::{
     fs::{self, File},
     io::Write,
+    mem::take,
+    path::Path,
+    str::FromStr,
 };
 
+use cluster::{Cluster, ClusterKind, ProgramOverrides};
+
 use input::{mint_input, private_key_input};
+use jito_auth::BundleTransport;
+use jito_searcher_client::{
+    leader_schedule::LeaderSchedule,
+    retry::{RetryPolicy, TipEscalation},
+};
 use log::info;
+use secret::SecretString;
 use serde::{Deserialize, Serialize};
-use solana_program::pubkey::Pubkey;
+use solana_program::{native_token::sol_to_lamports, pubkey::Pubkey};
+use zeroize::Zeroize;
+
+use crate::raydium::{account_watcher::websocket_url, fallback::PriorityFee};
 
 #[derive(Debug, Clone)]
 pub struct BackrunAccount {
@@ -17,34 +34,38 @@ pub struct BackrunAccount {
     pub account: Pubkey,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone)]
 pub struct PoolDataSettings {
-    #[serde(rename = "RPC-URL")]
     pub rpc_url: String,
-
-    #[serde(rename = "BLOCK-ENGINE-URL")]
     pub block_engine_url: String,
-
-    #[serde(rename = "DEPLOYER-PRIVATE-KEY")]
-    pub deployer_key: String,
-
-    #[serde(rename = "BUYER-PRIVATE-KEY")]
-    pub buyer_key: String,
-
-    #[serde(rename = "TOKEN-MINT")]
+    pub deployer_key: SecretString,
+    pub buyer_key: SecretString,
+    /// Block engine auth identity, empty to connect unauthenticated.
+    pub jito_auth_key: SecretString,
     pub token_mint: String,
-
-    #[serde(rename = "MARKET-ADDRESS")]
     pub market_id: String,
-
-    #[serde(rename = "POOL-ID")]
     pub pool_id: String,
-
-    #[serde(rename = "LUT-KEY")]
     pub lut_key: String,
-
-    #[serde(rename = "VOLUME-LUT-KEY")]
     pub volume_lut_key: String,
+    pub cluster: Cluster,
+    pub retry: RetryPolicy,
+    pub bundle_transport: BundleTransport,
+    /// Holds bundles back until a Jito leader is close, `None` to send right away.
+    pub leader_schedule: Option<LeaderSchedule>,
+    /// Further block engines every bundle is also sent to.
+    pub block_engine_regions: Vec<String>,
+    /// Adds every region reported by `GetRegions` to `block_engine_regions`.
+    pub discover_regions: bool,
+    /// Sends to this many of the fastest regions only, all of them when `None`.
+    pub region_fanout: Option<usize>,
+    /// Priority fee for sending non-atomic steps over RPC when their bundle fails, `None`
+    /// to never fall back.
+    pub rpc_fallback: Option<PriorityFee>,
+    /// Websocket of the RPC, used to wait for accounts without a geyser.
+    pub ws_url: String,
+    /// Yellowstone geyser to wait for accounts through, `None` to use the websocket.
+    pub geyser_url: Option<String>,
+    pub geyser_x_token: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Default)]
@@ -61,6 +82,9 @@ struct HelperSettings {
     #[serde(rename = "BUYER-PRIVATE-KEY")]
     buyer_key: String,
 
+    #[serde(rename = "JITO-AUTH-KEY", default)]
+    jito_auth_key: String,
+
     #[serde(rename = "TOKEN-MINT")]
     token_mint: String,
 
@@ -75,40 +99,168 @@ struct HelperSettings {
 
     #[serde(rename = "VOLUME-LUT-KEY")]
     volume_lut_key: String,
+
+    #[serde(
+        rename = "RETRY-MAX-ATTEMPTS",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    retry_max_attempts: Option<u32>,
+
+    #[serde(
+        rename = "RETRY-TIP-MULTIPLIER",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    retry_tip_multiplier: Option<f64>,
+
+    #[serde(
+        rename = "RETRY-MAX-TIP",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    retry_max_tip: Option<f64>,
+
+    #[serde(
+        rename = "SIMULATE-BUNDLES",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    simulate_bundles: Option<bool>,
+
+    #[serde(
+        rename = "BUNDLE-TRANSPORT",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    bundle_transport: String,
+
+    #[serde(
+        rename = "LEADER-SLOTS-AHEAD",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    leader_slots_ahead: Option<u64>,
+
+    #[serde(
+        rename = "LEADER-REGIONS",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    leader_regions: Vec<String>,
+
+    #[serde(
+        rename = "BLOCK-ENGINE-REGIONS",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    block_engine_regions: Vec<String>,
+
+    #[serde(
+        rename = "DISCOVER-REGIONS",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    discover_regions: bool,
+
+    #[serde(
+        rename = "REGION-FANOUT",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    region_fanout: Option<usize>,
+
+    #[serde(
+        rename = "RPC-FALLBACK-PRIORITY-FEE",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    rpc_fallback_priority_fee: Option<u64>,
+
+    #[serde(
+        rename = "RPC-FALLBACK-COMPUTE-UNITS",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    rpc_fallback_compute_units: Option<u32>,
+
+    #[serde(rename = "WS-URL", default, skip_serializing_if = "String::is_empty")]
+    ws_url: String,
+
+    #[serde(
+        rename = "GEYSER-URL",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    geyser_url: String,
+
+    #[serde(
+        rename = "GEYSER-X-TOKEN",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    geyser_x_token: String,
+
+    #[serde(rename = "CLUSTER", default, skip_serializing_if = "String::is_empty")]
+    cluster: String,
+
+    #[serde(
+        rename = "AMM-PROGRAM",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    amm_program: String,
+
+    #[serde(
+        rename = "MARKET-PROGRAM",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    market_program: String,
+
+    #[serde(
+        rename = "CREATE-FEE-DESTINATION",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    create_fee_destination: String,
 }
 
+impl Drop for HelperSettings {
+    fn drop(&mut self) {
+        self.deployer_key.zeroize();
+        self.buyer_key.zeroize();
+        self.jito_auth_key.zeroize();
+    }
+}
+
+/// Default settings file read by the interactive menu.
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Compute unit limit of RPC fallback transactions unless `RPC-FALLBACK-COMPUTE-UNITS` is set.
+const DEFAULT_FALLBACK_COMPUTE_UNITS: u32 = 400_000;
+
 pub async fn load_minter_settings() -> eyre::Result<PoolDataSettings> {
-    let args = match fs::read_to_string("settings.json") {
+    let args = match fs::read_to_string(SETTINGS_FILE) {
         Ok(args) => args,
         Err(_) => {
             info!("Settings file not found, creating a new one");
             // Create a new settings.json file with default settings
-            let default_settings = HelperSettings {
-                rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
-                block_engine_url: "https://ny.mainnet.block-engine.jito.wtf".to_string(),
-                deployer_key: "".to_string(),
-                buyer_key: "".to_string(),
-                token_mint: "".to_string(),
-                market_id: "".to_string(),
-                pool_id: "".to_string(),
-                lut_key: "".to_string(),
-                volume_lut_key: "".to_string(),
-            };
+            let mainnet = Cluster::mainnet();
+            let mut default_settings = HelperSettings::default();
+            default_settings.rpc_url = mainnet.rpc_url;
+            default_settings.block_engine_url = mainnet.block_engine_url;
+            default_settings.cluster = mainnet.kind.to_string();
             let default_settings_json = serde_json::to_string_pretty(&default_settings).unwrap();
-            let mut file = File::create("settings.json").unwrap();
+            let mut file = File::create(SETTINGS_FILE).unwrap();
             file.write_all(default_settings_json.as_bytes()).unwrap();
 
             "".to_string()
         }
     };
 
-    let mut helper_settings: HelperSettings = match serde_json::from_str(&args) {
-        Ok(settings) => settings,
-        Err(_) => {
-            // If the file is empty, use default settings
-            HelperSettings::default()
-        }
-    };
+    // If the file is empty, use default settings
+    let mut helper_settings: HelperSettings = serde_json::from_str(&args).unwrap_or_default();
 
     // If any field is empty, ask the user to fill it
     if helper_settings.deployer_key.is_empty() {
@@ -126,18 +278,229 @@ pub async fn load_minter_settings() -> eyre::Result<PoolDataSettings> {
 
     // Save the updated settings to the file
     let default_settings_json = serde_json::to_string_pretty(&helper_settings).unwrap();
-    let mut file = File::create("settings.json").unwrap();
+    let mut file = File::create(SETTINGS_FILE).unwrap();
     file.write_all(default_settings_json.as_bytes()).unwrap();
 
-    Ok(PoolDataSettings {
-        rpc_url: helper_settings.rpc_url,
-        block_engine_url: helper_settings.block_engine_url,
-        market_id: helper_settings.market_id,
-        token_mint: helper_settings.token_mint,
-        deployer_key: helper_settings.deployer_key,
-        buyer_key: helper_settings.buyer_key,
-        pool_id: helper_settings.pool_id,
-        lut_key: helper_settings.lut_key,
-        volume_lut_key: helper_settings.volume_lut_key,
-    })
+    helper_settings.try_into()
+}
+
+/// Reads the settings file at `path` without prompting.
+///
+/// Fails when any field the modes depend on is missing, so scripted runs stop
+/// early instead of blocking on input.
+pub fn read_settings(path: impl AsRef<Path>) -> eyre::Result<PoolDataSettings> {
+    let path = path.as_ref();
+    let args = fs::read_to_string(path)
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+    let helper_settings: HelperSettings = serde_json::from_str(&args)?;
+
+    let required = [
+        ("DEPLOYER-PRIVATE-KEY", &helper_settings.deployer_key),
+        ("BUYER-PRIVATE-KEY", &helper_settings.buyer_key),
+        ("TOKEN-MINT", &helper_settings.token_mint),
+        ("MARKET-ADDRESS", &helper_settings.market_id),
+    ];
+    for (field, value) in required {
+        if value.is_empty() {
+            return Err(eyre::eyre!("{} is missing in {}", field, path.display()));
+        }
+    }
+
+    helper_settings.try_into()
+}
+
+/// Encrypts the plaintext private keys of the settings file at `path` in place.
+///
+/// Returns the number of keys that were encrypted.
+pub fn encrypt_settings(path: impl AsRef<Path>) -> eyre::Result<usize> {
+    let path = path.as_ref();
+    let args = fs::read_to_string(path)?;
+    let mut helper_settings: HelperSettings = serde_json::from_str(&args)?;
+
+    let mut encrypted = 0;
+    for key in [
+        &mut helper_settings.deployer_key,
+        &mut helper_settings.buyer_key,
+        &mut helper_settings.jito_auth_key,
+    ] {
+        // JITO-AUTH-KEY may point to a keypair file instead of holding the key
+        if !key.is_empty() && !keystore::is_encrypted(key) && !Path::new(key).is_file() {
+            *key = keystore::encrypt(key)?;
+            encrypted += 1;
+        }
+    }
+
+    if encrypted > 0 {
+        let mut file = File::create(path)?;
+        file.write_all(serde_json::to_string_pretty(&helper_settings)?.as_bytes())?;
+    }
+
+    Ok(encrypted)
+}
+
+impl TryFrom<HelperSettings> for PoolDataSettings {
+    type Error = eyre::Report;
+
+    fn try_from(mut helper_settings: HelperSettings) -> eyre::Result<Self> {
+        let program = |value: &str| -> eyre::Result<Option<Pubkey>> {
+            match value {
+                "" => Ok(None),
+                value => Ok(Some(Pubkey::from_str(value)?)),
+            }
+        };
+        let overrides = ProgramOverrides {
+            amm_program: program(&helper_settings.amm_program)?,
+            market_program: program(&helper_settings.market_program)?,
+            create_fee_destination: program(&helper_settings.create_fee_destination)?,
+        };
+        let cluster =
+            Cluster::resolve(ClusterKind::from_str(&helper_settings.cluster)?, overrides)?;
+
+        // Empty URLs fall back to the cluster's endpoints
+        let mut rpc_url = take(&mut helper_settings.rpc_url);
+        if rpc_url.is_empty() {
+            rpc_url = cluster.rpc_url.clone();
+        }
+        if rpc_url.is_empty() {
+            return Err(eyre::eyre!(
+                "RPC-URL is required for a {} cluster",
+                cluster.kind
+            ));
+        }
+        let mut ws_url = take(&mut helper_settings.ws_url);
+        if ws_url.is_empty() {
+            ws_url = websocket_url(&rpc_url);
+        }
+        let mut block_engine_url = take(&mut helper_settings.block_engine_url);
+        if block_engine_url.is_empty() {
+            block_engine_url = cluster.block_engine_url.clone();
+        }
+
+        let default_retry = RetryPolicy::default();
+        let retry = RetryPolicy {
+            max_attempts: helper_settings
+                .retry_max_attempts
+                .unwrap_or(default_retry.max_attempts),
+            escalation: match helper_settings.retry_tip_multiplier {
+                Some(factor) => TipEscalation::Exponential { factor },
+                None => default_retry.escalation,
+            },
+            max_tip: helper_settings
+                .retry_max_tip
+                .map(sol_to_lamports)
+                .unwrap_or(default_retry.max_tip),
+            simulate: helper_settings
+                .simulate_bundles
+                .unwrap_or(default_retry.simulate),
+        };
+
+        let leader_schedule =
+            helper_settings
+                .leader_slots_ahead
+                .map(|slots_ahead| LeaderSchedule {
+                    slots_ahead,
+                    regions: take(&mut helper_settings.leader_regions),
+                    ..LeaderSchedule::default()
+                });
+
+        let rpc_fallback = helper_settings
+            .rpc_fallback_priority_fee
+            .map(|micro_lamports| PriorityFee {
+                micro_lamports,
+                compute_unit_limit: helper_settings
+                    .rpc_fallback_compute_units
+                    .unwrap_or(DEFAULT_FALLBACK_COMPUTE_UNITS),
+            });
+
+        Ok(PoolDataSettings {
+            rpc_url,
+            block_engine_url,
+            market_id: take(&mut helper_settings.market_id),
+            token_mint: take(&mut helper_settings.token_mint),
+            deployer_key: keystore::reveal(&helper_settings.deployer_key)?,
+            buyer_key: keystore::reveal(&helper_settings.buyer_key)?,
+            jito_auth_key: keystore::reveal(&helper_settings.jito_auth_key)?,
+            pool_id: take(&mut helper_settings.pool_id),
+            lut_key: take(&mut helper_settings.lut_key),
+            volume_lut_key: take(&mut helper_settings.volume_lut_key),
+            cluster,
+            retry,
+            bundle_transport: BundleTransport::from_str(&helper_settings.bundle_transport)?,
+            leader_schedule,
+            block_engine_regions: take(&mut helper_settings.block_engine_regions),
+            discover_regions: helper_settings.discover_regions,
+            region_fanout: helper_settings.region_fanout,
+            rpc_fallback,
+            ws_url,
+            geyser_url: Some(take(&mut helper_settings.geyser_url)).filter(|url| !url.is_empty()),
+            geyser_x_token: Some(take(&mut helper_settings.geyser_x_token))
+                .filter(|token| !token.is_empty()),
+        })
+    }
+}
+
+/// Settings for unit tests: fresh keys, placeholder mint and market, and `fields` on top.
+#[cfg(test)]
+pub(crate) fn test_settings(fields: serde_json::Value) -> eyre::Result<PoolDataSettings> {
+    use solana_sdk::signature::Keypair;
+
+    let mut settings = serde_json::json!({
+        "RPC-URL": "",
+        "BLOCK-ENGINE-URL": "",
+        "DEPLOYER-PRIVATE-KEY": Keypair::new().to_base58_string(),
+        "BUYER-PRIVATE-KEY": Keypair::new().to_base58_string(),
+        "TOKEN-MINT": Pubkey::new_unique().to_string(),
+        "MARKET-ADDRESS": Pubkey::new_unique().to_string(),
+        "POOL-ID": "",
+        "LUT-KEY": "",
+        "VOLUME-LUT-KEY": "",
+    });
+    if let serde_json::Value::Object(fields) = fields {
+        settings.as_object_mut().unwrap().extend(fields);
+    }
+
+    serde_json::from_value::<HelperSettings>(settings)?.try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::env::jito_auth::bundle_sender;
+
+    #[test]
+    fn falls_back_to_cluster_endpoints() {
+        let settings = test_settings(json!({ "CLUSTER": "devnet" })).unwrap();
+
+        assert_eq!(settings.cluster.kind, ClusterKind::Devnet);
+        assert_eq!(settings.rpc_url, "https://api.devnet.solana.com");
+        assert_eq!(settings.ws_url, "wss://api.devnet.solana.com");
+        assert!(settings.block_engine_url.is_empty());
+    }
+
+    #[test]
+    fn requires_rpc_url_for_custom_cluster() {
+        let err = test_settings(json!({
+            "CLUSTER": "custom",
+            "AMM-PROGRAM": Pubkey::new_unique().to_string(),
+            "MARKET-PROGRAM": Pubkey::new_unique().to_string(),
+            "CREATE-FEE-DESTINATION": Pubkey::new_unique().to_string(),
+        }))
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "RPC-URL is required for a custom cluster");
+    }
+
+    #[tokio::test]
+    async fn requires_block_engine_url_to_send_bundles() {
+        let settings = test_settings(json!({ "CLUSTER": "devnet" })).unwrap();
+
+        let err = bundle_sender(&settings).await.err().unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "BLOCK-ENGINE-URL is required for a devnet cluster"
+        );
+    }
 }
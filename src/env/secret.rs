@@ -0,0 +1,56 @@
+use std::fmt;
+
+use solana_sdk::signature::Keypair;
+use zeroize::Zeroize;
+
+/// Private key material or passphrases.
+///
+/// Prints as `<redacted>` in Debug and Display, is wiped from memory on drop and
+/// deliberately does not implement `Serialize`, so it cannot end up in logs or
+/// in files written from settings.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: String) -> Self {
+        Self(secret)
+    }
+
+    /// Returns the plaintext secret. Keep the borrow short lived.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Decodes the secret as a base58 keypair.
+    pub fn keypair(&self) -> Keypair {
+        Keypair::from_base58_string(&self.0)
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
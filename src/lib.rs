@@ -1,3 +1,4 @@
+pub mod cli;
 pub mod env;
 pub mod menu;
 pub mod raydium;
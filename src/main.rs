@@ -1,12 +1,18 @@
-use bundler::menu::{embed, selector::app};
+use bundler::{
+    cli::{run, Cli},
+    menu::{embed, selector::app},
+};
 use chrono::Local;
+use clap::Parser;
 use colored::Colorize;
-use log::info;
+use log::{error, info};
 use pretty_env_logger::env_logger::fmt::Color;
 use std::io::Write;
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+
     pretty_env_logger::env_logger::builder()
         .filter_level(log::LevelFilter::Info)
         .format(|f, record| {
@@ -35,10 +41,20 @@ async fn main() {
         })
         .init();
 
-    embed();
+    match cli.command {
+        Some(command) => {
+            if let Err(e) = run(cli.config, command).await {
+                error!("Command failed: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+        None => {
+            embed();
 
-    match app().await {
-        Ok(_) => info!("App exited successfully"),
-        Err(e) => info!("App exited with error: {:?}", e),
+            match app().await {
+                Ok(_) => info!("App exited successfully"),
+                Err(e) => info!("App exited with error: {:?}", e),
+            }
+        }
     }
 }
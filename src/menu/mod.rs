@@ -1,15 +1,14 @@
 pub mod selector;
 
 pub fn embed() {
-    let string = format!(
-        r"
+    let string = r"
 ██████╗ ██╗   ██╗███╗   ██╗██████╗ ██╗     ███████╗██████╗ 
 ██╔══██╗██║   ██║████╗  ██║██╔══██╗██║     ██╔════╝██╔══██╗
 ██████╔╝██║   ██║██╔██╗ ██║██║  ██║██║     █████╗  ██████╔╝
 ██╔══██╗██║   ██║██║╚██╗██║██║  ██║██║     ██╔══╝  ██╔══██╗
 ██████╔╝╚██████╔╝██║ ╚████║██████╔╝███████╗███████╗██║  ██║
 ╚═════╝  ╚═════╝ ╚═╝  ╚═══╝╚═════╝ ╚══════╝╚══════╝╚═╝  ╚═╝"
-    );
+        .to_string();
     println!("{esc}[2J{esc}[1;1H", esc = 27 as char);
     println!("{}", string);
 }
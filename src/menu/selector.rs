@@ -4,8 +4,14 @@ use async_recursion::async_recursion;
 use demand::{DemandOption, Select};
 
 use crate::raydium::{
-    atas::wrap_sol::sol_wrap, bundler::pool_main, distribution::sol_distribution::distributor,
-    lut::extend_lut::lut_main, wallets::wallets_main,
+    atas::wrap_sol::sol_wrap,
+    bundler::pool_main,
+    dashboard::dashboard_main,
+    distribution::sol_distribution::distributor,
+    launch::launch_main,
+    lut::extend_lut::lut_main,
+    report::report_main,
+    wallets::{encrypt_main, wallets_main},
 };
 
 #[async_recursion]
@@ -18,7 +24,11 @@ pub async fn app() -> Result<(), Box<dyn Error>> {
         .option(DemandOption::new("CreateLUT").label("▪ Create LUT"))
         .option(DemandOption::new("Distribute SOL").label("▪ Distribute SOL"))
         .option(DemandOption::new("Wrap SOL & ATAs").label("▪ Wrap SOL & ATAs"))
-        .option(DemandOption::new("multi-Liquidity").label("▪ Bundle Liquidity"));
+        .option(DemandOption::new("multi-Liquidity").label("▪ Bundle Liquidity"))
+        .option(DemandOption::new("Full Launch").label("▪ Run or Resume Full Launch"))
+        .option(DemandOption::new("Bundle Report").label("▪ Launch Bundle Report"))
+        .option(DemandOption::new("Wallet Dashboard").label("▪ Wallet Dashboard"))
+        .option(DemandOption::new("Encrypt Keys").label("▪ Encrypt Keys"));
 
     let selected_option = ms.run().expect("error running select");
 
@@ -36,7 +46,27 @@ pub async fn app() -> Result<(), Box<dyn Error>> {
             let _ = sol_wrap().await;
         }
         "multi-Liquidity" => {
-            let _ = pool_main().await;
+            if let Err(e) = pool_main().await {
+                eprintln!("Launch Error: {}", e);
+            }
+        }
+        "Full Launch" => {
+            if let Err(e) = launch_main().await {
+                eprintln!("Launch Error: {}", e);
+            }
+        }
+        "Bundle Report" => {
+            if let Err(e) = report_main().await {
+                eprintln!("Report Error: {}", e);
+            }
+        }
+        "Wallet Dashboard" => {
+            if let Err(e) = dashboard_main().await {
+                eprintln!("Dashboard Error: {}", e);
+            }
+        }
+        "Encrypt Keys" => {
+            let _ = encrypt_main().await;
         }
         _ => {}
     }
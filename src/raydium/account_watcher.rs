@@ -0,0 +1,626 @@
+//! Waiting for accounts to appear or change without hammering the RPC.
+//!
+//! An [`AccountWatcher`] subscribes to the account through a Yellowstone geyser when
+//! `GEYSER-URL` is set, or the RPC's websocket otherwise, and only polls
+//! `getAccountInfo` with a growing delay when no subscription can be opened.
+//! [`AccountWatcher::watch_accounts`] streams every write to a set of accounts instead.
+
+use std::{sync::Arc, time::Duration};
+
+use futures::{stream::select_all, StreamExt};
+use geyser_client::{
+    AccountFilter, CommitmentLevel, GeyserUpdate, SubscriptionBuilder, SubscriptionConfig,
+    SubscriptionEvent,
+};
+use log::{info, warn};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    rpc_config::RpcAccountInfoConfig,
+};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{sleep, timeout_at, Instant},
+};
+
+use crate::env::PoolDataSettings;
+
+/// First wait between polls, doubled after every poll.
+const POLL_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const POLL_MAX_BACKOFF: Duration = Duration::from_secs(4);
+/// How long a wait may take unless set with [`AccountWatcher::with_timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(90);
+/// Account writes [`AccountUpdates`] holds before the subscription waits for the reader.
+const UPDATES_BUFFER: usize = 1024;
+
+/// Where account updates come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchSource {
+    Geyser {
+        url: String,
+        x_token: Option<String>,
+    },
+    Websocket {
+        url: String,
+    },
+    /// Only poll the RPC.
+    Polling,
+}
+
+impl WatchSource {
+    /// The geyser in the settings, or the RPC's websocket without one.
+    pub fn from_settings(settings: &PoolDataSettings) -> Self {
+        match &settings.geyser_url {
+            Some(url) => WatchSource::Geyser {
+                url: url.clone(),
+                x_token: settings.geyser_x_token.clone(),
+            },
+            None => WatchSource::Websocket {
+                url: settings.ws_url.clone(),
+            },
+        }
+    }
+}
+
+/// Waits for accounts to exist or to match a predicate.
+#[derive(Clone)]
+pub struct AccountWatcher {
+    rpc_client: Arc<RpcClient>,
+    source: WatchSource,
+    timeout: Duration,
+}
+
+impl AccountWatcher {
+    /// A watcher that only polls `rpc_client`.
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self {
+            rpc_client,
+            source: WatchSource::Polling,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Watches through [`WatchSource::from_settings`], polling the settings' RPC.
+    pub fn from_settings(settings: &PoolDataSettings) -> Self {
+        Self::new(Arc::new(RpcClient::new(settings.rpc_url.clone())))
+            .with_source(WatchSource::from_settings(settings))
+    }
+
+    pub fn with_source(mut self, source: WatchSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn rpc_client(&self) -> &Arc<RpcClient> {
+        &self.rpc_client
+    }
+
+    /// Waits until `pubkey` exists.
+    pub async fn wait_for_account(&self, pubkey: &Pubkey) -> eyre::Result<Account> {
+        self.wait_for(pubkey, |_| true).await
+    }
+
+    /// Waits until `pubkey` exists and `predicate` holds for it.
+    pub async fn wait_for<P>(&self, pubkey: &Pubkey, predicate: P) -> eyre::Result<Account>
+    where
+        P: Fn(&Account) -> bool,
+    {
+        let deadline = Instant::now() + self.timeout;
+
+        let subscribed = match &self.source {
+            WatchSource::Geyser { url, x_token } => {
+                self.wait_with_geyser(url, x_token.as_deref(), pubkey, &predicate, deadline)
+                    .await
+            }
+            WatchSource::Websocket { url } => {
+                self.wait_with_websocket(url, pubkey, &predicate, deadline)
+                    .await
+            }
+            WatchSource::Polling => Ok(None),
+        };
+        match subscribed {
+            Ok(Some(account)) => return Ok(account),
+            Ok(None) => {}
+            Err(e) => warn!("Watching {} failed ({}), polling instead", pubkey, e),
+        }
+
+        self.poll(pubkey, &predicate, deadline).await
+    }
+
+    /// The account if it exists and matches, `None` otherwise.
+    async fn fetch<P>(&self, pubkey: &Pubkey, predicate: &P) -> eyre::Result<Option<Account>>
+    where
+        P: Fn(&Account) -> bool,
+    {
+        let account = self
+            .rpc_client
+            .get_account_with_commitment(pubkey, CommitmentConfig::processed())
+            .await?
+            .value;
+
+        Ok(account.filter(predicate))
+    }
+
+    /// Waits on a geyser subscription, `None` when its stream gave up before the deadline.
+    async fn wait_with_geyser<P>(
+        &self,
+        url: &str,
+        x_token: Option<&str>,
+        pubkey: &Pubkey,
+        predicate: &P,
+        deadline: Instant,
+    ) -> eyre::Result<Option<Account>>
+    where
+        P: Fn(&Account) -> bool,
+    {
+        let client = geyser_client::connect(url, x_token).await?;
+        let mut subscription = client
+            .subscribe(
+                SubscriptionBuilder::new()
+                    .accounts("watch", AccountFilter::new().account(*pubkey))
+                    .commitment(CommitmentLevel::Processed)
+                    .build(),
+                SubscriptionConfig::default(),
+            )
+            .await?;
+
+        // Subscribed first, so a write between the fetch and the first update is not missed
+        if let Some(account) = self.fetch(pubkey, predicate).await? {
+            return Ok(Some(account));
+        }
+
+        loop {
+            let event = match timeout_at(deadline, subscription.next()).await {
+                Ok(Some(event)) => event,
+                Ok(None) => return Ok(None),
+                Err(_) => return Err(timed_out(pubkey, self.timeout)),
+            };
+
+            match event {
+                SubscriptionEvent::Update {
+                    update: GeyserUpdate::Account(update),
+                    ..
+                } if update.pubkey == *pubkey && predicate(&update.account) => {
+                    return Ok(Some(update.account));
+                }
+                SubscriptionEvent::Update { .. } => {}
+                // Writes during the gap were missed
+                SubscriptionEvent::Reconnected => {
+                    if let Some(account) = self.fetch(pubkey, predicate).await? {
+                        return Ok(Some(account));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Waits on an `accountSubscribe`, `None` when the websocket closed before the deadline.
+    async fn wait_with_websocket<P>(
+        &self,
+        url: &str,
+        pubkey: &Pubkey,
+        predicate: &P,
+        deadline: Instant,
+    ) -> eyre::Result<Option<Account>>
+    where
+        P: Fn(&Account) -> bool,
+    {
+        let pubsub = PubsubClient::new(url).await?;
+        let (mut updates, unsubscribe) = pubsub
+            .account_subscribe(
+                pubkey,
+                Some(RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(CommitmentConfig::processed()),
+                    ..RpcAccountInfoConfig::default()
+                }),
+            )
+            .await?;
+
+        let found = match self.fetch(pubkey, predicate).await? {
+            Some(account) => Some(account),
+            None => loop {
+                let update = match timeout_at(deadline, updates.next()).await {
+                    Ok(Some(update)) => update,
+                    Ok(None) => break None,
+                    Err(_) => {
+                        drop(updates);
+                        unsubscribe().await;
+                        return Err(timed_out(pubkey, self.timeout));
+                    }
+                };
+
+                if let Some(account) = update
+                    .value
+                    .decode::<Account>()
+                    .filter(|account| predicate(account))
+                {
+                    break Some(account);
+                }
+            },
+        };
+
+        drop(updates);
+        unsubscribe().await;
+        Ok(found)
+    }
+
+    async fn poll<P>(
+        &self,
+        pubkey: &Pubkey,
+        predicate: &P,
+        deadline: Instant,
+    ) -> eyre::Result<Account>
+    where
+        P: Fn(&Account) -> bool,
+    {
+        let mut backoff = POLL_INITIAL_BACKOFF;
+        loop {
+            match self.fetch(pubkey, predicate).await {
+                Ok(Some(account)) => return Ok(account),
+                Ok(None) => {}
+                Err(e) => info!("Polling {} failed, retrying: {}", pubkey, e),
+            }
+
+            if Instant::now() + backoff >= deadline {
+                return Err(timed_out(pubkey, self.timeout));
+            }
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(POLL_MAX_BACKOFF);
+        }
+    }
+}
+
+/// Live writes to a set of accounts, from [`AccountWatcher::watch_accounts`].
+///
+/// Ends when the subscription fails; writes during a reconnect are missed.
+pub struct AccountUpdates {
+    updates: mpsc::Receiver<(Pubkey, Account)>,
+    task: JoinHandle<()>,
+}
+
+impl AccountUpdates {
+    pub async fn next(&mut self) -> Option<(Pubkey, Account)> {
+        self.updates.recv().await
+    }
+}
+
+impl Drop for AccountUpdates {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl AccountWatcher {
+    /// Subscribes to every write to `pubkeys`.
+    ///
+    /// Fails for a polling watcher, which has nothing to subscribe to.
+    pub async fn watch_accounts(&self, pubkeys: &[Pubkey]) -> eyre::Result<AccountUpdates> {
+        let (sender, updates) = mpsc::channel(UPDATES_BUFFER);
+
+        let task = match &self.source {
+            WatchSource::Geyser { url, x_token } => {
+                let client = geyser_client::connect(url, x_token.as_deref()).await?;
+                let filter = pubkeys.iter().fold(AccountFilter::new(), |filter, pubkey| {
+                    filter.account(*pubkey)
+                });
+                let mut subscription = client
+                    .subscribe(
+                        SubscriptionBuilder::new()
+                            .accounts("watch", filter)
+                            .commitment(CommitmentLevel::Processed)
+                            .build(),
+                        SubscriptionConfig::default(),
+                    )
+                    .await?;
+
+                tokio::spawn(async move {
+                    while let Some(event) = subscription.next().await {
+                        if let SubscriptionEvent::Update {
+                            update: GeyserUpdate::Account(update),
+                            ..
+                        } = event
+                        {
+                            if sender.send((update.pubkey, update.account)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                })
+            }
+            WatchSource::Websocket { url } => {
+                let pubsub = PubsubClient::new(url).await?;
+                let pubkeys = pubkeys.to_vec();
+
+                tokio::spawn(async move {
+                    let config = RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(CommitmentConfig::processed()),
+                        ..RpcAccountInfoConfig::default()
+                    };
+
+                    let mut streams = Vec::new();
+                    for pubkey in pubkeys {
+                        match pubsub
+                            .account_subscribe(&pubkey, Some(config.clone()))
+                            .await
+                        {
+                            Ok((stream, _)) => {
+                                streams.push(stream.map(move |update| (pubkey, update)))
+                            }
+                            Err(e) => {
+                                warn!("Subscribing to {} failed: {}", pubkey, e);
+                                return;
+                            }
+                        }
+                    }
+
+                    let mut updates = select_all(streams);
+                    while let Some((pubkey, update)) = updates.next().await {
+                        let Some(account) = update.value.decode::<Account>() else {
+                            continue;
+                        };
+                        if sender.send((pubkey, account)).await.is_err() {
+                            return;
+                        }
+                    }
+                })
+            }
+            WatchSource::Polling => {
+                return Err(eyre::eyre!("No geyser or websocket to watch accounts with"))
+            }
+        };
+
+        Ok(AccountUpdates { updates, task })
+    }
+}
+
+fn timed_out(pubkey: &Pubkey, timeout: Duration) -> eyre::Report {
+    eyre::eyre!("Account {} not ready after {:?}", pubkey, timeout)
+}
+
+/// Websocket URL of an RPC, as the Solana CLI derives it: same host, `ws` scheme and
+/// the next port when one is given.
+pub fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{}:{}", host, port.saturating_add(1)),
+            Err(_) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+
+    format!("{}://{}{}", scheme, authority, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use jito_mock_block_engine::{JsonRpcReply, MockJsonRpc};
+    use mock_geyser::{account_update, MockGeyser, MockGeyserConfig};
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            data: vec![1, 2, 3],
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn account_info(account: Option<&Account>) -> JsonRpcReply {
+        let value = account.map_or(Value::Null, |account| {
+            json!({
+                "data": [STANDARD.encode(&account.data), "base64"],
+                "executable": account.executable,
+                "lamports": account.lamports,
+                "owner": account.owner.to_string(),
+                "rentEpoch": account.rent_epoch,
+                "space": account.data.len(),
+            })
+        });
+        JsonRpcReply::Result(json!({ "context": { "slot": 1 }, "value": value }))
+    }
+
+    /// An RPC node that has no account until `found` polls, then `account`.
+    async fn rpc_finding(account: &Account, found: usize) -> MockJsonRpc {
+        let rpc = MockJsonRpc::start().await.unwrap();
+        let mut replies = vec![account_info(None); found];
+        replies.push(account_info(Some(account)));
+        rpc.script("getAccountInfo", replies);
+        rpc
+    }
+
+    fn watcher(rpc: &MockJsonRpc, source: WatchSource) -> AccountWatcher {
+        // The client asks for the version before its first call with a commitment
+        rpc.script(
+            "getVersion",
+            vec![JsonRpcReply::Result(
+                json!({ "solana-core": "1.17.20", "feature-set": 0 }),
+            )],
+        );
+        AccountWatcher::new(Arc::new(RpcClient::new(rpc.url())))
+            .with_source(source)
+            .with_timeout(Duration::from_secs(5))
+    }
+
+    /// A URL nothing listens on.
+    async fn closed_url(scheme: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("{}://{}", scheme, listener.local_addr().unwrap())
+    }
+
+    #[tokio::test]
+    async fn polls_until_account_exists() {
+        let pubkey = Pubkey::new_unique();
+        let expected = account(1_000);
+        let rpc = rpc_finding(&expected, 2).await;
+
+        let found = watcher(&rpc, WatchSource::Polling)
+            .wait_for_account(&pubkey)
+            .await
+            .unwrap();
+
+        assert_eq!(found, expected);
+        let calls = rpc.calls_of("getAccountInfo");
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].params[0], pubkey.to_string());
+    }
+
+    #[tokio::test]
+    async fn polls_until_predicate_holds() {
+        let rpc = MockJsonRpc::start().await.unwrap();
+        rpc.script(
+            "getAccountInfo",
+            vec![
+                account_info(Some(&account(1))),
+                account_info(Some(&account(1_000))),
+            ],
+        );
+
+        let found = watcher(&rpc, WatchSource::Polling)
+            .wait_for(&Pubkey::new_unique(), |account| account.lamports >= 1_000)
+            .await
+            .unwrap();
+
+        assert_eq!(found.lamports, 1_000);
+        assert_eq!(rpc.calls_of("getAccountInfo").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_out_when_account_never_appears() {
+        let rpc = MockJsonRpc::start().await.unwrap();
+        rpc.script("getAccountInfo", vec![account_info(None)]);
+
+        let error = watcher(&rpc, WatchSource::Polling)
+            .with_timeout(Duration::from_millis(600))
+            .wait_for_account(&Pubkey::new_unique())
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("not ready after"), "{}", error);
+        // Polled with a growing delay, not in a tight loop
+        assert!(rpc.calls_of("getAccountInfo").len() <= 3);
+    }
+
+    #[tokio::test]
+    async fn waits_for_geyser_update() {
+        let geyser = MockGeyser::start(MockGeyserConfig::default())
+            .await
+            .unwrap();
+        let rpc = MockJsonRpc::start().await.unwrap();
+        rpc.script("getAccountInfo", vec![account_info(None)]);
+        let pubkey = Pubkey::new_unique();
+        let expected = account(1_000);
+
+        let watcher = watcher(
+            &rpc,
+            WatchSource::Geyser {
+                url: geyser.url(),
+                x_token: None,
+            },
+        );
+        let wait = tokio::spawn(async move {
+            watcher
+                .wait_for(&pubkey, |account| account.lamports >= 1_000)
+                .await
+        });
+
+        while geyser.requests().is_empty() {
+            sleep(Duration::from_millis(10)).await;
+        }
+        geyser.emit(account_update(&Pubkey::new_unique(), &expected, 5));
+        geyser.emit(account_update(&pubkey, &account(1), 6));
+        geyser.emit(account_update(&pubkey, &expected, 7));
+
+        assert_eq!(wait.await.unwrap().unwrap(), expected);
+        // Fetched once after subscribing, never polled
+        assert_eq!(rpc.calls_of("getAccountInfo").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn polls_when_geyser_is_unreachable() {
+        let expected = account(1_000);
+        let rpc = rpc_finding(&expected, 1).await;
+        let source = WatchSource::Geyser {
+            url: closed_url("http").await,
+            x_token: None,
+        };
+
+        let found = watcher(&rpc, source)
+            .wait_for_account(&Pubkey::new_unique())
+            .await
+            .unwrap();
+
+        assert_eq!(found, expected);
+        assert_eq!(rpc.calls_of("getAccountInfo").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn polls_when_websocket_is_unreachable() {
+        let expected = account(1_000);
+        let rpc = rpc_finding(&expected, 1).await;
+        let source = WatchSource::Websocket {
+            url: closed_url("ws").await,
+        };
+
+        let found = watcher(&rpc, source)
+            .wait_for_account(&Pubkey::new_unique())
+            .await
+            .unwrap();
+
+        assert_eq!(found, expected);
+        assert_eq!(rpc.calls_of("getAccountInfo").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn polling_watcher_cannot_watch_accounts() {
+        let rpc = MockJsonRpc::start().await.unwrap();
+
+        assert!(watcher(&rpc, WatchSource::Polling)
+            .watch_accounts(&[Pubkey::new_unique()])
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn derives_websocket_url_from_rpc_url() {
+        assert_eq!(
+            websocket_url("http://127.0.0.1:8899"),
+            "ws://127.0.0.1:8900"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com/api-key"),
+            "wss://rpc.example.com/api-key"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com:443/path"),
+            "wss://rpc.example.com:444/path"
+        );
+        assert_eq!(websocket_url("localhost:8899"), "ws://localhost:8900");
+        // Not a port, kept as is
+        assert_eq!(websocket_url("http://[::1]/rpc"), "ws://[::1]/rpc");
+    }
+}
@@ -1,10 +1,8 @@
-use std::{str::FromStr, sync::Arc};
+use std::str::FromStr;
 
 use bincode::serialize;
-use jito_protos::searcher::SubscribeBundleResultsRequest;
-use jito_searcher_client::{get_searcher_client, send_bundle_with_confirmation};
+use log::debug;
 use solana_address_lookup_table_program::state::AddressLookupTable;
-use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     address_lookup_table::AddressLookupTableAccount,
     message::{v0::Message, VersionedMessage},
@@ -19,81 +17,60 @@ use spl_associated_token_account::get_associated_token_address;
 use spl_token::instruction::sync_native;
 
 use crate::{
-    env::{
-        jito_auth::{auth_keypair, jito_tip_acc, jito_tip_inx},
-        load_minter_settings, PoolDataSettings,
+    env::{load_minter_settings, PoolDataSettings},
+    raydium::{
+        account_watcher::AccountWatcher,
+        fallback::{send_step, Fee},
+        instruction::decoder::SOLC_MINT,
+        manifest::LaunchManifest,
+        wallets::list_folders,
     },
-    raydium::{instruction::decoder::SOLC_MINT, wallets::load_wallets},
 };
 
 pub async fn wsol(
     pool_data: PoolDataSettings,
     wallets: Vec<&Keypair>,
-) -> Result<Vec<VersionedTransaction>, Box<dyn std::error::Error + Send>> {
-    let lut_creation = match Pubkey::from_str(&pool_data.lut_key) {
-        Ok(lut) => lut,
-        Err(e) => {
-            panic!("LUT key not Found in Settings: {}", e);
-        }
-    };
-
-    let connection = RpcClient::new(pool_data.rpc_url);
+    fee: Fee,
+) -> eyre::Result<Vec<VersionedTransaction>> {
+    fee.validate()?;
 
-    let mut raw_account = None;
+    let lut_creation = Pubkey::from_str(&pool_data.lut_key)
+        .map_err(|e| eyre::eyre!("LUT key not Found in Settings: {}", e))?;
 
-    while raw_account.is_none() {
-        match connection.get_account(&lut_creation).await {
-            Ok(account) => raw_account = Some(account),
-            Err(e) => {
-                eprintln!("Error getting LUT account: {}, retrying...", e);
-            }
-        }
-    }
+    let watcher = AccountWatcher::from_settings(&pool_data);
+    let connection = watcher.rpc_client();
 
-    let raw_account = raw_account.unwrap();
+    let raw_account = watcher.wait_for_account(&lut_creation).await?;
 
-    let address_lookup_table = match AddressLookupTable::deserialize(&raw_account.data) {
-        Ok(address_lookup_table) => address_lookup_table,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Error: {}", e);
-        }
-    };
+    let address_lookup_table = AddressLookupTable::deserialize(&raw_account.data)
+        .map_err(|e| eyre::eyre!("LUT {} cannot be decoded: {}", lut_creation, e))?;
     let address_lookup_table_account = AddressLookupTableAccount {
         key: lut_creation,
         addresses: address_lookup_table.addresses.to_vec(),
     };
 
-    let buyer_wallet = Keypair::from_base58_string(&pool_data.buyer_key);
+    let buyer_wallet = pool_data.buyer_key.keypair();
 
-    let balance = connection
-        .get_balance(&buyer_wallet.pubkey())
-        .await
-        .unwrap();
+    let balance = connection.get_balance(&buyer_wallet.pubkey()).await?;
 
     println!("Buyer Balance: {} SOL", lamports_to_sol(balance));
 
-    let mint = Pubkey::from_str(&pool_data.token_mint).unwrap();
+    let mint = Pubkey::from_str(&pool_data.token_mint)
+        .map_err(|e| eyre::eyre!("Token mint not Found in Settings: {}", e))?;
 
-    let recent_blockhash = match connection.get_latest_blockhash().await {
-        Ok(recent_blockhash) => recent_blockhash,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Error: {}", e);
-        }
-    };
+    let recent_blockhash = connection.get_latest_blockhash().await?;
 
-    let wallet_chunks: Vec<_> = wallets.chunks(3).collect();
-    let mut txns_chunk = Vec::new();
+    // Instructions and signers of each transaction, wallets without SOL to wrap are left out
+    let mut chunks = Vec::new();
 
-    for (chunk_index, wallet_chunk) in wallet_chunks.iter().enumerate() {
+    for (chunk_index, wallet_chunk) in wallets.chunks(3).enumerate() {
         let mut current_instructions = Vec::new();
         let mut current_wallets = Vec::new();
 
         for wallet in wallet_chunk.iter() {
             let user_token_source = get_associated_token_address(&wallet.pubkey(), &SOLC_MINT);
 
-            let balance = connection.get_balance(&wallet.pubkey()).await.unwrap();
+            let balance = connection.get_balance(&wallet.pubkey()).await?;
 
             //if the balance is less than 0.00203928 SOL, skip the wallet
             if balance < sol_to_lamports(0.02) {
@@ -123,72 +100,56 @@ pub async fn wsol(
                 &user_token_source,
                 balance - sol_to_lamports(0.006),
             ));
-
-            let sync_native = match sync_native(&spl_token::id(), &user_token_source) {
-                Ok(sync_native) => sync_native,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    panic!("Error: {}", e);
-                }
-            };
-            if chunk_index == wallet_chunks.len() - 1 && wallet == wallet_chunk.last().unwrap() {
-                let tip = jito_tip_inx(
-                    buyer_wallet.pubkey(),
-                    jito_tip_acc(),
-                    sol_to_lamports(0.001),
-                );
-                current_instructions.push(tip);
-            }
-            current_instructions.push(sync_native);
+            current_instructions.push(sync_native(&spl_token::id(), &user_token_source)?);
 
             current_wallets.push(*wallet);
         }
 
-        println!(
-            "Chunk {}: {} instructions",
+        debug!(
+            "Chunk {}: {} instructions for {} wallets",
             chunk_index,
-            current_instructions.len()
+            current_instructions.len(),
+            current_wallets.len()
         );
 
-        println!("Chunk {}: {} wallets", chunk_index, current_wallets.len());
-
-        if current_instructions.len() == 0 {
-            continue;
+        if !current_instructions.is_empty() {
+            chunks.push((current_instructions, current_wallets));
         }
+    }
+
+    // The last transaction tips, whichever wallets it wraps
+    if let (Some(tip), Some((instructions, _))) =
+        (fee.tip(buyer_wallet.pubkey()), chunks.last_mut())
+    {
+        instructions.push(tip);
+    }
+
+    let mut txns_chunk = Vec::new();
+    for (mut current_instructions, mut current_wallets) in chunks {
         current_wallets.push(&buyer_wallet);
+        current_instructions.splice(0..0, fee.compute_budget()?);
 
-        let versioned_msg = VersionedMessage::V0(
-            Message::try_compile(
-                &buyer_wallet.pubkey(),
-                &current_instructions,
-                &[address_lookup_table_account.clone()],
-                recent_blockhash,
-            )
-            .unwrap(),
-        );
+        let versioned_msg = VersionedMessage::V0(Message::try_compile(
+            &buyer_wallet.pubkey(),
+            &current_instructions,
+            std::slice::from_ref(&address_lookup_table_account),
+            recent_blockhash,
+        )?);
 
-        let versioned_tx = match VersionedTransaction::try_new(versioned_msg, &current_wallets) {
-            Ok(tx) => tx,
-            Err(e) => {
-                eprintln!("Error creating pool transaction: {}", e);
-                panic!("Error: {}", e);
-            }
-        };
+        let versioned_tx = VersionedTransaction::try_new(versioned_msg, &current_wallets)?;
 
         txns_chunk.push(versioned_tx);
-        // Now you can use chunk_index, current_wallets, and current_instructions
     }
-    println!("txn: {:?}", txns_chunk.len());
 
-    let txn_size: Vec<_> = txns_chunk
+    let sizes: Vec<usize> = txns_chunk
         .iter()
-        .map(|x| {
-            let serialized_x = serialize(x).unwrap();
-            serialized_x.len()
-        })
+        .map(|tx| serialize(tx).map_or(0, |tx| tx.len()))
         .collect();
-
-    println!("txn_size: {:?}", txn_size);
+    debug!(
+        "{} wrap transactions of {:?} bytes",
+        txns_chunk.len(),
+        sizes
+    );
 
     Ok(txns_chunk)
 }
@@ -202,53 +163,184 @@ pub async fn sol_wrap() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let rpc_client = Arc::new(RpcClient::new(settings.rpc_url.clone()));
-
-    let wallets: Vec<Keypair> = match load_wallets().await {
-        Ok(wallets) => wallets,
+    let (folder, wallets): (String, Vec<Keypair>) = match list_folders().await {
+        Ok(folders) => folders,
         Err(e) => {
             eprintln!("Error: {}", e);
             panic!("Error: {}", e);
         }
     };
 
+    let mut manifest = LaunchManifest::load(&folder)?;
+
+    if let Err(e) = wrap_wallets(settings, &wallets, &mut manifest).await {
+        eprintln!("Distribution Error: {}", e);
+        panic!("Error: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Creates the wSOL and token ATAs for `wallets` and wraps their SOL, one bundle per 14 wallets.
+///
+/// Uses the LUT recorded in `manifest`. Each chunk is sent with [`send_step`], which
+/// records its attempts.
+pub async fn wrap_wallets(
+    mut settings: PoolDataSettings,
+    wallets: &[Keypair],
+    manifest: &mut LaunchManifest,
+) -> eyre::Result<()> {
+    settings.lut_key = manifest.lut(&settings)?.to_string();
+
     let wallet_chunks: Vec<_> = wallets.chunks(14).collect();
 
-    for (_chunk_index, wallet_chunk) in wallet_chunks.iter().enumerate() {
-        let wallets: Vec<&Keypair> = wallet_chunk.iter().map(|x| x).collect();
+    for wallet_chunk in wallet_chunks.iter() {
+        let wallets: Vec<&Keypair> = wallet_chunk.iter().collect();
 
-        let wrap = match wsol(settings.clone(), wallets).await {
-            Ok(wrap) => wrap,
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                panic!("Error: {}", e);
-            }
-        };
+        let (settings, wallets) = (&settings, &wallets);
+        send_step(settings, "wrap", sol_to_lamports(0.001), manifest, |fee| {
+            wsol(settings.clone(), wallets.clone(), fee)
+        })
+        .await?;
+    }
 
-        let mut client =
-            get_searcher_client(&settings.block_engine_url, &Arc::new(auth_keypair())).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jito_mock_block_engine::JsonRpcReply;
+    use serde_json::json;
+    use solana_sdk::system_instruction::SystemInstruction;
+
+    use super::*;
+    use crate::{
+        env::jito_auth::{generate_tip_accounts, tip_program_id},
+        raydium::{
+            fallback::PriorityFee,
+            testing::{MockCluster, LANDED_SLOT},
+        },
+    };
 
-        let mut bundle_results_subscription = client
-            .subscribe_bundle_results(SubscribeBundleResultsRequest {})
+    #[tokio::test]
+    async fn wraps_through_block_engine() {
+        let cluster = MockCluster::start().await;
+        cluster.set_balance(sol_to_lamports(1.0));
+        cluster.set_lookup_table(Vec::new());
+        let settings = cluster.settings();
+        let buyer = settings.buyer_key.keypair().pubkey();
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = LaunchManifest::load(dir.path().to_str().unwrap()).unwrap();
+        manifest.lut_key = Some(Pubkey::new_unique().to_string());
+        let wallets: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+
+        wrap_wallets(settings, &wallets, &mut manifest)
             .await
-            .expect("subscribe to bundle results")
-            .into_inner();
-
-        match send_bundle_with_confirmation(
-            &wrap,
-            &rpc_client.clone(),
-            &mut client,
-            &mut bundle_results_subscription,
-        )
-        .await
-        {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Distribution Error: {}", e);
-                panic!("Error: {}", e);
+            .unwrap();
+
+        let bundles = cluster.bundles();
+        assert_eq!(bundles.len(), 1);
+        // Three wallets per transaction, each paid for by the buyer
+        assert_eq!(bundles[0].len(), 2);
+        for (transaction, wallets) in bundles[0].iter().zip(wallets.chunks(3)) {
+            let keys = transaction.message.static_account_keys();
+            assert_eq!(keys[0], buyer);
+            assert_eq!(transaction.signatures.len(), wallets.len() + 1);
+
+            let wrapped: Vec<(Pubkey, u64)> = transaction
+                .message
+                .instructions()
+                .iter()
+                .filter_map(|ix| match bincode::deserialize(&ix.data) {
+                    Ok(SystemInstruction::Transfer { lamports }) => {
+                        Some((keys[ix.accounts[1] as usize], lamports))
+                    }
+                    _ => None,
+                })
+                .filter(|(to, _)| *to != buyer)
+                .collect();
+            for wallet in wallets {
+                let wsol_account = get_associated_token_address(&wallet.pubkey(), &SOLC_MINT);
+                assert!(wrapped
+                    .contains(&(wsol_account, sol_to_lamports(1.0) - sol_to_lamports(0.006))));
             }
-        };
+        }
+
+        assert_eq!(manifest.bundles.len(), 1);
+        assert_eq!(manifest.bundles[0].step, "wrap");
+        assert!(manifest.bundles[0].landed);
+        assert_eq!(manifest.bundles[0].slot, Some(LANDED_SLOT));
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn fails_on_unusable_lookup_table() {
+        let cluster = MockCluster::start().await;
+        cluster.set_balance(sol_to_lamports(1.0));
+        let wallet = Keypair::new();
+        let fee = Fee::Priority(PriorityFee {
+            micro_lamports: 1_000,
+            compute_unit_limit: 200_000,
+        });
+
+        let mut settings = cluster.settings();
+        settings.lut_key = "not a key".to_string();
+        assert!(wsol(settings, vec![&wallet], fee).await.is_err());
+
+        cluster.rpc.script(
+            "getAccountInfo",
+            vec![JsonRpcReply::Result(json!({
+                "context": { "slot": LANDED_SLOT },
+                "value": {
+                    "data": ["AAAA", "base64"],
+                    "executable": false,
+                    "lamports": 1_000_000,
+                    "owner": Pubkey::new_unique().to_string(),
+                    "rentEpoch": 0,
+                    "space": 3,
+                },
+            }))],
+        );
+        let mut settings = cluster.settings();
+        settings.lut_key = Pubkey::new_unique().to_string();
+        let error = wsol(settings, vec![&wallet], fee).await.unwrap_err();
+        assert!(error.to_string().contains("cannot be decoded"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn tips_when_last_wallet_is_skipped() {
+        let cluster = MockCluster::start().await;
+        cluster.set_lookup_table(Vec::new());
+        let wallets: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        // Too little SOL to wrap, the bundle ends with the first three wallets
+        let skipped = wallets[3].pubkey().to_string();
+        cluster.rpc.answer("getBalance", move |params| {
+            let lamports = match params[0].as_str() == Some(skipped.as_str()) {
+                true => sol_to_lamports(0.01),
+                false => sol_to_lamports(1.0),
+            };
+            JsonRpcReply::Result(json!({ "context": { "slot": LANDED_SLOT }, "value": lamports }))
+        });
+        let settings = cluster.settings();
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = LaunchManifest::load(dir.path().to_str().unwrap()).unwrap();
+        manifest.lut_key = Some(Pubkey::new_unique().to_string());
+
+        wrap_wallets(settings, &wallets, &mut manifest)
+            .await
+            .unwrap();
+
+        let bundles = cluster.bundles();
+        assert_eq!(bundles[0].len(), 1);
+        let transaction = &bundles[0][0];
+        let keys = transaction.message.static_account_keys();
+        let tip_accounts = generate_tip_accounts(&tip_program_id());
+        assert!(transaction.message.instructions().iter().any(|ix| {
+            matches!(
+                bincode::deserialize(&ix.data),
+                Ok(SystemInstruction::Transfer { lamports })
+                    if lamports == sol_to_lamports(0.001)
+                        && tip_accounts.contains(&keys[ix.accounts[1] as usize])
+            )
+        }));
+    }
 }
@@ -1,12 +1,14 @@
-use std::{io::Write, str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc};
 
 use bincode::serialize;
-use jito_protos::searcher::SubscribeBundleResultsRequest;
-use jito_searcher_client::{get_searcher_client, send_bundle_with_confirmation};
+use jito_searcher_client::retry::send_bundle_with_retry;
+use log::info;
 use solana_address_lookup_table_program::state::AddressLookupTable;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::Instruction,
     message::{v0::Message, VersionedMessage},
     native_token::lamports_to_sol,
     pubkey::Pubkey,
@@ -18,17 +20,20 @@ use spl_associated_token_account::get_associated_token_address;
 
 use crate::{
     env::{
-        input::bundle_priority_tip,
-        jito_auth::{auth_keypair, jito_tip_acc, jito_tip_inx},
-        load_minter_settings,
+        cluster::Cluster,
+        input::{bundle_priority_tip, liq_amount, token_percentage},
+        jito_auth::{bundle_sender, jito_tip_acc, jito_tip_inx, validate_tip_account},
+        load_minter_settings, PoolDataSettings,
     },
     raydium::{
+        account_watcher::{AccountWatcher, WatchSource},
         instruction::{
             decoder::SOLC_MINT,
-            instruction::SOL_MINT,
-            pool_ixs::{load_pool_keys, pool_ixs},
+            instruction::{AmmKeys, MarketPubkeys, SOL_MINT},
+            pool_ixs::{deployer_token_balance, load_pool_keys, pool_ixs, LaunchParams},
             swap_ixs::swap_ixs,
         },
+        manifest::LaunchManifest,
         wallets::list_folders,
     },
 };
@@ -40,25 +45,152 @@ pub struct PoolDeployResponse {
 }
 
 pub async fn pool_main() -> eyre::Result<()> {
-    let (_, wallets) = match list_folders().await {
-        Ok(folders) => folders,
-        Err(e) => {
-            eprintln!("Error listing folders: {}", e);
-            return Ok(());
-        }
+    let (folder, wallets) = list_folders()
+        .await
+        .map_err(|e| eyre::eyre!("Error listing folders: {}", e))?;
+
+    let engine = load_minter_settings().await?;
+    let mut manifest = LaunchManifest::load(&folder)?;
+
+    let params = LaunchParams {
+        sol_amount: liq_amount(),
+        token_percentage: token_percentage(),
+        bundle_tip: bundle_priority_tip().await,
+        open_time: 0,
     };
 
-    let mut engine = load_minter_settings().await?;
+    launch_pool(engine, &wallets, &mut manifest, params).await
+}
+
+/// Accounts shared by every transaction of the launch bundle.
+pub struct LaunchAccounts<'a> {
+    pub cluster: &'a Cluster,
+    pub deployer: &'a Keypair,
+    pub buyer: &'a Keypair,
+    pub token_mint: Pubkey,
+    pub amm_keys: AmmKeys,
+    pub market_keys: MarketPubkeys,
+    pub lookup_table: AddressLookupTableAccount,
+}
+
+/// Builds the launch bundle: the pool creation transaction followed by the buys.
+///
+/// `buys` pairs each wallet with the wSOL amount it swaps. Buys are packed seven per
+/// transaction and the tip to `tip_account` is appended to the last one. Fails without
+/// buys, which would leave the bundle untipped, and when `tip_account` is not a known tip
+/// account.
+pub fn build_launch_bundle(
+    accounts: &LaunchAccounts,
+    pool_ixs: &[Instruction],
+    buys: &[(&Keypair, u64)],
+    params: &LaunchParams,
+    tip_account: Pubkey,
+    recent_blockhash: Hash,
+) -> eyre::Result<Vec<VersionedTransaction>> {
+    validate_tip_account(&tip_account)?;
+    if buys.is_empty() {
+        return Err(eyre::eyre!("No wallet holds wSOL to buy with"));
+    }
+
+    //-------------------Pool Transaction---------------------------------------
+    let versioned_msg = VersionedMessage::V0(Message::try_compile(
+        &accounts.deployer.pubkey(),
+        pool_ixs,
+        std::slice::from_ref(&accounts.lookup_table),
+        recent_blockhash,
+    )?);
+
+    let versioned_tx = VersionedTransaction::try_new(versioned_msg, &[accounts.deployer])?;
+
+    // -------------------Swap Instructions---------------------------------------
+
+    let buy_chunks = buys.chunks(7).collect::<Vec<_>>();
+    let mut txns_chunk = Vec::new();
+
+    txns_chunk.push(versioned_tx);
+
+    for (chunk_index, buy_chunk) in buy_chunks.iter().enumerate() {
+        let mut current_instructions = Vec::new();
+        let mut current_wallets = Vec::new();
+
+        for (wallet, amount_in) in buy_chunk.iter() {
+            let user_token_source = get_associated_token_address(&wallet.pubkey(), &SOL_MINT);
+
+            let swap_ixs = swap_ixs(
+                accounts.cluster,
+                &accounts.token_mint,
+                accounts.amm_keys,
+                accounts.market_keys.clone(),
+                wallet,
+                *amount_in,
+                false,
+                user_token_source,
+            )?;
+
+            current_instructions.push(swap_ixs);
+            current_wallets.push(*wallet);
+        }
+
+        if chunk_index == buy_chunks.len() - 1 {
+            let tip = jito_tip_inx(accounts.buyer.pubkey(), tip_account, params.bundle_tip);
+            current_instructions.push(tip);
+        }
+
+        info!("Tx-{}: {} wallets", chunk_index + 1, current_wallets.len());
+
+        current_wallets.push(accounts.buyer);
+
+        let versioned_msg = VersionedMessage::V0(Message::try_compile(
+            &accounts.buyer.pubkey(),
+            &current_instructions,
+            std::slice::from_ref(&accounts.lookup_table),
+            recent_blockhash,
+        )?);
+
+        let versioned_tx = VersionedTransaction::try_new(versioned_msg, &current_wallets)?;
+
+        txns_chunk.push(versioned_tx);
+    }
+
+    if txns_chunk.len() > 5 {
+        return Err(eyre::eyre!("Too many transactions to send in one bundle"));
+    }
+
+    Ok(txns_chunk)
+}
+
+/// Creates the pool and buys from every wallet in a single bundle.
+///
+/// The market keys and LUT come from `manifest` when an earlier mode recorded them.
+pub async fn launch_pool(
+    engine: PoolDataSettings,
+    wallets: &[Keypair],
+    manifest: &mut LaunchManifest,
+    params: LaunchParams,
+) -> eyre::Result<()> {
+    manifest.record_target(&engine)?;
 
     let rpc_client = Arc::new(RpcClient::new(engine.rpc_url.clone()));
 
-    let deployer_key = Keypair::from_base58_string(&engine.deployer_key.clone());
-    let buyer_key = Keypair::from_base58_string(&engine.buyer_key.clone());
+    let deployer_key = engine.deployer_key.keypair();
+    let buyer_key = engine.buyer_key.keypair();
+    let token_mint = Pubkey::from_str(&engine.token_mint)?;
+    let market = Pubkey::from_str(&engine.market_id)?;
 
     // -------------------Pool Creation Instructions--------------------------
     println!("Creating Pool Transaction");
 
-    let (create_pool_ixs, amm_pool, amm_keys) = match pool_ixs(engine.clone()).await {
+    let token_balance =
+        deployer_token_balance(&rpc_client, &deployer_key.pubkey(), &token_mint, &params).await?;
+
+    let (create_pool_ixs, amm_keys) = match pool_ixs(
+        &engine.cluster,
+        &deployer_key.pubkey(),
+        &market,
+        &token_mint,
+        token_balance,
+        &params,
+    ) {
         Ok(ixs) => ixs,
         Err(e) => {
             eprintln!("Error creating pool IXs: {}", e);
@@ -66,185 +198,288 @@ pub async fn pool_main() -> eyre::Result<()> {
         }
     };
 
-    engine.pool_id = amm_pool.to_string();
-    let mut file = std::fs::File::create("mintor_settings.json").unwrap();
-    file.write_all(serde_json::to_string_pretty(&engine)?.as_bytes())?;
-
-    let market_keys = load_pool_keys(rpc_client.clone(), amm_keys).await?;
+    let market_keys = match manifest.market_keys.clone() {
+        Some(market_keys) => market_keys,
+        None => load_pool_keys(rpc_client.clone(), amm_keys).await?,
+    };
 
-    let bundle_tip = bundle_priority_tip().await;
+    manifest.record_pool(amm_keys, market_keys.clone());
+    manifest.save()?;
 
     // -------------------LUT Account------------------------------------------
 
-    let lut_creation = match Pubkey::from_str(&engine.lut_key) {
-        Ok(lut) => lut,
-        Err(e) => {
-            panic!("LUT key not Found in Settings: {}", e);
-        }
-    };
+    let lut_creation = manifest.lut(&engine)?;
 
-    let mut raw_account = None;
-
-    while raw_account.is_none() {
-        match rpc_client.get_account(&lut_creation).await {
-            Ok(account) => raw_account = Some(account),
-            Err(e) => {
-                eprintln!("Error getting LUT account: {}, retrying...", e);
-            }
-        }
-    }
-
-    let raw_account = raw_account.unwrap();
+    let raw_account = AccountWatcher::new(rpc_client.clone())
+        .with_source(WatchSource::from_settings(&engine))
+        .wait_for_account(&lut_creation)
+        .await?;
 
     let address_lookup_table = AddressLookupTable::deserialize(&raw_account.data)?;
     let address_lookup_table_account = AddressLookupTableAccount {
         key: lut_creation,
         addresses: address_lookup_table.addresses.to_vec(),
     };
-    let market_keys = market_keys.clone();
-    let server_data = engine.clone();
-
-    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
-
-    //-------------------Pool Transaction---------------------------------------
-    let versioned_msg = VersionedMessage::V0(
-        Message::try_compile(
-            &deployer_key.pubkey(),
-            &create_pool_ixs, /* , tax_txn*/
-            &[address_lookup_table_account.clone()],
-            recent_blockhash,
-        )
-        .unwrap(),
-    );
 
-    let versioned_tx = match VersionedTransaction::try_new(versioned_msg, &[&deployer_key]) {
-        Ok(tx) => tx,
-        Err(e) => {
-            eprintln!("Error creating pool transaction: {}", e);
-            return Err(e.into());
-        }
-    };
+    // -------------------Wallet Balances---------------------------------------
 
-    // -------------------Swap Instructions---------------------------------------
+    let mut buys = Vec::new();
+    for wallet in wallets {
+        let user_token_source = get_associated_token_address(&wallet.pubkey(), &SOLC_MINT);
 
-    let wallets_chunks = wallets.chunks(7).collect::<Vec<_>>();
-    let mut txns_chunk = Vec::new();
+        let balance = match rpc_client
+            .get_token_account_balance(&user_token_source)
+            .await
+        {
+            Ok(balance) => balance.amount.parse::<u64>()?,
+            Err(e) => {
+                eprintln!("Error getting token account balance: {}", e);
+                continue;
+            }
+        };
 
-    txns_chunk.push(versioned_tx);
+        println!("Balance: {} SOL", lamports_to_sol(balance));
 
-    for (chunk_index, wallet_chunk) in wallets_chunks.iter().enumerate() {
-        let mut current_instructions = Vec::new();
-        let mut current_wallets = Vec::new();
+        buys.push((wallet, balance));
+    }
 
-        for (i, wallet) in wallet_chunk.iter().enumerate() {
-            let user_token_source = get_associated_token_address(&wallet.pubkey(), &SOLC_MINT);
-
-            let balance = match rpc_client
-                .get_token_account_balance(&user_token_source)
-                .await
-            {
-                Ok(balance) => balance.amount.parse::<u64>().unwrap(),
-                Err(e) => {
-                    eprintln!("Error getting token account balance: {}", e);
-                    continue;
-                }
-            };
+    let mut sender = bundle_sender(&engine).await?;
 
-            println!("Balance: {} SOL", lamports_to_sol(balance));
+    let tip_account = jito_tip_acc(sender.as_mut()).await;
 
-            let user_token_source = get_associated_token_address(&wallet.pubkey(), &SOL_MINT);
+    let accounts = LaunchAccounts {
+        cluster: &engine.cluster,
+        deployer: &deployer_key,
+        buyer: &buyer_key,
+        token_mint,
+        amm_keys,
+        market_keys,
+        lookup_table: address_lookup_table_account,
+    };
 
-            let swap_ixs = swap_ixs(
-                server_data.clone(),
-                amm_keys.clone(),
-                market_keys.clone(),
-                wallet,
-                balance,
-                false,
-                user_token_source,
-            )
-            .unwrap();
+    // -------------------Subscribe to Bundle Results---------------------------------------
 
-            current_instructions.push(swap_ixs);
-            current_wallets.push(wallet);
+    let (accounts, create_pool_ixs, buys, rpc) = (&accounts, &create_pool_ixs, &buys, &rpc_client);
+    let outcome = send_bundle_with_retry(
+        &engine.retry,
+        params.bundle_tip,
+        |bundle_tip| async move {
+            let recent_blockhash = rpc.get_latest_blockhash().await?;
+            let params = LaunchParams {
+                bundle_tip,
+                ..params
+            };
 
-            if chunk_index == wallets_chunks.len() - 1 && i == wallet_chunk.len() - 1 {
-                let tip = jito_tip_inx(buyer_key.pubkey(), jito_tip_acc(), bundle_tip);
-                current_instructions.push(tip);
-            }
-        }
+            let txns_chunk = build_launch_bundle(
+                accounts,
+                create_pool_ixs,
+                buys,
+                &params,
+                tip_account,
+                recent_blockhash,
+            )?;
 
-        println!("Tx-{}: {} wallets", chunk_index + 1, current_wallets.len());
+            txns_chunk.iter().for_each(|tx| {
+                println!("Txn: {:?}", tx.signatures);
+            });
 
-        current_wallets.push(&buyer_key);
+            let txn_size: Vec<_> = txns_chunk
+                .iter()
+                .map(|x| {
+                    let serialized_x = serialize(x).unwrap();
+                    serialized_x.len()
+                })
+                .collect();
 
-        let versioned_msg = VersionedMessage::V0(
-            Message::try_compile(
-                &buyer_key.pubkey(),
-                &current_instructions,
-                &[address_lookup_table_account.clone()],
-                recent_blockhash,
-            )
-            .unwrap(),
-        );
+            println!("txn_size: {:?}", txn_size);
 
-        let versioned_tx = match VersionedTransaction::try_new(versioned_msg, &current_wallets) {
-            Ok(tx) => tx,
-            Err(e) => {
-                eprintln!("Error creating pool transaction: {}", e);
-                panic!("Error: {}", e);
+            eyre::Ok(txns_chunk)
+        },
+        &rpc_client,
+        sender.as_mut(),
+    )
+    .await;
+
+    manifest.record_attempts("launch", &outcome.attempts);
+    manifest.save()?;
+
+    match outcome.result {
+        Ok((uuid, landed)) => {
+            println!(
+                "Launch bundle {} landed in slot {} (leader {})",
+                uuid,
+                landed.slot,
+                landed.validator.as_deref().unwrap_or("unknown")
+            );
+            for transaction in &landed.transactions {
+                match transaction.index {
+                    Some(index) => println!(
+                        "  {} slot {} index {}",
+                        transaction.signature, transaction.slot, index
+                    ),
+                    None => println!("  {} slot {}", transaction.signature, transaction.slot),
+                }
             }
-        };
-
-        txns_chunk.push(versioned_tx);
-        // Now you can use chunk_index, current_wallets, and current_instructions
+        }
+        Err(e) => return Err(eyre::eyre!("Error sending bundle: {}", e)),
     }
 
-    txns_chunk.iter().for_each(|tx| {
-        println!("Txn: {:?}", tx.signatures);
-    });
+    Ok(())
+}
 
-    let txn_size: Vec<_> = txns_chunk
-        .iter()
-        .map(|x| {
-            let serialized_x = serialize(x).unwrap();
-            serialized_x.len()
-        })
-        .collect();
+#[cfg(test)]
+mod tests {
+    use solana_sdk::native_token::sol_to_lamports;
 
-    println!("txn_size: {:?}", txn_size);
+    use super::*;
+    use crate::{
+        env::{
+            jito_auth::{generate_tip_accounts, tip_program_id},
+            test_settings,
+        },
+        raydium::testing::{MockCluster, LANDED_SLOT},
+    };
 
-    // -------------------Subscribe to Bundle Results---------------------------------------
+    fn market_keys() -> MarketPubkeys {
+        let key = || Box::new(Pubkey::new_unique());
+        MarketPubkeys {
+            market: key(),
+            req_q: key(),
+            event_q: key(),
+            bids: key(),
+            asks: key(),
+            coin_vault: key(),
+            pc_vault: key(),
+            vault_signer_key: key(),
+            coin_mint: key(),
+            pc_mint: key(),
+            coin_lot_size: 1,
+            pc_lot_size: 1,
+        }
+    }
 
-    let mut client =
-        get_searcher_client(&engine.block_engine_url, &Arc::new(auth_keypair())).await?;
+    #[tokio::test]
+    async fn launches_through_block_engine() {
+        let cluster = MockCluster::start().await;
+        cluster.set_balance(sol_to_lamports(10.0));
+        cluster.set_token_balance(sol_to_lamports(1.0));
+        let settings = cluster.settings();
+        let amm_program = settings.cluster.amm_program;
+        let deployer = settings.deployer_key.keypair().pubkey();
+        let buyer = settings.buyer_key.keypair().pubkey();
+        let params = LaunchParams {
+            sol_amount: sol_to_lamports(1.0),
+            token_percentage: 0.5,
+            bundle_tip: 10_000,
+            open_time: 0,
+        };
 
-    let mut bundle_results_subscription = client
-        .subscribe_bundle_results(SubscribeBundleResultsRequest {})
-        .await
-        .expect("subscribe to bundle results")
-        .into_inner();
+        // The pool and market keys the LUT step adds, without them the buys do not fit
+        let (_, amm_keys) = pool_ixs(
+            &settings.cluster,
+            &deployer,
+            &Pubkey::from_str(&settings.market_id).unwrap(),
+            &Pubkey::from_str(&settings.token_mint).unwrap(),
+            0,
+            &params,
+        )
+        .unwrap();
+        let market_keys = market_keys();
+        cluster.set_lookup_table(vec![
+            amm_keys.amm_pool,
+            amm_keys.amm_coin_mint,
+            amm_keys.amm_pc_mint,
+            amm_keys.amm_lp_mint,
+            amm_keys.amm_authority,
+            amm_keys.amm_open_order,
+            amm_keys.amm_target,
+            amm_keys.amm_coin_vault,
+            amm_keys.amm_pc_vault,
+            amm_keys.market_program,
+            *market_keys.req_q,
+            *market_keys.event_q,
+            *market_keys.bids,
+            *market_keys.asks,
+            *market_keys.coin_vault,
+            *market_keys.pc_vault,
+            *market_keys.vault_signer_key,
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = LaunchManifest::load(dir.path().to_str().unwrap()).unwrap();
+        manifest.lut_key = Some(Pubkey::new_unique().to_string());
+        manifest.market_keys = Some(market_keys);
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+
+        launch_pool(settings, &wallets, &mut manifest, params)
+            .await
+            .unwrap();
 
-    if txns_chunk.len() > 5 {
-        eprintln!("Too many transactions to send in one bundle");
-        return Err(eyre::eyre!("Too many transactions to send in one bundle"));
+        let bundles = cluster.bundles();
+        assert_eq!(bundles.len(), 1);
+        let [pool, buys] = bundles[0].as_slice() else {
+            panic!("expected the pool and one buy transaction");
+        };
+        assert_eq!(pool.message.static_account_keys()[0], deployer);
+        assert!(pool
+            .message
+            .instructions()
+            .iter()
+            .any(|ix| *ix.program_id(pool.message.static_account_keys()) == amm_program));
+        assert_eq!(buys.message.static_account_keys()[0], buyer);
+        assert_eq!(buys.signatures.len(), wallets.len() + 1);
+
+        assert_eq!(manifest.pool_id, Some(amm_keys.amm_pool.to_string()));
+        assert_eq!(manifest.bundles.len(), 1);
+        assert_eq!(manifest.bundles[0].step, "launch");
+        assert!(manifest.bundles[0].landed);
+        assert_eq!(manifest.bundles[0].slot, Some(LANDED_SLOT));
     }
 
-    let rpc_client = &Arc::new(rpc_client);
-
-    match send_bundle_with_confirmation(
-        &txns_chunk,
-        rpc_client,
-        &mut client,
-        &mut bundle_results_subscription,
-    )
-    .await
-    {
-        Ok(bundle_results) => bundle_results,
-        Err(e) => {
-            eprintln!("Error sending bundle: {}", e);
-        }
-    };
+    #[test]
+    fn rejects_launch_without_buys() {
+        let settings = test_settings(serde_json::json!({})).unwrap();
+        let deployer = settings.deployer_key.keypair();
+        let buyer = settings.buyer_key.keypair();
+        let params = LaunchParams {
+            sol_amount: sol_to_lamports(1.0),
+            token_percentage: 0.5,
+            bundle_tip: 10_000,
+            open_time: 0,
+        };
+        let (pool_ixs, amm_keys) = pool_ixs(
+            &settings.cluster,
+            &deployer.pubkey(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            sol_to_lamports(1.0),
+            &params,
+        )
+        .unwrap();
+        let accounts = LaunchAccounts {
+            cluster: &settings.cluster,
+            deployer: &deployer,
+            buyer: &buyer,
+            token_mint: Pubkey::new_unique(),
+            amm_keys,
+            market_keys: market_keys(),
+            lookup_table: AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: Vec::new(),
+            },
+        };
+        let tip_account = generate_tip_accounts(&tip_program_id())[0];
+
+        let error = build_launch_bundle(
+            &accounts,
+            &pool_ixs,
+            &[],
+            &params,
+            tip_account,
+            Hash::default(),
+        )
+        .unwrap_err();
 
-    Ok(())
+        assert!(error.to_string().contains("No wallet"), "{}", error);
+    }
 }
@@ -0,0 +1,551 @@
+//! Live balances of a wallet folder.
+//!
+//! Every wallet's system account, wSOL ATA and token ATA are fetched with batched
+//! `getMultipleAccounts` calls, then kept current from account subscriptions through
+//! the [`AccountWatcher`]. Without a subscription the balances are polled instead.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{stdout, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    terminal::{Clear, ClearType},
+};
+use log::warn;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::program_pack::Pack;
+use solana_sdk::{
+    account::Account,
+    native_token::{lamports_to_sol, LAMPORTS_PER_SOL},
+    pubkey::Pubkey,
+    signer::Signer,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::{Account as TokenAccount, Mint};
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::{
+    env::{load_minter_settings, PoolDataSettings},
+    raydium::{
+        account_watcher::AccountWatcher, instruction::decoder::SOLC_MINT, manifest::LaunchManifest,
+        wallets::list_folders,
+    },
+};
+
+/// Most accounts `getMultipleAccounts` returns per call.
+const MULTIPLE_ACCOUNTS_BATCH: usize = 100;
+/// Full refetch interval while subscribed, catching writes missed during reconnects.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// Refetch interval when no subscription could be opened.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Accounts shown for one wallet.
+#[derive(Debug, Clone, Copy)]
+struct WalletAccounts {
+    wallet: Pubkey,
+    wsol_ata: Pubkey,
+    token_ata: Option<Pubkey>,
+}
+
+/// Balances of one wallet, in lamports and token base units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalletBalance {
+    pub wallet: Pubkey,
+    pub sol: u64,
+    pub wsol: u64,
+    pub token: u64,
+    /// Lamports held as rent by the wallet's token accounts.
+    pub rent_locked: u64,
+}
+
+/// Balances of every wallet of a folder, kept current by [`run_dashboard`].
+pub struct Dashboard {
+    folder: String,
+    wallets: Vec<WalletAccounts>,
+    token_mint: Option<Pubkey>,
+    token_decimals: u8,
+    accounts: HashMap<Pubkey, Account>,
+}
+
+impl Dashboard {
+    pub fn new(folder: &str, wallets: &[Pubkey], token_mint: Option<Pubkey>) -> Self {
+        Self {
+            folder: folder.to_string(),
+            wallets: wallets
+                .iter()
+                .map(|wallet| WalletAccounts {
+                    wallet: *wallet,
+                    wsol_ata: get_associated_token_address(wallet, &SOLC_MINT),
+                    token_ata: token_mint.map(|mint| get_associated_token_address(wallet, &mint)),
+                })
+                .collect(),
+            token_mint,
+            token_decimals: 0,
+            accounts: HashMap::new(),
+        }
+    }
+
+    /// Every account the dashboard reads, wallets first.
+    pub fn pubkeys(&self) -> Vec<Pubkey> {
+        self.wallets
+            .iter()
+            .map(|accounts| accounts.wallet)
+            .chain(self.wallets.iter().map(|accounts| accounts.wsol_ata))
+            .chain(
+                self.wallets
+                    .iter()
+                    .filter_map(|accounts| accounts.token_ata),
+            )
+            .collect()
+    }
+
+    /// Refetches every account, and the token's decimals.
+    pub async fn refresh(&mut self, rpc_client: &RpcClient) -> eyre::Result<()> {
+        let mut pubkeys = self.pubkeys();
+        pubkeys.extend(self.token_mint);
+
+        let mut accounts = HashMap::new();
+        for batch in pubkeys.chunks(MULTIPLE_ACCOUNTS_BATCH) {
+            let fetched = rpc_client.get_multiple_accounts(batch).await?;
+            accounts.extend(
+                batch
+                    .iter()
+                    .zip(fetched)
+                    .filter_map(|(pubkey, account)| Some((*pubkey, account?))),
+            );
+        }
+
+        if let Some(mint) = self
+            .token_mint
+            .and_then(|mint| accounts.remove(&mint))
+            .and_then(|mint| Mint::unpack(&mint.data).ok())
+        {
+            self.token_decimals = mint.decimals;
+        }
+        self.accounts = accounts;
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, pubkey: Pubkey, account: Account) {
+        self.accounts.insert(pubkey, account);
+    }
+
+    /// Token amount and lamports of the token account at `pubkey`, zero when it does not exist.
+    fn token_account(&self, pubkey: &Pubkey) -> (u64, u64) {
+        match self.accounts.get(pubkey) {
+            Some(account) => (
+                TokenAccount::unpack(&account.data)
+                    .map(|token| token.amount)
+                    .unwrap_or_default(),
+                account.lamports,
+            ),
+            None => (0, 0),
+        }
+    }
+
+    pub fn balances(&self) -> Vec<WalletBalance> {
+        self.wallets
+            .iter()
+            .map(|accounts| {
+                let sol = self
+                    .accounts
+                    .get(&accounts.wallet)
+                    .map(|account| account.lamports)
+                    .unwrap_or_default();
+                let (wsol, wsol_lamports) = self.token_account(&accounts.wsol_ata);
+                let (token, token_lamports) = accounts
+                    .token_ata
+                    .map(|ata| self.token_account(&ata))
+                    .unwrap_or_default();
+
+                WalletBalance {
+                    wallet: accounts.wallet,
+                    sol,
+                    wsol,
+                    token,
+                    // The wrapped SOL itself is not rent
+                    rent_locked: wsol_lamports.saturating_sub(wsol) + token_lamports,
+                }
+            })
+            .collect()
+    }
+
+    fn ui_token(&self, amount: u64) -> f64 {
+        amount as f64 / 10f64.powi(self.token_decimals as i32)
+    }
+
+    /// Balances as CSV, one row per wallet followed by the totals, amounts in SOL and tokens.
+    pub fn to_csv(&self) -> String {
+        let balances = self.balances();
+        let total = totals(&balances);
+
+        let mut csv = String::from("wallet,sol,wsol,token,rent_locked\n");
+        for balance in balances
+            .iter()
+            .map(|balance| (balance.wallet.to_string(), balance))
+            .chain([("total".to_string(), &total)])
+        {
+            let (name, balance) = balance;
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{}",
+                name,
+                lamports_to_sol(balance.sol),
+                lamports_to_sol(balance.wsol),
+                self.ui_token(balance.token),
+                lamports_to_sol(balance.rent_locked)
+            );
+        }
+
+        csv
+    }
+
+    /// Clears the terminal and draws the balance table.
+    pub fn render(&self) -> eyre::Result<()> {
+        let balances = self.balances();
+        let total = totals(&balances);
+
+        let mut out = stdout();
+        execute!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+
+        writeln!(
+            out,
+            "{} - {} wallets{}\n",
+            self.folder,
+            balances.len(),
+            self.token_mint
+                .map(|mint| format!(", token {}", mint))
+                .unwrap_or_default()
+        )?;
+        writeln!(
+            out,
+            "{:<44} {:>14} {:>14} {:>18} {:>12}",
+            "Wallet", "SOL", "wSOL", "Token", "Rent"
+        )?;
+        for (name, balance) in balances
+            .iter()
+            .map(|balance| (balance.wallet.to_string(), balance))
+            .chain([("Total".to_string(), &total)])
+        {
+            writeln!(
+                out,
+                "{:<44} {:>14.6} {:>14.6} {:>18.2} {:>12.6}",
+                name,
+                balance.sol as f64 / LAMPORTS_PER_SOL as f64,
+                balance.wsol as f64 / LAMPORTS_PER_SOL as f64,
+                self.ui_token(balance.token),
+                balance.rent_locked as f64 / LAMPORTS_PER_SOL as f64
+            )?;
+        }
+        writeln!(out, "\nCtrl-C to exit")?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+fn totals(balances: &[WalletBalance]) -> WalletBalance {
+    balances
+        .iter()
+        .fold(WalletBalance::default(), |total, balance| WalletBalance {
+            wallet: total.wallet,
+            sol: total.sol + balance.sol,
+            wsol: total.wsol + balance.wsol,
+            token: total.token + balance.token,
+            rent_locked: total.rent_locked + balance.rent_locked,
+        })
+}
+
+fn export(dashboard: &Dashboard, csv: Option<&Path>) -> eyre::Result<()> {
+    if let Some(path) = csv {
+        fs::write(path, dashboard.to_csv())?;
+    }
+
+    Ok(())
+}
+
+/// Shows the balances of `wallets` until Ctrl-C, rewriting `csv` on every change.
+///
+/// With `once` the balances are fetched, shown and exported a single time.
+pub async fn run_dashboard(
+    settings: &PoolDataSettings,
+    folder: &str,
+    wallets: &[Pubkey],
+    csv: Option<PathBuf>,
+    once: bool,
+) -> eyre::Result<()> {
+    let manifest = LaunchManifest::load(folder)?;
+    let token_mint = manifest
+        .token_mint
+        .as_deref()
+        .into_iter()
+        .chain([settings.token_mint.as_str()])
+        .find(|mint| !mint.is_empty())
+        .map(Pubkey::from_str)
+        .transpose()?;
+
+    let watcher = AccountWatcher::from_settings(settings);
+    let mut dashboard = Dashboard::new(folder, wallets, token_mint);
+    dashboard.refresh(watcher.rpc_client()).await?;
+    dashboard.render()?;
+    export(&dashboard, csv.as_deref())?;
+    if once {
+        return Ok(());
+    }
+
+    let mut updates = match watcher.watch_accounts(&dashboard.pubkeys()).await {
+        Ok(updates) => Some(updates),
+        Err(e) => {
+            warn!("Polling balances, subscribing failed: {}", e);
+            None
+        }
+    };
+    let mut refresh = interval(match updates {
+        Some(_) => RESYNC_INTERVAL,
+        None => POLL_INTERVAL,
+    });
+    refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The first tick completes immediately
+    refresh.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            update = async { updates.as_mut()?.next().await }, if updates.is_some() => {
+                match update {
+                    Some((pubkey, account)) => dashboard.update(pubkey, account),
+                    None => {
+                        warn!("Account subscription closed, polling balances");
+                        updates = None;
+                        refresh = interval(POLL_INTERVAL);
+                        continue;
+                    }
+                }
+            }
+            _ = refresh.tick() => {
+                if let Err(e) = dashboard.refresh(watcher.rpc_client()).await {
+                    warn!("Refreshing balances failed: {}", e);
+                    continue;
+                }
+            }
+        }
+
+        dashboard.render()?;
+        export(&dashboard, csv.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// Dashboard of a wallet folder picked from the menu, exported to `balances.csv` in it.
+pub async fn dashboard_main() -> eyre::Result<()> {
+    let settings = load_minter_settings().await?;
+    let (folder, wallets) = match list_folders().await {
+        Ok(folders) => folders,
+        Err(e) => {
+            eprintln!("Error listing folders: {}", e);
+            return Ok(());
+        }
+    };
+
+    let wallets: Vec<Pubkey> = wallets.iter().map(|wallet| wallet.pubkey()).collect();
+    let csv = PathBuf::from(&folder).join("balances.csv");
+
+    run_dashboard(&settings, &folder, &wallets, Some(csv), false).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use jito_mock_block_engine::{JsonRpcReply, MockJsonRpc};
+    use serde_json::{json, Value};
+    use spl_token::state::AccountState;
+
+    use super::*;
+
+    const RENT: u64 = 2_039_280;
+
+    fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64, lamports: u64) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(
+            TokenAccount {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                state: AccountState::Initialized,
+                ..TokenAccount::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            lamports,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn system_account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            ..Account::default()
+        }
+    }
+
+    fn mint_account(decimals: u8) -> Account {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                decimals,
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            lamports: RENT,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn ui_account(account: &Account) -> Value {
+        json!({
+            "data": [STANDARD.encode(&account.data), "base64"],
+            "executable": account.executable,
+            "lamports": account.lamports,
+            "owner": account.owner.to_string(),
+            "rentEpoch": account.rent_epoch,
+            "space": account.data.len(),
+        })
+    }
+
+    #[test]
+    fn sums_balances_and_rent_of_wallet_accounts() {
+        let mint = Pubkey::new_unique();
+        let (funded, empty) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut dashboard = Dashboard::new("wallets", &[funded, empty], Some(mint));
+
+        dashboard.update(funded, system_account(3 * LAMPORTS_PER_SOL));
+        dashboard.update(
+            get_associated_token_address(&funded, &SOLC_MINT),
+            token_account(
+                &SOLC_MINT,
+                &funded,
+                LAMPORTS_PER_SOL,
+                LAMPORTS_PER_SOL + RENT,
+            ),
+        );
+        dashboard.update(
+            get_associated_token_address(&funded, &mint),
+            token_account(&mint, &funded, 500, RENT),
+        );
+        dashboard.update(empty, system_account(LAMPORTS_PER_SOL));
+
+        let balances = dashboard.balances();
+        assert_eq!(
+            balances,
+            [
+                WalletBalance {
+                    wallet: funded,
+                    sol: 3 * LAMPORTS_PER_SOL,
+                    wsol: LAMPORTS_PER_SOL,
+                    token: 500,
+                    rent_locked: 2 * RENT,
+                },
+                WalletBalance {
+                    wallet: empty,
+                    sol: LAMPORTS_PER_SOL,
+                    ..WalletBalance::default()
+                },
+            ]
+        );
+
+        let total = totals(&balances);
+        assert_eq!(total.sol, 4 * LAMPORTS_PER_SOL);
+        assert_eq!(total.rent_locked, 2 * RENT);
+        assert_eq!(
+            dashboard.to_csv().lines().last(),
+            Some(format!("total,4,1,500,{}", lamports_to_sol(2 * RENT)).as_str())
+        );
+    }
+
+    #[test]
+    fn reads_accounts_of_every_wallet() {
+        let mint = Pubkey::new_unique();
+        let wallets = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let pubkeys = Dashboard::new("wallets", &wallets, Some(mint)).pubkeys();
+        assert_eq!(pubkeys.len(), 6);
+        assert_eq!(pubkeys[..2], wallets);
+        assert!(pubkeys.contains(&get_associated_token_address(&wallets[1], &mint)));
+
+        // No token ATAs without a mint
+        assert_eq!(Dashboard::new("wallets", &wallets, None).pubkeys().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn refreshes_in_batches_of_multiple_accounts() {
+        let mint = Pubkey::new_unique();
+        let wallets: Vec<Pubkey> = (0..40).map(|_| Pubkey::new_unique()).collect();
+        let funded: HashSet<String> = wallets.iter().map(Pubkey::to_string).collect();
+
+        let rpc = MockJsonRpc::start().await.unwrap();
+        rpc.script(
+            "getVersion",
+            vec![JsonRpcReply::Result(
+                json!({ "solana-core": "1.17.20", "feature-set": 0 }),
+            )],
+        );
+        let mint_key = mint.to_string();
+        rpc.answer("getMultipleAccounts", move |params| {
+            let accounts: Vec<Value> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pubkey| match pubkey.as_str().unwrap() {
+                    pubkey if pubkey == mint_key => ui_account(&mint_account(6)),
+                    pubkey if funded.contains(pubkey) => {
+                        ui_account(&system_account(LAMPORTS_PER_SOL))
+                    }
+                    _ => Value::Null,
+                })
+                .collect();
+            JsonRpcReply::Result(json!({ "context": { "slot": 1 }, "value": accounts }))
+        });
+
+        let mut dashboard = Dashboard::new("wallets", &wallets, Some(mint));
+        dashboard.refresh(&RpcClient::new(rpc.url())).await.unwrap();
+
+        // Three accounts per wallet and the mint
+        let batches: Vec<usize> = rpc
+            .calls_of("getMultipleAccounts")
+            .iter()
+            .map(|call| call.params[0].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batches, [MULTIPLE_ACCOUNTS_BATCH, 21]);
+
+        assert_eq!(dashboard.token_decimals, 6);
+        assert!(!dashboard.accounts.contains_key(&mint));
+        assert!(dashboard
+            .balances()
+            .iter()
+            .all(|balance| balance.sol == LAMPORTS_PER_SOL && balance.rent_locked == 0));
+    }
+}
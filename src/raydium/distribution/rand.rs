@@ -1,17 +1,46 @@
 use rand::seq::SliceRandom;
 use rand::Rng;
+use solana_sdk::native_token::lamports_to_sol;
+
+/// Checks that `total` lamports can be split over `iterations` wallets, each getting
+/// between `min_value` and `max_value` lamports except the last one, which takes the rest.
+pub fn check_distribution(
+    total: u64,
+    iterations: usize,
+    min_value: u64,
+    max_value: u64,
+) -> eyre::Result<()> {
+    if iterations == 0 {
+        return Err(eyre::eyre!("No wallets to distribute to"));
+    }
+    if min_value > max_value {
+        return Err(eyre::eyre!(
+            "Minimum amount {} SOL is above the maximum {} SOL",
+            lamports_to_sol(min_value),
+            lamports_to_sol(max_value)
+        ));
+    }
+    // Every wallet gets at least a lamport
+    let needed = (iterations as u64).saturating_mul(min_value.max(1));
+    if total < needed {
+        return Err(eyre::eyre!(
+            "Total amount {} SOL cannot give {} wallets {} SOL each",
+            lamports_to_sol(total),
+            iterations,
+            lamports_to_sol(min_value)
+        ));
+    }
+
+    Ok(())
+}
 
 pub fn distribute_randomly(
     total: u64,
     iterations: usize,
     min_value: u64,
     max_value: u64,
-) -> Vec<u64> {
-    println!("Total: {}, Iterations: {}", total, iterations);
-    assert!(
-        total >= iterations as u64,
-        "Total must be greater than or equal to the number of iterations"
-    );
+) -> eyre::Result<Vec<u64>> {
+    check_distribution(total, iterations, min_value, max_value)?;
 
     let mut rng = rand::thread_rng();
     let mut amounts = vec![0; iterations];
@@ -44,5 +73,30 @@ pub fn distribute_randomly(
         }
     }
 
-    amounts
+    Ok(amounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_whole_total() {
+        let amounts = distribute_randomly(10_000, 7, 500, 2_000).unwrap();
+
+        assert_eq!(amounts.len(), 7);
+        assert_eq!(amounts.iter().sum::<u64>(), 10_000);
+    }
+
+    #[test]
+    fn rejects_minimum_above_maximum() {
+        assert!(distribute_randomly(10_000, 7, 2_000, 500).is_err());
+    }
+
+    #[test]
+    fn rejects_total_below_the_minimums() {
+        assert!(distribute_randomly(3_000, 7, 500, 2_000).is_err());
+        assert!(distribute_randomly(5, 7, 0, 2_000).is_err());
+        assert!(distribute_randomly(10_000, 0, 500, 2_000).is_err());
+    }
 }
@@ -1,13 +1,11 @@
 use std::sync::Arc;
 
 use bincode::serialize;
-use jito_protos::searcher::SubscribeBundleResultsRequest;
-use jito_searcher_client::{get_searcher_client, send_bundle_with_confirmation};
-use log::info;
+use log::{debug, info};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    instruction::Instruction, message::VersionedMessage, pubkey::Pubkey, signature::Keypair,
-    signer::Signer, system_instruction, transaction::VersionedTransaction,
+    hash::Hash, instruction::Instruction, message::VersionedMessage, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, system_instruction, transaction::VersionedTransaction,
 };
 use spl_associated_token_account::{
     get_associated_token_address, instruction::create_associated_token_account,
@@ -16,90 +14,108 @@ use spl_associated_token_account::{
 use crate::{
     env::{
         input::{bundle_priority_tip, sol_amount},
-        jito_auth::{auth_keypair, jito_tip_acc, jito_tip_inx},
         load_minter_settings, PoolDataSettings,
     },
     raydium::{
-        distribution::rand::distribute_randomly, instruction::instruction::SOL_MINT,
-        wallets::load_wallets,
+        distribution::rand::distribute_randomly,
+        fallback::{send_step, Fee},
+        instruction::instruction::SOL_MINT,
+        manifest::LaunchManifest,
+        wallets::list_folders,
     },
 };
 
+/// Transfers that fit in one transaction next to the tip.
+const TRANSFERS_PER_TX: usize = 21;
+/// Transfers that fit in one transaction next to the compute budget instructions.
+const PRIORITY_TRANSFERS_PER_TX: usize = 19;
+
 pub async fn sol_distribution(
     server_data: PoolDataSettings,
-    wallets: &&[Keypair],
-    total_amount: u64,
-    min_amount: u64,
-    max_amount: u64,
-    bundle_tip: u64,
-) -> eyre::Result<(Vec<u64>, Vec<VersionedTransaction>)> {
+    wallets: &[Keypair],
+    amounts: &[u64],
+    fee: Fee,
+) -> eyre::Result<Vec<VersionedTransaction>> {
+    fee.validate()?;
+
     let connection = RpcClient::new(server_data.rpc_url.clone());
 
-    let buyer_wallet = Arc::new(Keypair::from_base58_string(&server_data.buyer_key));
+    let buyer_wallet = server_data.buyer_key.keypair();
 
-    let rand_amount = distribute_randomly(total_amount, wallets.len(), min_amount, max_amount);
+    let recent_blockhash = connection.get_latest_blockhash().await?;
 
-    let wallet_chunks: Vec<_> = wallets.chunks(21).collect();
-    let mut bundle_txns = vec![];
+    let bundle_txns =
+        distribution_transactions(&buyer_wallet, wallets, amounts, &fee, recent_blockhash)?;
 
-    let recent_blockhash = connection.get_latest_blockhash().await?;
+    let sizes: Vec<usize> = bundle_txns
+        .iter()
+        .map(|tx| serialize(tx).map_or(0, |tx| tx.len()))
+        .collect();
+    debug!(
+        "{} distribution transactions of {:?} bytes",
+        bundle_txns.len(),
+        sizes
+    );
+
+    Ok(bundle_txns)
+}
+
+/// Transactions sending `amounts[i]` from `buyer_wallet` to `wallets[i]`, paying `fee`.
+pub fn distribution_transactions(
+    buyer_wallet: &Keypair,
+    wallets: &[Keypair],
+    amounts: &[u64],
+    fee: &Fee,
+    recent_blockhash: Hash,
+) -> eyre::Result<Vec<VersionedTransaction>> {
+    if amounts.len() < wallets.len() {
+        return Err(eyre::eyre!(
+            "{} amounts for {} wallets",
+            amounts.len(),
+            wallets.len()
+        ));
+    }
+
+    let per_tx = match fee.is_bundle() {
+        true => TRANSFERS_PER_TX,
+        false => PRIORITY_TRANSFERS_PER_TX,
+    };
+    let wallet_chunks: Vec<_> = wallets.chunks(per_tx).collect();
+    let mut bundle_txns = vec![];
 
     for (index, wallet_chunk) in wallet_chunks.iter().enumerate() {
-        let mut current_instructions = Vec::new();
+        let mut current_instructions = fee.compute_budget()?;
 
         for (i, wallet) in wallet_chunk.iter().enumerate() {
             let transfer_instruction = system_instruction::transfer(
                 &buyer_wallet.pubkey(),
                 &wallet.pubkey(),
-                rand_amount[index],
+                amounts[index * per_tx + i],
             );
 
             current_instructions.push(transfer_instruction);
 
             if index == wallet_chunks.len() - 1 && i == wallet_chunk.len() - 1 {
-                info!("Adding tip to last transaction");
-                let tip = jito_tip_inx(buyer_wallet.pubkey(), jito_tip_acc(), bundle_tip);
-                current_instructions.push(tip);
+                if let Some(tip) = fee.tip(buyer_wallet.pubkey()) {
+                    info!("Adding tip to last transaction");
+                    current_instructions.push(tip);
+                }
             }
         }
 
-        let versioned_msg = VersionedMessage::V0(
-            match solana_sdk::message::v0::Message::try_compile(
-                &buyer_wallet.pubkey(),
-                &current_instructions,
-                &[],
-                recent_blockhash,
-            ) {
-                Ok(message) => message,
-                Err(e) => {
-                    eprintln!("Error: {}", e);
-                    panic!("Error: {}", e);
-                }
-            },
-        );
+        let versioned_msg = VersionedMessage::V0(solana_sdk::message::v0::Message::try_compile(
+            &buyer_wallet.pubkey(),
+            &current_instructions,
+            &[],
+            recent_blockhash,
+        )?);
 
-        let transaction = VersionedTransaction::try_new(versioned_msg, &[&buyer_wallet])?;
+        let transaction = VersionedTransaction::try_new(versioned_msg, &[buyer_wallet])?;
 
         bundle_txns.push(transaction);
     }
 
-    let mut sum = 0;
-    let txn_size: Vec<_> = bundle_txns
-        .iter()
-        .map(|x| {
-            let serialized_x = serialize(x).unwrap();
-            //sum all of them
-            sum += serialized_x.len();
-            serialized_x.len()
-        })
-        .collect();
-
-    println!("Sum: {:?}", sum);
-    println!("txn_size: {:?}", txn_size);
-
-    println!("Generated transactions: {}", bundle_txns.len());
-
-    Ok((rand_amount, bundle_txns))
+    Ok(bundle_txns)
 }
 
 //server_data.BlockEngineSelections
@@ -140,66 +156,212 @@ pub async fn distributor() -> eyre::Result<()> {
         }
     };
 
-    let connection = Arc::new(RpcClient::new(data.rpc_url.clone()));
-
-    let mut client = get_searcher_client(&data.block_engine_url, &Arc::new(auth_keypair())).await?;
-
-    let mut bundle_results_subscription = client
-        .subscribe_bundle_results(SubscribeBundleResultsRequest {})
-        .await
-        .expect("subscribe to bundle results")
-        .into_inner();
-
-    let wallets: Vec<Keypair> = match load_wallets().await {
-        Ok(wallets) => wallets,
+    let (folder, wallets): (String, Vec<Keypair>) = match list_folders().await {
+        Ok(folders) => folders,
         Err(e) => {
             eprintln!("Error: {}", e);
             panic!("Error: {}", e);
         }
     };
+    let mut manifest = LaunchManifest::load(&folder)?;
+
     let total_amount = sol_amount("Total Amount:").await;
     let max_amount = sol_amount("Max Distribution Amount:").await;
 
     let min_amount = sol_amount("Min Distribution Amount:").await;
     let bundle_tip = bundle_priority_tip().await;
 
-    let wallet_chunks = wallets.chunks(104).collect::<Vec<_>>();
+    match distribute_sol(
+        data,
+        &wallets,
+        &mut manifest,
+        total_amount,
+        min_amount,
+        max_amount,
+        bundle_tip,
+    )
+    .await
+    {
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Distribution Error: {}", e);
+        }
+    };
 
-    for (_index, wallet_chunk) in wallet_chunks.iter().enumerate() {
-        let (_amounts, transactions_1) = match sol_distribution(
-            data.clone(),
-            wallet_chunk,
-            total_amount,
-            min_amount,
-            max_amount,
-            bundle_tip,
-        )
-        .await
-        {
-            Ok((amounts, transactions_1)) => (amounts, transactions_1),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                panic!("Error: {}", e);
-            }
-        };
+    Ok(())
+}
+
+/// Funds `wallets` from the buyer wallet with randomized amounts, one bundle per 104 wallets.
+///
+/// Each chunk is sent with [`send_step`], the amount a wallet received is recorded in
+/// `manifest` once its chunk landed.
+pub async fn distribute_sol(
+    data: PoolDataSettings,
+    wallets: &[Keypair],
+    manifest: &mut LaunchManifest,
+    total_amount: u64,
+    min_amount: u64,
+    max_amount: u64,
+    bundle_tip: u64,
+) -> eyre::Result<()> {
+    // Drawn once for all wallets so the total is split across every bundle
+    let amounts = distribute_randomly(total_amount, wallets.len(), min_amount, max_amount)?;
 
+    manifest.record_target(&data)?;
+
+    for (wallet_chunk, amounts) in wallets.chunks(104).zip(amounts.chunks(104)) {
         info!("Sending Bundle");
 
-        match send_bundle_with_confirmation(
-            &transactions_1,
-            &connection,
-            &mut client,
-            &mut bundle_results_subscription,
-        )
-        .await
-        {
-            Ok(_) => {}
-            Err(e) => {
-                eprintln!("Distribution Error: {}", e);
-                return Ok(());
+        let data = &data;
+        let result = send_step(data, "distribute", bundle_tip, manifest, |fee| {
+            sol_distribution(data.clone(), wallet_chunk, amounts, fee)
+        })
+        .await;
+
+        if result.is_ok() {
+            for (wallet, amount) in wallet_chunk.iter().zip(amounts) {
+                *manifest
+                    .funding
+                    .entry(wallet.pubkey().to_string())
+                    .or_default() += amount;
             }
-        };
+            manifest.save()?;
+        }
+
+        result?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_sdk::{native_token::sol_to_lamports, system_instruction::SystemInstruction};
+
+    use super::*;
+    use crate::{
+        env::jito_auth::{generate_tip_accounts, tip_program_id},
+        raydium::testing::{MockCluster, LANDED_SLOT},
+    };
+
+    /// Lamports each transfer of `transactions` sends, keyed by recipient.
+    fn transfers(transactions: &[VersionedTransaction]) -> Vec<HashMap<Pubkey, u64>> {
+        transactions
+            .iter()
+            .map(|transaction| {
+                let keys = transaction.message.static_account_keys();
+                transaction
+                    .message
+                    .instructions()
+                    .iter()
+                    .filter_map(|ix| match bincode::deserialize(&ix.data) {
+                        Ok(SystemInstruction::Transfer { lamports }) => {
+                            Some((keys[ix.accounts[1] as usize], lamports))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sends_each_wallet_its_own_amount() {
+        let buyer = Keypair::new();
+        let wallets: Vec<Keypair> = (0..45).map(|_| Keypair::new()).collect();
+        let amounts: Vec<u64> = (0..45).map(|i| 1_000_000 + i).collect();
+        let tip_account = Pubkey::new_unique();
+        let fee = Fee::JitoTip {
+            account: tip_account,
+            lamports: 5_000,
+        };
+
+        let transactions =
+            distribution_transactions(&buyer, &wallets, &amounts, &fee, Hash::default()).unwrap();
+
+        let transfers = transfers(&transactions);
+        assert_eq!(
+            transfers.iter().map(HashMap::len).collect::<Vec<_>>(),
+            [21, 21, 4]
+        );
+        for (i, wallet) in wallets.iter().enumerate() {
+            assert_eq!(
+                transfers[i / TRANSFERS_PER_TX].get(&wallet.pubkey()),
+                Some(&amounts[i]),
+                "wallet {}",
+                i
+            );
+        }
+        assert_eq!(transfers[2].get(&tip_account), Some(&5_000));
+    }
+
+    #[test]
+    fn rejects_missing_amounts() {
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let fee = Fee::JitoTip {
+            account: Pubkey::new_unique(),
+            lamports: 5_000,
+        };
+
+        assert!(distribution_transactions(
+            &Keypair::new(),
+            &wallets,
+            &[1, 2],
+            &fee,
+            Hash::default()
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn distributes_through_block_engine() {
+        let cluster = MockCluster::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = LaunchManifest::load(dir.path().to_str().unwrap()).unwrap();
+        let wallets: Vec<Keypair> = (0..25).map(|_| Keypair::new()).collect();
+
+        distribute_sol(
+            cluster.settings(),
+            &wallets,
+            &mut manifest,
+            sol_to_lamports(25.0),
+            sol_to_lamports(0.5),
+            sol_to_lamports(1.5),
+            10_000,
+        )
+        .await
+        .unwrap();
+
+        let bundles = cluster.bundles();
+        assert_eq!(bundles.len(), 1);
+        let transfers = transfers(&bundles[0]);
+        assert_eq!(
+            transfers.iter().map(HashMap::len).collect::<Vec<_>>(),
+            [21, 5]
+        );
+        for (i, wallet) in wallets.iter().enumerate() {
+            let key = wallet.pubkey();
+            assert_eq!(
+                transfers[i / TRANSFERS_PER_TX].get(&key).copied(),
+                manifest.funding.get(&key.to_string()).copied(),
+                "wallet {}",
+                i
+            );
+        }
+        assert_eq!(
+            manifest.funding.values().sum::<u64>(),
+            sol_to_lamports(25.0)
+        );
+        let tip_accounts = generate_tip_accounts(&tip_program_id());
+        assert!(transfers[1]
+            .iter()
+            .any(|(to, lamports)| tip_accounts.contains(to) && *lamports == 10_000));
+
+        assert_eq!(manifest.bundles.len(), 1);
+        assert_eq!(manifest.bundles[0].step, "distribute");
+        assert!(manifest.bundles[0].landed);
+        assert_eq!(manifest.bundles[0].slot, Some(LANDED_SLOT));
+    }
+}
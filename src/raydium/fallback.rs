@@ -0,0 +1,544 @@
+//! Plain RPC delivery for steps that do not need to land atomically.
+//!
+//! SOL distribution, wrapping and LUT extension go out as Jito bundles first. When the
+//! block engine cannot be reached or keeps refusing them, the step is rebuilt with
+//! priority fees instead of a tip and its transactions are sent one by one with
+//! `sendTransaction`. The launch bundle never comes through here.
+
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jito_protos::bundle::DroppedReason;
+use jito_searcher_client::{
+    bundle_sender::BundleSendError,
+    retry::{send_bundle_with_retry, BundleAttempt},
+    BundleError, BundleRejectionError,
+};
+use log::{info, warn};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_program::program_error::ProgramError;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, pubkey::Pubkey,
+    signature::Signature, transaction::VersionedTransaction,
+};
+use tokio::time::sleep;
+
+use crate::{
+    env::{
+        jito_auth::{bundle_sender, jito_tip_acc, jito_tip_inx, validate_tip_account},
+        PoolDataSettings,
+    },
+    raydium::{instruction::instruction::compute_ixs, manifest::LaunchManifest},
+};
+
+/// How long to wait for an RPC transaction to confirm.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+/// Interval between status checks, the transaction is resent at each one.
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Compute unit price and limit paid by every transaction sent over RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityFee {
+    /// Price per compute unit, in micro-lamports.
+    pub micro_lamports: u64,
+    pub compute_unit_limit: u32,
+}
+
+/// How the transactions of a step pay for inclusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fee {
+    /// A tip to a Jito tip account, paid by the last transaction of the bundle.
+    JitoTip { account: Pubkey, lamports: u64 },
+    /// Compute budget instructions at the front of every transaction.
+    Priority(PriorityFee),
+}
+
+impl Fee {
+    /// Fails when a Jito tip would go to an unknown account.
+    pub fn validate(&self) -> eyre::Result<()> {
+        match self {
+            Fee::JitoTip { account, .. } => validate_tip_account(account),
+            Fee::Priority(_) => Ok(()),
+        }
+    }
+
+    /// Instructions that go first in every transaction.
+    pub fn compute_budget(&self) -> Result<Vec<Instruction>, ProgramError> {
+        match self {
+            Fee::JitoTip { .. } => Ok(Vec::new()),
+            Fee::Priority(fee) => compute_ixs(fee.micro_lamports, fee.compute_unit_limit),
+        }
+    }
+
+    /// Instruction paying the tip from `payer`, for the last transaction.
+    pub fn tip(&self, payer: Pubkey) -> Option<Instruction> {
+        match self {
+            Fee::JitoTip { account, lamports } => Some(jito_tip_inx(payer, *account, *lamports)),
+            Fee::Priority(_) => None,
+        }
+    }
+
+    pub fn is_bundle(&self) -> bool {
+        matches!(self, Fee::JitoTip { .. })
+    }
+}
+
+/// A transaction sent over RPC by one of the modes.
+#[derive(Debug, Clone)]
+pub struct RpcTransaction {
+    pub signature: Signature,
+    /// Slot the transaction was confirmed in, `None` when it failed.
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Sends a step as a bundle, falling back to RPC transactions with a priority fee.
+///
+/// `build` is called with the fee the transactions must pay. The fallback only runs when
+/// `RPC-FALLBACK-PRIORITY-FEE` is set and the bundle provably did not land: the block
+/// engine was unreachable, refused the bundle or dropped it once its blockhash expired.
+/// Bundles that failed simulation, timed out or partly landed are not resent. A step one
+/// of whose earlier bundles turns up on chain in full counts as sent.
+///
+/// Every bundle attempt and RPC transaction is recorded in `manifest` under `step`.
+pub async fn send_step<F, Fut>(
+    settings: &PoolDataSettings,
+    step: &str,
+    base_tip: u64,
+    manifest: &mut LaunchManifest,
+    mut build: F,
+) -> eyre::Result<()>
+where
+    F: FnMut(Fee) -> Fut,
+    Fut: Future<Output = eyre::Result<Vec<VersionedTransaction>>>,
+{
+    let rpc_client = Arc::new(RpcClient::new(settings.rpc_url.clone()));
+
+    let (reason, attempts) = match bundle_sender(settings).await {
+        Ok(mut sender) => {
+            let tip_account = jito_tip_acc(sender.as_mut()).await;
+            let outcome = send_bundle_with_retry(
+                &settings.retry,
+                base_tip,
+                |lamports| {
+                    build(Fee::JitoTip {
+                        account: tip_account,
+                        lamports,
+                    })
+                },
+                &rpc_client,
+                sender.as_mut(),
+            )
+            .await;
+
+            manifest.record_attempts(step, &outcome.attempts);
+            manifest.save()?;
+
+            match outcome.result {
+                Ok(_) => return Ok(()),
+                Err(e)
+                    if settings.rpc_fallback.is_some()
+                        && never_landed(e.as_ref(), &outcome.attempts) =>
+                {
+                    (e.to_string(), outcome.attempts)
+                }
+                Err(e) => return Err(eyre::eyre!("{}", e)),
+            }
+        }
+        Err(e) if settings.rpc_fallback.is_some() => (e.to_string(), Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let Some(priority_fee) = settings.rpc_fallback else {
+        unreachable!("only falls back with a priority fee configured")
+    };
+
+    if earlier_attempt_landed(&rpc_client, &attempts).await? {
+        info!(
+            "An earlier {} bundle landed after all, not resending over RPC",
+            step
+        );
+        return Ok(());
+    }
+
+    warn!(
+        "Bundle for {} failed ({}), sending over RPC with a priority fee of {} micro-lamports per CU",
+        step, reason, priority_fee.micro_lamports
+    );
+    let transactions = build(Fee::Priority(priority_fee)).await?;
+    let sent = send_transactions(&rpc_client, &transactions).await;
+
+    manifest.record_transactions(step, priority_fee.micro_lamports, &sent);
+    manifest.save()?;
+
+    match sent.iter().find_map(|tx| tx.error.as_ref()) {
+        Some(error) => Err(eyre::eyre!("{} over RPC failed: {}", step, error)),
+        None if sent.len() < transactions.len() => {
+            Err(eyre::eyre!("{} over RPC stopped early", step))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Whether the bundle failed in a way that rules out it landing later.
+fn never_landed(error: &(dyn std::error::Error + 'static), attempts: &[BundleAttempt]) -> bool {
+    if let Some(error) = error.downcast_ref::<BundleError>() {
+        return match error {
+            // The transactions themselves fail, RPC would not do better
+            BundleError::Rejected(BundleRejectionError::SimulationFailure { .. }) => false,
+            // The engine may have forwarded the bundle before failing
+            BundleError::Rejected(BundleRejectionError::InternalError { .. }) => false,
+            BundleError::Rejected(_) => true,
+            BundleError::Dropped(DroppedReason::BlockhashExpired) => true,
+            // Part of the bundle is on chain, or may come back on another fork
+            BundleError::Dropped(
+                DroppedReason::PartiallyProcessed | DroppedReason::NotFinalized,
+            ) => false,
+            BundleError::TimedOut => false,
+        };
+    }
+
+    // Only a send that never reached the block engine, not a failed status check
+    error.downcast_ref::<BundleSendError>().is_some()
+        && attempts
+            .last()
+            .is_some_and(|attempt| attempt.uuid.is_none())
+}
+
+/// Whether every transaction of one of `attempts` is on chain.
+///
+/// An attempt that timed out before the last one failed may still have landed since.
+/// Fails when only part of an attempt is on chain, resending the whole step would
+/// repeat what landed.
+async fn earlier_attempt_landed(
+    rpc_client: &RpcClient,
+    attempts: &[BundleAttempt],
+) -> eyre::Result<bool> {
+    for attempt in attempts
+        .iter()
+        .filter(|attempt| !attempt.signatures.is_empty())
+    {
+        let statuses = rpc_client
+            .get_signature_statuses(&attempt.signatures)
+            .await?
+            .value;
+        let landed = attempt
+            .signatures
+            .iter()
+            .zip(&statuses)
+            .filter(|(_, status)| status.as_ref().is_some_and(|status| status.err.is_none()))
+            .count();
+
+        if landed == attempt.signatures.len() {
+            return Ok(true);
+        }
+        if landed > 0 {
+            return Err(eyre::eyre!(
+                "{} of {} transactions of bundle attempt {} are on chain, not resending",
+                landed,
+                attempt.signatures.len(),
+                attempt.attempt
+            ));
+        }
+    }
+
+    Ok(false)
+}
+
+/// Sends `transactions` in order, each once the previous one is confirmed.
+///
+/// Stops at the first failure, later transactions may depend on it.
+pub async fn send_transactions(
+    rpc_client: &RpcClient,
+    transactions: &[VersionedTransaction],
+) -> Vec<RpcTransaction> {
+    let mut sent = Vec::with_capacity(transactions.len());
+
+    for transaction in transactions {
+        let result = send_and_confirm(rpc_client, transaction).await;
+        let failed = result.is_err();
+
+        sent.push(RpcTransaction {
+            signature: transaction.signatures[0],
+            slot: result.as_ref().ok().copied(),
+            error: result.err().map(|e| e.to_string()),
+        });
+        if failed {
+            break;
+        }
+    }
+
+    sent
+}
+
+/// Sends `transaction` and resends it until it is confirmed, returning its slot.
+async fn send_and_confirm(
+    rpc_client: &RpcClient,
+    transaction: &VersionedTransaction,
+) -> eyre::Result<u64> {
+    let signature = rpc_client
+        .send_transaction_with_config(transaction, RpcSendTransactionConfig::default())
+        .await?;
+    info!("Sent transaction {} over RPC", signature);
+
+    let deadline = Instant::now() + CONFIRM_TIMEOUT;
+    while Instant::now() < deadline {
+        sleep(CONFIRM_POLL_INTERVAL).await;
+
+        let status = rpc_client.get_signature_statuses(&[signature]).await?.value[0].clone();
+        match status {
+            Some(status) if status.err.is_some() => {
+                return Err(eyre::eyre!(
+                    "transaction {} failed: {}",
+                    signature,
+                    status.err.unwrap()
+                ));
+            }
+            Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                info!(
+                    "Transaction {} confirmed in slot {}",
+                    signature, status.slot
+                );
+                return Ok(status.slot);
+            }
+            _ => {
+                // Preflight already passed, resending only guards against dropped packets
+                let _ = rpc_client
+                    .send_transaction_with_config(
+                        transaction,
+                        RpcSendTransactionConfig {
+                            skip_preflight: true,
+                            ..RpcSendTransactionConfig::default()
+                        },
+                    )
+                    .await;
+            }
+        }
+    }
+
+    Err(eyre::eyre!(
+        "transaction {} not confirmed after {:?}",
+        signature,
+        CONFIRM_TIMEOUT
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use jito_mock_block_engine::ScriptedResult;
+    use jito_protos::bundle::{rejected::Reason, StateAuctionBidRejected};
+    use jito_searcher_client::retry::RetryPolicy;
+    use solana_sdk::{
+        hash::Hash,
+        message::{v0, VersionedMessage},
+        signature::Keypair,
+        signer::Signer,
+        system_instruction,
+    };
+
+    use super::*;
+    use crate::raydium::{manifest::LaunchManifest, testing::MockCluster};
+
+    const PRIORITY_FEE: PriorityFee = PriorityFee {
+        micro_lamports: 1_000,
+        compute_unit_limit: 200_000,
+    };
+
+    fn attempt(uuid: Option<&str>) -> BundleAttempt {
+        BundleAttempt {
+            attempt: 1,
+            tip: 1_000,
+            uuid: uuid.map(str::to_string),
+            signatures: vec![Signature::new_unique()],
+            landed: false,
+            expected_slot: None,
+            slot: None,
+            transactions: Vec::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn falls_back_only_when_bundle_cannot_land() {
+        let sent = [attempt(Some("uuid"))];
+        let lost_auction = BundleError::Rejected(BundleRejectionError::StateAuctionBidRejected {
+            auction_id: "auction".to_string(),
+            simulated_bid_lamports: 1_000,
+            msg: None,
+        });
+        let simulation = BundleError::Rejected(BundleRejectionError::SimulationFailure {
+            tx_signature: "signature".to_string(),
+            msg: None,
+        });
+        let internal = BundleError::Rejected(BundleRejectionError::InternalError {
+            msg: "engine restarted".to_string(),
+        });
+
+        assert!(never_landed(&lost_auction, &sent));
+        assert!(never_landed(
+            &BundleError::Dropped(DroppedReason::BlockhashExpired),
+            &sent
+        ));
+        assert!(!never_landed(&simulation, &sent));
+        assert!(!never_landed(&internal, &sent));
+        assert!(!never_landed(&BundleError::TimedOut, &sent));
+        assert!(!never_landed(
+            &BundleError::Dropped(DroppedReason::PartiallyProcessed),
+            &sent
+        ));
+        assert!(!never_landed(
+            &BundleError::Dropped(DroppedReason::NotFinalized),
+            &sent
+        ));
+
+        // A send that failed before the engine assigned a UUID never reached it
+        assert!(never_landed(
+            &BundleSendError::NoBlockEngine,
+            &[attempt(None)]
+        ));
+        assert!(!never_landed(&BundleSendError::NoBlockEngine, &sent));
+    }
+
+    /// `count` transfers from `payer` paying `fee`, the last one tipping.
+    fn transfers(
+        payer: &Keypair,
+        fee: Fee,
+        count: usize,
+    ) -> eyre::Result<Vec<VersionedTransaction>> {
+        let blockhash = Hash::new_unique();
+        (0..count)
+            .map(|i| {
+                let mut instructions = fee.compute_budget()?;
+                instructions.push(system_instruction::transfer(
+                    &payer.pubkey(),
+                    &Pubkey::new_unique(),
+                    1_000,
+                ));
+                if i == count - 1 {
+                    instructions.extend(fee.tip(payer.pubkey()));
+                }
+                let message =
+                    v0::Message::try_compile(&payer.pubkey(), &instructions, &[], blockhash)?;
+                Ok(VersionedTransaction::try_new(
+                    VersionedMessage::V0(message),
+                    &[payer],
+                )?)
+            })
+            .collect()
+    }
+
+    /// A cluster whose block engine refuses the first bundle, and settings sending once.
+    async fn refusing_cluster() -> (MockCluster, PoolDataSettings) {
+        let cluster = MockCluster::start().await;
+        cluster.engine.push_script(vec![ScriptedResult::rejected(
+            Reason::StateAuctionBidRejected(StateAuctionBidRejected {
+                auction_id: "auction".to_string(),
+                simulated_bid_lamports: 1_000,
+                msg: None,
+            }),
+        )]);
+
+        let mut settings = cluster.settings();
+        settings.retry = RetryPolicy::once();
+        settings.rpc_fallback = Some(PRIORITY_FEE);
+        (cluster, settings)
+    }
+
+    fn manifest() -> (tempfile::TempDir, LaunchManifest) {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = LaunchManifest::load(dir.path().to_str().unwrap()).unwrap();
+        (dir, manifest)
+    }
+
+    #[tokio::test]
+    async fn sends_refused_bundle_over_rpc() {
+        let (cluster, settings) = refusing_cluster().await;
+        cluster.land_rpc_transactions(None);
+        let (_dir, mut manifest) = manifest();
+        let payer = Keypair::new();
+
+        send_step(&settings, "wrap", 1_000, &mut manifest, |fee| {
+            let transactions = transfers(&payer, fee, 3);
+            async { transactions }
+        })
+        .await
+        .unwrap();
+
+        let sent = cluster.rpc.calls_of("sendTransaction");
+        assert_eq!(sent.len(), 3);
+        assert_eq!(manifest.transactions.len(), 3);
+        assert!(manifest
+            .transactions
+            .iter()
+            .all(|tx| tx.slot.is_some() && tx.priority_fee == PRIORITY_FEE.micro_lamports));
+    }
+
+    #[tokio::test]
+    async fn stops_rpc_fallback_at_first_failure() {
+        let (cluster, settings) = refusing_cluster().await;
+        cluster.land_rpc_transactions(Some(1));
+        let (_dir, mut manifest) = manifest();
+        let payer = Keypair::new();
+
+        let result = send_step(&settings, "wrap", 1_000, &mut manifest, |fee| {
+            let transactions = transfers(&payer, fee, 3);
+            async { transactions }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(cluster.rpc.calls_of("sendTransaction").len(), 2);
+        assert_eq!(manifest.transactions.len(), 2);
+        assert!(manifest.transactions[0].error.is_none());
+        assert!(manifest.transactions[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn keeps_bundle_found_on_chain_before_fallback() {
+        let (cluster, settings) = refusing_cluster().await;
+        cluster.set_landed(|_| true);
+        let (_dir, mut manifest) = manifest();
+        let payer = Keypair::new();
+
+        send_step(&settings, "wrap", 1_000, &mut manifest, |fee| {
+            let transactions = transfers(&payer, fee, 2);
+            async { transactions }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(cluster.bundles().len(), 1);
+        assert!(cluster.rpc.calls_of("sendTransaction").is_empty());
+        assert!(manifest.transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_fallback_when_bundle_partly_landed() {
+        let (cluster, settings) = refusing_cluster().await;
+        let first = Arc::new(Mutex::new(None));
+        let landed = first.clone();
+        cluster.set_landed(move |signature| *landed.lock().unwrap() == Some(*signature));
+        let (_dir, mut manifest) = manifest();
+        let payer = Keypair::new();
+
+        let result = send_step(&settings, "wrap", 1_000, &mut manifest, |fee| {
+            let transactions = transfers(&payer, fee, 2);
+            if let Ok(transactions) = &transactions {
+                first
+                    .lock()
+                    .unwrap()
+                    .get_or_insert(transactions[0].signatures[0]);
+            }
+            async { transactions }
+        })
+        .await;
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("1 of 2 transactions"), "{}", error);
+        assert!(cluster.rpc.calls_of("sendTransaction").is_empty());
+    }
+}
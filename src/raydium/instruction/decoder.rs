@@ -68,6 +68,7 @@ pub struct LIQUIDITY_STATE_LAYOUT_V4 {
 }
 
 impl LIQUIDITY_STATE_LAYOUT_V4 {
+    #[allow(clippy::field_reassign_with_default)]
     pub fn decode(input: &mut &[u8]) -> eyre::Result<Self> {
         let mut s = Self::default();
         s.status = Self::unpack_u64(input)?;
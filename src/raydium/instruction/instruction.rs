@@ -19,6 +19,8 @@ use solana_sdk::{
 use std::convert::TryInto;
 use std::mem::size_of;
 
+use crate::raydium::account_watcher::AccountWatcher;
+
 use super::{
     decoder::{program_address, LIQUIDITY_STATE_LAYOUT_V4, SOLC_MINT},
     error::AmmError,
@@ -284,7 +286,7 @@ pub enum AmmInstruction {
     ///   15. `[singer]` Admin Account
     ///   16. `[]` (optional) New AMM open orders Account to replace old AMM open orders Account
     // SetParams(SetParamsInstruction),
-
+    ///
     ///   Withdraw Pnl from pool by protocol
     ///
     ///   0. `[]` Spl Token program id
@@ -476,16 +478,16 @@ impl AmmInstruction {
                         })
                     }
                     _ => {
-                        return Err(ProgramError::InvalidInstructionData.into());
+                        return Err(ProgramError::InvalidInstructionData);
                     }
                 }
             }
-            _ => return Err(ProgramError::InvalidInstructionData.into()),
+            _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
 
     fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
-        if input.len() >= 1 {
+        if !input.is_empty() {
             let (amount, rest) = input.split_at(1);
             let amount = amount
                 .get(..1)
@@ -494,7 +496,7 @@ impl AmmInstruction {
                 .ok_or(ProgramError::InvalidInstructionData)?;
             Ok((amount, rest))
         } else {
-            Err(ProgramError::InvalidInstructionData.into())
+            Err(ProgramError::InvalidInstructionData)
         }
     }
 
@@ -508,7 +510,7 @@ impl AmmInstruction {
                 .ok_or(ProgramError::InvalidInstructionData)?;
             Ok((amount, rest))
         } else {
-            Err(ProgramError::InvalidInstructionData.into())
+            Err(ProgramError::InvalidInstructionData)
         }
     }
 
@@ -522,14 +524,14 @@ impl AmmInstruction {
                 .ok_or(ProgramError::InvalidInstructionData)?;
             Ok((amount, rest))
         } else {
-            Err(ProgramError::InvalidInstructionData.into())
+            Err(ProgramError::InvalidInstructionData)
         }
     }
 
     /// Packs a [AmmInstruction](enum.AmmInstruction.html) into a byte buffer.
     pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
+        match self {
             Self::Initialize(InitializeInstruction { nonce, open_time }) => {
                 buf.push(0);
                 buf.push(*nonce);
@@ -622,23 +624,23 @@ impl AmmInstruction {
                         let owner = match owner {
                             Some(owner) => {
                                 if *owner == Pubkey::default() {
-                                    return Err(ProgramError::InvalidInstructionData.into());
+                                    return Err(ProgramError::InvalidInstructionData);
                                 } else {
                                     owner
                                 }
                             }
-                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                            None => return Err(ProgramError::InvalidInstructionData),
                         };
                         buf.extend_from_slice(&owner.to_bytes());
                     }
                     2 => {
                         let create_pool_fee = match create_pool_fee {
                             Some(create_pool_fee) => create_pool_fee,
-                            None => return Err(ProgramError::InvalidInstructionData.into()),
+                            None => return Err(ProgramError::InvalidInstructionData),
                         };
                         buf.extend_from_slice(&create_pool_fee.to_le_bytes());
                     }
-                    _ => return Err(ProgramError::InvalidInstructionData.into()),
+                    _ => return Err(ProgramError::InvalidInstructionData),
                 }
             }
         }
@@ -658,9 +660,9 @@ pub fn initialize_amm_pool(
     pc_amount: u64,   // transfer pc asset to the pool pc vault as pool init vault
     coin_amount: u64, // transfer coin asset to the pool coin vault as pool init vault
 ) -> eyre::Result<Instruction> {
-    println!("Coin: {}\nPC: {}\nLP: {}", user_coin, user_pc, user_lp);
+    log::debug!("Coin: {}\nPC: {}\nLP: {}", user_coin, user_pc, user_lp);
     let amm_pool_init_instruction = initialize2(
-        &amm_program,
+        amm_program,
         &amm_keys.amm_pool,
         &amm_keys.amm_authority,
         &amm_keys.amm_open_order,
@@ -670,14 +672,14 @@ pub fn initialize_amm_pool(
         &amm_keys.amm_coin_vault,
         &amm_keys.amm_pc_vault,
         &amm_keys.amm_target,
-        &Pubkey::find_program_address(&[&AMM_CONFIG_SEED], &amm_program).0,
+        &Pubkey::find_program_address(&[AMM_CONFIG_SEED], amm_program).0,
         create_fee_detination,
         &amm_keys.market_program,
         &amm_keys.market,
-        &user_owner,
-        &user_coin,
-        &user_pc,
-        &user_lp,
+        user_owner,
+        user_coin,
+        user_pc,
+        user_lp,
         amm_keys.nonce,
         open_time,
         pc_amount,
@@ -1369,47 +1371,43 @@ pub fn get_amm_pda_keys(
     coin_mint: &Pubkey,
     pc_mint: &Pubkey,
 ) -> AmmKeys {
-    let amm_pool = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
-        AMM_ASSOCIATED_SEED,
-        &amm_program,
-    )
-    .0;
-    let (amm_authority, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], &amm_program);
+    let amm_pool =
+        get_associated_address_and_bump_seed(amm_program, market, AMM_ASSOCIATED_SEED, amm_program)
+            .0;
+    let (amm_authority, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], amm_program);
     let amm_open_order = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
+        amm_program,
+        market,
         OPEN_ORDER_ASSOCIATED_SEED,
-        &amm_program,
+        amm_program,
     )
     .0;
     let amm_lp_mint = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
+        amm_program,
+        market,
         LP_MINT_ASSOCIATED_SEED,
-        &amm_program,
+        amm_program,
     )
     .0;
     let amm_coin_vault = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
+        amm_program,
+        market,
         COIN_VAULT_ASSOCIATED_SEED,
-        &amm_program,
+        amm_program,
     )
     .0;
     let amm_pc_vault = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
+        amm_program,
+        market,
         PC_VAULT_ASSOCIATED_SEED,
-        &amm_program,
+        amm_program,
     )
     .0;
     let amm_target = get_associated_address_and_bump_seed(
-        &amm_program,
-        &market,
+        amm_program,
+        market,
         TARGET_ASSOCIATED_SEED,
-        &amm_program,
+        amm_program,
     )
     .0;
 
@@ -1429,7 +1427,7 @@ pub fn get_amm_pda_keys(
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct AmmKeys {
     pub amm_pool: Pubkey,
     pub amm_coin_mint: Pubkey,
@@ -1446,21 +1444,21 @@ pub struct AmmKeys {
 }
 
 /// Suffix for amm authority seed
-pub const AUTHORITY_AMM: &'static [u8] = b"amm authority";
+pub const AUTHORITY_AMM: &[u8] = b"amm authority";
 /// Suffix for amm associated seed
-pub const AMM_ASSOCIATED_SEED: &'static [u8] = b"amm_associated_seed";
+pub const AMM_ASSOCIATED_SEED: &[u8] = b"amm_associated_seed";
 /// Suffix for target associated seed
-pub const TARGET_ASSOCIATED_SEED: &'static [u8] = b"target_associated_seed";
+pub const TARGET_ASSOCIATED_SEED: &[u8] = b"target_associated_seed";
 /// Suffix for amm open order associated seed
-pub const OPEN_ORDER_ASSOCIATED_SEED: &'static [u8] = b"open_order_associated_seed";
+pub const OPEN_ORDER_ASSOCIATED_SEED: &[u8] = b"open_order_associated_seed";
 /// Suffix for coin vault associated seed
-pub const COIN_VAULT_ASSOCIATED_SEED: &'static [u8] = b"coin_vault_associated_seed";
+pub const COIN_VAULT_ASSOCIATED_SEED: &[u8] = b"coin_vault_associated_seed";
 /// Suffix for pc vault associated seed
-pub const PC_VAULT_ASSOCIATED_SEED: &'static [u8] = b"pc_vault_associated_seed";
+pub const PC_VAULT_ASSOCIATED_SEED: &[u8] = b"pc_vault_associated_seed";
 /// Suffix for lp mint associated seed
-pub const LP_MINT_ASSOCIATED_SEED: &'static [u8] = b"lp_mint_associated_seed";
+pub const LP_MINT_ASSOCIATED_SEED: &[u8] = b"lp_mint_associated_seed";
 /// Amm config seed
-pub const AMM_CONFIG_SEED: &'static [u8] = b"amm_config_account_seed";
+pub const AMM_CONFIG_SEED: &[u8] = b"amm_config_account_seed";
 
 pub fn get_associated_address_and_bump_seed(
     info_id: &Pubkey,
@@ -1472,7 +1470,7 @@ pub fn get_associated_address_and_bump_seed(
         &[
             &info_id.to_bytes(),
             &market_address.to_bytes(),
-            &associated_seed,
+            associated_seed,
         ],
         program_id,
     )
@@ -1507,10 +1505,9 @@ pub fn swap(
     other_amount_threshold: u64,
     out: bool,
 ) -> eyre::Result<Instruction> {
-    let swap_instruction;
-    if out {
-        swap_instruction = swap_base_in(
-            &amm_program,
+    let swap_instruction = if out {
+        swap_base_in(
+            amm_program,
             &amm_keys.amm_pool,
             &amm_keys.amm_authority,
             &amm_keys.amm_open_order,
@@ -1530,10 +1527,10 @@ pub fn swap(
             user_owner,
             amount_specified,
             other_amount_threshold,
-        )?;
+        )?
     } else {
-        swap_instruction = swap_base_in(
-            &amm_program,
+        swap_base_in(
+            amm_program,
             &amm_keys.amm_pool,
             &amm_keys.amm_authority,
             &amm_keys.amm_open_order,
@@ -1553,8 +1550,8 @@ pub fn swap(
             user_owner,
             amount_specified,
             other_amount_threshold,
-        )?;
-    }
+        )?
+    };
     Ok(swap_instruction)
 }
 
@@ -1569,7 +1566,7 @@ pub async fn get_keys_for_market<'a>(
     use safe_transmute::{transmute_one_pedantic, transmute_one_to_bytes, transmute_to_bytes};
     use serum_dex::state::{gen_vault_signer_key, AccountFlag, Market, MarketState, MarketStateV2};
 
-    let account_data: Vec<u8> = client.get_account_data(&market).await?;
+    let account_data: Vec<u8> = client.get_account_data(market).await?;
     let words: Cow<[u64]> = remove_dex_account_padding(&account_data)?;
     let market_state: MarketState = {
         let account_flags = Market::account_flags(&account_data)?;
@@ -1658,25 +1655,8 @@ fn remove_dex_account_padding<'a>(data: &'a [u8]) -> eyre::Result<Cow<'a, [u64]>
     Ok(words)
 }
 
-pub async fn load_amm_keys(client: &RpcClient, amm_pool: &Pubkey) -> eyre::Result<AmmKeys> {
-    let mut retries = 0;
-    let max_retries = 1000;
-    let mut account = None;
-
-    while account.is_none() && retries < max_retries {
-        match client.get_account(&amm_pool).await {
-            Ok(acc) => account = Some(acc),
-            Err(_) => {
-                retries += 1;
-                continue;
-            }
-        }
-    }
-
-    let account = match account {
-        Some(acc) => acc,
-        None => return Err(eyre::eyre!("Account not found after maximum retries")),
-    };
+pub async fn load_amm_keys(watcher: &AccountWatcher, amm_pool: &Pubkey) -> eyre::Result<AmmKeys> {
+    let account = watcher.wait_for_account(amm_pool).await?;
 
     let data = account.clone().data;
     let mut info = LIQUIDITY_STATE_LAYOUT_V4::decode(&mut &data[..])?;
@@ -1704,7 +1684,7 @@ pub async fn load_amm_keys(client: &RpcClient, amm_pool: &Pubkey) -> eyre::Resul
 
 pub fn authority_id(program_id: &Pubkey, amm_seed: &[u8], nonce: u8) -> Result<Pubkey, AmmError> {
     Pubkey::create_program_address(&[amm_seed, &[nonce]], program_id)
-        .map_err(|_| AmmError::InvalidProgramAddress.into())
+        .map_err(|_| AmmError::InvalidProgramAddress)
 }
 
 pub async fn get_account<T>(client: &RpcClient, addr: &Pubkey) -> eyre::Result<Option<T>>
@@ -1,5 +1,6 @@
 pub mod decoder;
 pub mod error;
+#[allow(clippy::module_inception)]
 pub mod instruction;
 pub mod pool_ixs;
 pub mod swap_ixs;
@@ -1,10 +1,7 @@
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
 
 use crate::{
-    env::{
-        input::{liq_amount, token_percentage},
-        PoolDataSettings,
-    },
+    env::cluster::Cluster,
     raydium::instruction::{
         decoder::SOLC_MINT,
         instruction::{get_amm_pda_keys, initialize_amm_pool, SOL_MINT},
@@ -24,101 +21,77 @@ use spl_token::instruction::initialize_account;
 
 use super::instruction::{get_keys_for_market, AmmKeys, MarketPubkeys};
 
-pub const AMM_PROGRAM: Pubkey = solana_sdk::pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+/// Launch parameters, collected by the menu prompts or the CLI flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LaunchParams {
+    /// SOL liquidity added to the pool, in lamports
+    pub sol_amount: u64,
+    /// Share of the deployer's token balance added to the pool, 0.0 - 1.0
+    pub token_percentage: f64,
+    /// Tip paid by the buyer wallet in the last transaction of the bundle, in lamports
+    pub bundle_tip: u64,
+    /// Unix timestamp the pool opens for swaps, 0 opens it immediately
+    pub open_time: u64,
+}
 
-pub async fn pool_ixs(
-    pool_data: PoolDataSettings,
-) -> eyre::Result<(Vec<Instruction>, Pubkey, AmmKeys)> {
-    let market_program = Pubkey::from_str("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX")?;
-    let market = Pubkey::from_str(&pool_data.market_id)?;
-    let amm_coin_mint = Pubkey::from_str(&pool_data.token_mint)?;
+/// Builds the instructions creating the pool from `deployer`'s token balance.
+///
+/// `token_balance` is the deployer's token ATA balance; the caller is expected to
+/// fetch it. Returns the instructions together with the derived AMM keys.
+pub fn pool_ixs(
+    cluster: &Cluster,
+    deployer: &Pubkey,
+    market: &Pubkey,
+    token_mint: &Pubkey,
+    token_balance: u64,
+    params: &LaunchParams,
+) -> eyre::Result<(Vec<Instruction>, AmmKeys)> {
     let amm_pc_mint = SOL_MINT;
-    // maintnet: 7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5
-    // devnet: 3XMrhbv989VxAMi3DErLV9eJht1pHppW5LbKxe9fkEFR
-    let create_fee_destination = Pubkey::from_str("7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5")?;
-
-    let wallet = Keypair::from_base58_string(&pool_data.deployer_key);
-
-    let rpc_client = RpcClient::new(pool_data.rpc_url.clone());
-
-    let associated_token = spl_associated_token_account::get_associated_token_address(
-        &wallet.pubkey(),
-        &Pubkey::from_str(&pool_data.token_mint)?,
-    );
-
-    let token_accounts = rpc_client
-        .get_token_account_balance(&associated_token)
-        .await?;
-
-    let base_pc_amount = token_accounts.amount.parse::<u64>()?;
-
-    println!("Base PC Amount: {}", base_pc_amount);
-
-    let sol_amount = liq_amount();
-    let percentage = token_percentage();
-
-    let balance = rpc_client.get_balance(&wallet.pubkey()).await?;
-
-    if balance < (sol_amount + sol_to_lamports(0.3 + 0.4)) {
-        log::error!(
-            "Insufficient balance in deployer key to create pool: {} SOL",
-            lamports_to_sol(balance)
-        );
-        panic!();
-    }
 
-    let input_pc_amount = sol_to_lamports(lamports_to_sol(base_pc_amount) * percentage);
+    let input_pc_amount = sol_to_lamports(lamports_to_sol(token_balance) * params.token_percentage);
 
     // generate amm keys
     let amm_keys = get_amm_pda_keys(
-        &AMM_PROGRAM,
-        &market_program,
-        &market,
-        &amm_coin_mint,
+        &cluster.amm_program,
+        &cluster.market_program,
+        market,
+        token_mint,
         &amm_pc_mint,
     );
 
     let mut pool_inx = vec![];
 
-    let (pubkey, seed) = generate_pubkey(wallet.pubkey()).await?;
-
-    println!("Seed: {}", seed);
+    let (pubkey, seed) = generate_pubkey(*deployer)?;
 
     let inx = create_account_with_seed(
-        &wallet.pubkey(),
+        deployer,
         &pubkey,
-        &wallet.pubkey(),
+        deployer,
         &seed,
-        sol_amount + 2039280,
+        params.sol_amount + 2039280,
         165,
         &spl_token::id(),
     );
 
-    let init = initialize_account(&spl_token::id(), &pubkey, &SOLC_MINT, &wallet.pubkey())?;
-
-    let token = spl_associated_token_account::get_associated_token_address(
-        &wallet.pubkey(),
-        &amm_keys.amm_pc_mint,
-    );
+    let init = initialize_account(&spl_token::id(), &pubkey, &SOLC_MINT, deployer)?;
 
-    println!("Token: {}", token);
     // build initialize instruction
     let build_init_instruction = initialize_amm_pool(
-        &AMM_PROGRAM,
+        &cluster.amm_program,
         &amm_keys,
-        &create_fee_destination,
-        &wallet.pubkey(),
+        &cluster.create_fee_destination,
+        deployer,
         &spl_associated_token_account::get_associated_token_address(
-            &wallet.pubkey(),
+            deployer,
             &amm_keys.amm_coin_mint,
         ),
         &pubkey,
         &spl_associated_token_account::get_associated_token_address(
-            &wallet.pubkey(),
+            deployer,
             &amm_keys.amm_lp_mint,
         ),
-        0,
-        sol_amount,
+        params.open_time,
+        params.sol_amount,
         input_pc_amount,
     )?;
 
@@ -126,10 +99,40 @@ pub async fn pool_ixs(
     pool_inx.push(init);
     pool_inx.push(build_init_instruction);
 
-    Ok((pool_inx, amm_keys.amm_pool, amm_keys))
+    Ok((pool_inx, amm_keys))
+}
+
+/// Checks that `deployer` holds enough SOL and returns its token balance for [`pool_ixs`].
+pub async fn deployer_token_balance(
+    rpc_client: &RpcClient,
+    deployer: &Pubkey,
+    token_mint: &Pubkey,
+    params: &LaunchParams,
+) -> eyre::Result<u64> {
+    let associated_token =
+        spl_associated_token_account::get_associated_token_address(deployer, token_mint);
+
+    let token_accounts = rpc_client
+        .get_token_account_balance(&associated_token)
+        .await?;
+
+    let base_pc_amount = token_accounts.amount.parse::<u64>()?;
+
+    info!("Base PC Amount: {}", base_pc_amount);
+
+    let balance = rpc_client.get_balance(deployer).await?;
+
+    if balance < (params.sol_amount + sol_to_lamports(0.3 + 0.4)) {
+        return Err(eyre::eyre!(
+            "Insufficient balance in deployer key to create pool: {} SOL",
+            lamports_to_sol(balance)
+        ));
+    }
+
+    Ok(base_pc_amount)
 }
 
-pub async fn generate_pubkey(from_public_key: Pubkey) -> eyre::Result<(Pubkey, String)> {
+pub fn generate_pubkey(from_public_key: Pubkey) -> eyre::Result<(Pubkey, String)> {
     let seed = Keypair::new()
         .pubkey()
         .to_string()
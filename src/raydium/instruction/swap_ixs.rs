@@ -1,17 +1,13 @@
-use std::str::FromStr;
-
 use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer};
 use spl_associated_token_account::get_associated_token_address;
 
-use crate::env::PoolDataSettings;
-
-use super::{
-    instruction::{swap, AmmKeys, MarketPubkeys},
-    pool_ixs::AMM_PROGRAM,
-};
+use super::instruction::{swap, AmmKeys, MarketPubkeys};
+use crate::env::cluster::Cluster;
 
+#[allow(clippy::too_many_arguments)]
 pub fn swap_ixs(
-    server_data: PoolDataSettings,
+    cluster: &Cluster,
+    token_mint: &Pubkey,
     amm_keys: AmmKeys,
     market_keys: MarketPubkeys,
     wallet: &Keypair,
@@ -25,14 +21,11 @@ pub fn swap_ixs(
     //     buyer_wallet = &buyer_keypair;
     // }
 
-    let user_token_destination = get_associated_token_address(
-        &buyer_wallet.pubkey(),
-        &Pubkey::from_str(&server_data.token_mint)?,
-    );
+    let user_token_destination = get_associated_token_address(&buyer_wallet.pubkey(), token_mint);
 
     // build swap instruction
     let build_swap_instruction = swap(
-        &AMM_PROGRAM,
+        &cluster.amm_program,
         &amm_keys,
         &market_keys,
         &buyer_wallet.pubkey(),
@@ -0,0 +1,621 @@
+//! Resumable launch orchestrator.
+//!
+//! Runs the launch modes as ordered steps and records each finished step in the
+//! wallet folder's manifest. A step that is not marked finished is first checked
+//! against the chain, so a rerun after a failure only spends on the wallets and
+//! accounts that are still missing.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use demand::{Confirm, Input};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    env::{
+        input::{bundle_priority_tip, liq_amount, sol_amount, token_percentage},
+        load_minter_settings, PoolDataSettings,
+    },
+    raydium::{
+        atas::wrap_sol::wrap_wallets,
+        bundler::launch_pool,
+        distribution::{rand::check_distribution, sol_distribution::distribute_sol},
+        instruction::{instruction::SOL_MINT, pool_ixs::LaunchParams},
+        lut::extend_lut::create_wallets_lut,
+        manifest::LaunchManifest,
+        wallets::{read_wallets, save_wallets},
+    },
+};
+
+/// `getMultipleAccounts` accepts at most 100 keys per request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchStep {
+    Wallets,
+    Lut,
+    Distribute,
+    Wrap,
+    Launch,
+}
+
+impl LaunchStep {
+    /// Every step in the order a launch runs them.
+    pub const ALL: [LaunchStep; 5] = [
+        LaunchStep::Wallets,
+        LaunchStep::Lut,
+        LaunchStep::Distribute,
+        LaunchStep::Wrap,
+        LaunchStep::Launch,
+    ];
+}
+
+impl fmt::Display for LaunchStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LaunchStep::Wallets => "Generate Wallets",
+            LaunchStep::Lut => "Create LUT",
+            LaunchStep::Distribute => "Distribute SOL",
+            LaunchStep::Wrap => "Wrap SOL & ATAs",
+            LaunchStep::Launch => "Bundle Liquidity",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Everything a full launch needs besides the settings file.
+#[derive(Debug, Clone, Copy)]
+pub struct LaunchPlan {
+    /// Wallets generated when the folder has none yet.
+    pub wallet_count: u64,
+    pub encrypt: bool,
+    /// Total lamports distributed over all wallets.
+    pub total_amount: u64,
+    pub min_amount: u64,
+    pub max_amount: u64,
+    pub distribution_tip: u64,
+    pub params: LaunchParams,
+}
+
+pub async fn launch_main() -> eyre::Result<()> {
+    let folder = Input::new("Enter Folder Name:")
+        .placeholder("Floki...")
+        .prompt("Input: ")
+        .run()
+        .expect("error running input");
+
+    let (wallet_count, encrypt) = if has_wallets(&folder) {
+        (0, false)
+    } else {
+        let count = loop {
+            let string = Input::new("Wallet Count:")
+                .placeholder("27")
+                .prompt("Input: ")
+                .run()
+                .expect("error running input");
+
+            match string.parse::<u64>() {
+                Ok(val) => break val,
+                Err(_) => println!("Invalid input. Please enter a number."),
+            }
+        };
+        let encrypt = Confirm::new("Keystore")
+            .description("Encrypt the wallet files with the keystore passphrase?")
+            .affirmative("Yes")
+            .negative("No")
+            .run()
+            .unwrap();
+
+        (count, encrypt)
+    };
+
+    let settings = load_minter_settings().await?;
+
+    let total_amount = sol_amount("Total Amount:").await;
+    let max_amount = sol_amount("Max Distribution Amount:").await;
+    let min_amount = sol_amount("Min Distribution Amount:").await;
+    let distribution_tip = bundle_priority_tip().await;
+
+    let params = LaunchParams {
+        sol_amount: liq_amount(),
+        token_percentage: token_percentage(),
+        bundle_tip: bundle_priority_tip().await,
+        open_time: 0,
+    };
+
+    let plan = LaunchPlan {
+        wallet_count,
+        encrypt,
+        total_amount,
+        min_amount,
+        max_amount,
+        distribution_tip,
+        params,
+    };
+
+    run_launch(settings, &folder, &plan).await
+}
+
+/// Runs every unfinished step of the launch in `folder`, in order.
+///
+/// Steps marked finished in the manifest are skipped. The others are checked on
+/// chain first and only run for the work that is still missing.
+pub async fn run_launch(
+    settings: PoolDataSettings,
+    folder: &str,
+    plan: &LaunchPlan,
+) -> eyre::Result<()> {
+    let rpc_client = Arc::new(RpcClient::new(settings.rpc_url.clone()));
+
+    if !has_wallets(folder) {
+        save_wallets(folder, plan.wallet_count, plan.encrypt)?;
+    }
+
+    let mut manifest = LaunchManifest::load(folder)?;
+    manifest.record_target(&settings)?;
+    manifest.mark_complete(LaunchStep::Wallets);
+    manifest.save()?;
+
+    let wallets = read_wallets(folder)?;
+    if !manifest.is_complete(LaunchStep::Distribute) {
+        // Fail before paying for the LUT
+        check_distribution(
+            plan.total_amount,
+            wallets.len(),
+            plan.min_amount,
+            plan.max_amount,
+        )?;
+    }
+
+    for step in LaunchStep::ALL {
+        if manifest.is_complete(step) {
+            info!("{}: already done", step);
+            continue;
+        }
+
+        info!("{}: checking chain state", step);
+
+        match step {
+            LaunchStep::Wallets => {}
+            LaunchStep::Lut => {
+                if lut_extended(&rpc_client, &manifest, &wallets).await? {
+                    info!("{}: LUT already extended", step);
+                } else {
+                    create_wallets_lut(settings.clone(), &wallets, &mut manifest).await?;
+                }
+            }
+            LaunchStep::Distribute => {
+                let pending = unfunded_wallets(&rpc_client, &wallets, plan.min_amount).await?;
+                if pending.is_empty() {
+                    info!("{}: every wallet is already funded", step);
+                } else {
+                    // Funded wallets already received their share of the total
+                    let total_amount = (plan.total_amount as u128 * pending.len() as u128
+                        / wallets.len() as u128) as u64;
+
+                    info!(
+                        "{}: funding {} of {} wallets",
+                        step,
+                        pending.len(),
+                        wallets.len()
+                    );
+
+                    distribute_sol(
+                        settings.clone(),
+                        &pending,
+                        &mut manifest,
+                        total_amount,
+                        plan.min_amount,
+                        plan.max_amount,
+                        plan.distribution_tip,
+                    )
+                    .await?;
+                }
+            }
+            LaunchStep::Wrap => {
+                let pending = unwrapped_wallets(&rpc_client, &wallets).await?;
+                if pending.is_empty() {
+                    info!("{}: every wallet is already wrapped", step);
+                } else {
+                    info!(
+                        "{}: wrapping {} of {} wallets",
+                        step,
+                        pending.len(),
+                        wallets.len()
+                    );
+
+                    wrap_wallets(settings.clone(), &pending, &mut manifest).await?;
+
+                    // wsol skips wallets without enough SOL to wrap
+                    let skipped = unwrapped_wallets(&rpc_client, &wallets).await?;
+                    if !skipped.is_empty() {
+                        warn!("{}: {} wallets were not wrapped", step, skipped.len());
+                    }
+                }
+            }
+            LaunchStep::Launch => {
+                if pool_exists(&rpc_client, &manifest).await? {
+                    info!("{}: pool already exists", step);
+                } else {
+                    launch_pool(settings.clone(), &wallets, &mut manifest, plan.params).await?;
+                    if !pool_exists(&rpc_client, &manifest).await? {
+                        return Err(eyre::eyre!("{}: launch bundle did not land", step));
+                    }
+                }
+            }
+        }
+
+        manifest.mark_complete(step);
+        manifest.save()?;
+
+        info!("{}: done", step);
+    }
+
+    Ok(())
+}
+
+fn has_wallets(folder: &str) -> bool {
+    read_wallets(folder).is_ok_and(|wallets| !wallets.is_empty())
+}
+
+async fn get_accounts(
+    rpc_client: &RpcClient,
+    pubkeys: &[Pubkey],
+) -> eyre::Result<Vec<Option<Account>>> {
+    let mut accounts = Vec::with_capacity(pubkeys.len());
+    for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(rpc_client.get_multiple_accounts(chunk).await?);
+    }
+
+    Ok(accounts)
+}
+
+/// True when the manifest's LUT exists and holds the pool and every wallet.
+async fn lut_extended(
+    rpc_client: &RpcClient,
+    manifest: &LaunchManifest,
+    wallets: &[Keypair],
+) -> eyre::Result<bool> {
+    let (Some(lut_key), Some(amm_keys)) = (&manifest.lut_key, manifest.amm_keys) else {
+        return Ok(false);
+    };
+
+    let Some(account) = get_accounts(rpc_client, &[Pubkey::from_str(lut_key)?])
+        .await?
+        .remove(0)
+    else {
+        return Ok(false);
+    };
+
+    let lookup_table = AddressLookupTable::deserialize(&account.data)?;
+    let extended = lookup_table.addresses.contains(&amm_keys.amm_pool)
+        && wallets
+            .iter()
+            .all(|wallet| lookup_table.addresses.contains(&wallet.pubkey()));
+
+    Ok(extended)
+}
+
+/// Wallets holding less than `min_amount` SOL and no wSOL account yet.
+async fn unfunded_wallets(
+    rpc_client: &RpcClient,
+    wallets: &[Keypair],
+    min_amount: u64,
+) -> eyre::Result<Vec<Keypair>> {
+    let pubkeys: Vec<Pubkey> = wallets.iter().map(|wallet| wallet.pubkey()).collect();
+    let wsol_atas: Vec<Pubkey> = pubkeys
+        .iter()
+        .map(|wallet| get_associated_token_address(wallet, &SOL_MINT))
+        .collect();
+
+    let accounts = get_accounts(rpc_client, &pubkeys).await?;
+    let atas = get_accounts(rpc_client, &wsol_atas).await?;
+
+    let pending = wallets
+        .iter()
+        .zip(accounts.iter().zip(atas.iter()))
+        .filter(|(_, (account, ata))| {
+            let lamports = account.as_ref().map_or(0, |account| account.lamports);
+            lamports < min_amount && ata.is_none()
+        })
+        .map(|(wallet, _)| wallet.insecure_clone())
+        .collect();
+
+    Ok(pending)
+}
+
+/// Wallets without a wSOL account. ATA creation and the wrap land in the same
+/// transaction, so an existing wSOL account means the wallet was wrapped.
+async fn unwrapped_wallets(
+    rpc_client: &RpcClient,
+    wallets: &[Keypair],
+) -> eyre::Result<Vec<Keypair>> {
+    let wsol_atas: Vec<Pubkey> = wallets
+        .iter()
+        .map(|wallet| get_associated_token_address(&wallet.pubkey(), &SOL_MINT))
+        .collect();
+
+    let atas = get_accounts(rpc_client, &wsol_atas).await?;
+
+    let pending = wallets
+        .iter()
+        .zip(atas.iter())
+        .filter(|(_, ata)| ata.is_none())
+        .map(|(wallet, _)| wallet.insecure_clone())
+        .collect();
+
+    Ok(pending)
+}
+
+async fn pool_exists(rpc_client: &RpcClient, manifest: &LaunchManifest) -> eyre::Result<bool> {
+    let Some(amm_keys) = manifest.amm_keys else {
+        return Ok(false);
+    };
+
+    let account = get_accounts(rpc_client, &[amm_keys.amm_pool])
+        .await?
+        .remove(0);
+
+    Ok(account.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_sdk::{
+        native_token::sol_to_lamports, system_instruction::SystemInstruction,
+        transaction::VersionedTransaction,
+    };
+
+    use super::*;
+    use crate::raydium::{
+        instruction::{instruction::AmmKeys, pool_ixs::pool_ixs},
+        testing::{lookup_table, MockCluster},
+    };
+
+    fn system_account(lamports: u64) -> Account {
+        Account {
+            lamports,
+            ..Account::default()
+        }
+    }
+
+    fn plan(wallet_count: u64) -> LaunchPlan {
+        LaunchPlan {
+            wallet_count,
+            encrypt: false,
+            total_amount: sol_to_lamports(wallet_count as f64),
+            min_amount: sol_to_lamports(0.5),
+            max_amount: sol_to_lamports(1.5),
+            distribution_tip: 10_000,
+            params: LaunchParams {
+                sol_amount: sol_to_lamports(1.0),
+                token_percentage: 0.5,
+                bundle_tip: 10_000,
+                open_time: 0,
+            },
+        }
+    }
+
+    fn amm_keys(settings: &PoolDataSettings, plan: &LaunchPlan) -> AmmKeys {
+        pool_ixs(
+            &settings.cluster,
+            &settings.deployer_key.keypair().pubkey(),
+            &Pubkey::from_str(&settings.market_id).unwrap(),
+            &Pubkey::from_str(&settings.token_mint).unwrap(),
+            0,
+            &plan.params,
+        )
+        .unwrap()
+        .1
+    }
+
+    /// A wallet folder whose manifest has the LUT and pool and `completed` marked finished.
+    fn wallet_folder(
+        dir: &tempfile::TempDir,
+        plan: &LaunchPlan,
+        amm_keys: AmmKeys,
+        completed: &[LaunchStep],
+    ) -> (String, Vec<Keypair>) {
+        let folder = dir.path().join("wallets").to_str().unwrap().to_string();
+        save_wallets(&folder, plan.wallet_count, plan.encrypt).unwrap();
+
+        let mut manifest = LaunchManifest::load(&folder).unwrap();
+        manifest.lut_key = Some(Pubkey::new_unique().to_string());
+        manifest.amm_keys = Some(amm_keys);
+        for step in completed {
+            manifest.mark_complete(*step);
+        }
+        manifest.save().unwrap();
+
+        (folder.clone(), read_wallets(&folder).unwrap())
+    }
+
+    /// Lamports each wallet received from the bundle's transfers.
+    fn transfers(bundle: &[VersionedTransaction]) -> HashMap<Pubkey, u64> {
+        bundle
+            .iter()
+            .flat_map(|transaction| {
+                let keys = transaction.message.static_account_keys();
+                transaction
+                    .message
+                    .instructions()
+                    .iter()
+                    .filter_map(|ix| match bincode::deserialize(&ix.data) {
+                        Ok(SystemInstruction::Transfer { lamports }) => {
+                            Some((keys[ix.accounts[1] as usize], lamports))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn finds_wallets_still_to_fund_and_wrap() {
+        let cluster = MockCluster::start().await;
+        let wallets: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        let wsol_ata = |wallet: &Keypair| get_associated_token_address(&wallet.pubkey(), &SOL_MINT);
+        cluster.set_accounts(HashMap::from([
+            // Funded, not wrapped yet
+            (wallets[0].pubkey(), system_account(sol_to_lamports(1.0))),
+            // Wrapped its SOL already
+            (wallets[1].pubkey(), system_account(sol_to_lamports(0.001))),
+            (wsol_ata(&wallets[1]), system_account(sol_to_lamports(0.9))),
+            // Below the minimum, the third wallet has no account at all
+            (wallets[3].pubkey(), system_account(sol_to_lamports(0.01))),
+        ]));
+        let rpc_client = RpcClient::new(cluster.settings().rpc_url);
+
+        let unfunded = unfunded_wallets(&rpc_client, &wallets, sol_to_lamports(0.5))
+            .await
+            .unwrap();
+        assert_eq!(
+            unfunded.iter().map(Keypair::pubkey).collect::<Vec<_>>(),
+            [wallets[2].pubkey(), wallets[3].pubkey()]
+        );
+
+        let unwrapped = unwrapped_wallets(&rpc_client, &wallets).await.unwrap();
+        assert_eq!(
+            unwrapped.iter().map(Keypair::pubkey).collect::<Vec<_>>(),
+            [
+                wallets[0].pubkey(),
+                wallets[2].pubkey(),
+                wallets[3].pubkey()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_accounts_in_batches() {
+        let cluster = MockCluster::start().await;
+        cluster.set_accounts(HashMap::new());
+        let pubkeys: Vec<Pubkey> = (0..150).map(|_| Pubkey::new_unique()).collect();
+
+        let accounts = get_accounts(&RpcClient::new(cluster.settings().rpc_url), &pubkeys)
+            .await
+            .unwrap();
+
+        assert_eq!(accounts.len(), 150);
+        let batches: Vec<usize> = cluster
+            .rpc
+            .calls_of("getMultipleAccounts")
+            .iter()
+            .map(|call| call.params[0].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batches, [MAX_MULTIPLE_ACCOUNTS, 50]);
+    }
+
+    #[tokio::test]
+    async fn resumes_after_each_finished_step() {
+        // Accounts each step checks, Wallets has no check
+        let checks = [0, 1, 2, 1, 1];
+
+        for (resume, step) in LaunchStep::ALL.iter().enumerate() {
+            let cluster = MockCluster::start().await;
+            let settings = cluster.settings();
+            let plan = plan(3);
+            let amm_keys = amm_keys(&settings, &plan);
+            let dir = tempfile::tempdir().unwrap();
+            let (folder, wallets) =
+                wallet_folder(&dir, &plan, amm_keys, &LaunchStep::ALL[..resume]);
+
+            // Everything after the finished steps is on chain already
+            let manifest = LaunchManifest::load(&folder).unwrap();
+            let mut lut = vec![amm_keys.amm_pool];
+            lut.extend(wallets.iter().map(Keypair::pubkey));
+            let mut accounts = HashMap::from([
+                (manifest.lut(&settings).unwrap(), lookup_table(lut)),
+                (amm_keys.amm_pool, system_account(sol_to_lamports(0.1))),
+            ]);
+            for wallet in &wallets {
+                accounts.insert(wallet.pubkey(), system_account(sol_to_lamports(0.1)));
+                accounts.insert(
+                    get_associated_token_address(&wallet.pubkey(), &SOL_MINT),
+                    system_account(sol_to_lamports(0.9)),
+                );
+            }
+            cluster.set_accounts(accounts);
+
+            run_launch(settings, &folder, &plan).await.unwrap();
+
+            assert!(cluster.bundles().is_empty(), "resuming at {}", step);
+            // Finished steps are not checked again
+            assert_eq!(
+                cluster.rpc.calls_of("getMultipleAccounts").len(),
+                checks[resume..].iter().sum::<usize>(),
+                "resuming at {}",
+                step
+            );
+            let manifest = LaunchManifest::load(&folder).unwrap();
+            assert!(LaunchStep::ALL
+                .iter()
+                .all(|step| manifest.is_complete(*step)));
+        }
+    }
+
+    #[tokio::test]
+    async fn funds_only_unfunded_wallets_with_their_share() {
+        let cluster = MockCluster::start().await;
+        cluster.set_balance(sol_to_lamports(1.0));
+        cluster.set_lookup_table(Vec::new());
+        let settings = cluster.settings();
+        let plan = plan(4);
+        let amm_keys = amm_keys(&settings, &plan);
+        let dir = tempfile::tempdir().unwrap();
+        let (folder, wallets) = wallet_folder(
+            &dir,
+            &plan,
+            amm_keys,
+            &[LaunchStep::Wallets, LaunchStep::Lut],
+        );
+        // The first wallet got its share before the last run stopped
+        cluster.set_accounts(HashMap::from([
+            (wallets[0].pubkey(), system_account(sol_to_lamports(1.0))),
+            (amm_keys.amm_pool, system_account(sol_to_lamports(0.1))),
+        ]));
+
+        run_launch(settings, &folder, &plan).await.unwrap();
+
+        let bundles = cluster.bundles();
+        // Distribute, then wrap all four wallets
+        assert_eq!(bundles.len(), 2);
+        let funded = transfers(&bundles[0]);
+        assert!(!funded.contains_key(&wallets[0].pubkey()));
+        let shares: Vec<u64> = wallets[1..]
+            .iter()
+            .map(|wallet| funded[&wallet.pubkey()])
+            .collect();
+        // Three of four wallets get three quarters of the total
+        assert_eq!(shares.iter().sum::<u64>(), plan.total_amount / 4 * 3);
+        assert!(shares
+            .iter()
+            .all(|share| (plan.min_amount..=plan.max_amount).contains(share)));
+
+        let manifest = LaunchManifest::load(&folder).unwrap();
+        assert_eq!(manifest.funding.len(), 3);
+        assert!(LaunchStep::ALL
+            .iter()
+            .all(|step| manifest.is_complete(*step)));
+    }
+
+    #[tokio::test]
+    async fn checks_distribution_before_creating_lut() {
+        let cluster = MockCluster::start().await;
+        let settings = cluster.settings();
+        let mut plan = plan(3);
+        plan.total_amount = sol_to_lamports(1.0);
+        let dir = tempfile::tempdir().unwrap();
+        let (folder, _) = wallet_folder(&dir, &plan, amm_keys(&settings, &plan), &[]);
+
+        assert!(run_launch(settings, &folder, &plan).await.is_err());
+        assert!(cluster.rpc.calls().is_empty());
+    }
+}
@@ -1,8 +1,7 @@
 use std::{str::FromStr, sync::Arc};
 
-use jito_protos::searcher::SubscribeBundleResultsRequest;
-use jito_searcher_client::{get_searcher_client, send_bundle_with_confirmation};
-use log::info;
+use bincode::serialize;
+use log::{debug, info};
 use solana_address_lookup_table_program::instruction::extend_lookup_table;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
@@ -17,51 +16,56 @@ use solana_sdk::{
 use spl_associated_token_account::get_associated_token_address;
 
 use crate::{
-    env::{
-        jito_auth::{auth_keypair, jito_tip_acc, jito_tip_inx},
-        load_minter_settings, PoolDataSettings,
-    },
+    env::{load_minter_settings, PoolDataSettings},
     raydium::{
+        fallback::send_step,
         instruction::{
             instruction::{get_amm_pda_keys, AmmKeys, MarketPubkeys, SOL_MINT},
             pool_ixs::load_pool_keys,
         },
-        wallets::load_wallets,
+        manifest::LaunchManifest,
+        wallets::list_folders,
     },
 };
 
 use super::create_lut::create_lut;
 
+/// Addresses per extend instruction, filling a transaction on its own.
+const ADDRESSES_PER_EXTEND: usize = 30;
+/// Addresses per extend instruction that leave room for the compute budget instructions.
+const PRIORITY_ADDRESSES_PER_EXTEND: usize = 27;
+
 pub async fn poolkeys_lut(
     amm_keys: AmmKeys,
     market_keys: MarketPubkeys,
     lut: Pubkey,
     server_data: PoolDataSettings,
 ) -> eyre::Result<Instruction> {
-    let buyer_wallet = Keypair::from_base58_string(&server_data.buyer_key);
-
-    let mut keys = vec![];
-    keys.push(amm_keys.amm_pool);
-    keys.push(amm_keys.amm_coin_mint);
-    keys.push(amm_keys.amm_pc_mint);
-    keys.push(amm_keys.amm_lp_mint);
-    keys.push(amm_keys.amm_authority);
-    keys.push(amm_keys.amm_open_order);
-    keys.push(amm_keys.amm_target);
-    keys.push(amm_keys.amm_coin_vault);
-    keys.push(amm_keys.amm_pc_vault);
-    keys.push(amm_keys.market_program);
-    keys.push(amm_keys.market);
-    keys.push(*market_keys.market);
-    keys.push(*market_keys.req_q);
-    keys.push(*market_keys.event_q);
-    keys.push(*market_keys.bids);
-    keys.push(*market_keys.asks);
-    keys.push(*market_keys.coin_vault);
-    keys.push(*market_keys.pc_vault);
-    keys.push(*market_keys.vault_signer_key);
-    keys.push(*market_keys.coin_mint);
-    keys.push(*market_keys.pc_mint);
+    let buyer_wallet = server_data.buyer_key.keypair();
+
+    let keys = vec![
+        amm_keys.amm_pool,
+        amm_keys.amm_coin_mint,
+        amm_keys.amm_pc_mint,
+        amm_keys.amm_lp_mint,
+        amm_keys.amm_authority,
+        amm_keys.amm_open_order,
+        amm_keys.amm_target,
+        amm_keys.amm_coin_vault,
+        amm_keys.amm_pc_vault,
+        amm_keys.market_program,
+        amm_keys.market,
+        *market_keys.market,
+        *market_keys.req_q,
+        *market_keys.event_q,
+        *market_keys.bids,
+        *market_keys.asks,
+        *market_keys.coin_vault,
+        *market_keys.pc_vault,
+        *market_keys.vault_signer_key,
+        *market_keys.coin_mint,
+        *market_keys.pc_mint,
+    ];
 
     let add_accounts = extend_lookup_table(
         lut,
@@ -77,8 +81,9 @@ pub async fn accountatas_lut(
     lut: Pubkey,
     server_data: PoolDataSettings,
     wallets: Vec<Pubkey>,
+    addresses_per_extend: usize,
 ) -> eyre::Result<Vec<Instruction>> {
-    let buyer_wallet = Keypair::from_base58_string(&server_data.buyer_key);
+    let buyer_wallet = server_data.buyer_key.keypair();
     let mint = Pubkey::from_str(&server_data.token_mint)?;
 
     let mut atas: Vec<Pubkey> = vec![];
@@ -103,12 +108,12 @@ pub async fn accountatas_lut(
     let mut chunk: Vec<Pubkey> = vec![];
     for ata in atas {
         chunk.push(ata);
-        if chunk.len() == 30 {
+        if chunk.len() == addresses_per_extend {
             chunks.push(chunk);
             chunk = vec![];
         }
     }
-    if chunk.len() > 0 {
+    if !chunk.is_empty() {
         chunks.push(chunk);
     }
 
@@ -132,130 +137,157 @@ pub async fn lut_caller(
     amm_keys: AmmKeys,
     market_keys: MarketPubkeys,
     wallets: Vec<Pubkey>,
+    manifest: &mut LaunchManifest,
 ) -> eyre::Result<Pubkey> {
-    let buyer_wallet = Keypair::from_base58_string(&server_data.buyer_key);
+    let buyer_wallet = server_data.buyer_key.keypair();
 
     let rpc_client = Arc::new(RpcClient::new(server_data.rpc_url.clone()));
 
     let (lut_inx, lut_account) = create_lut(server_data.clone()).await?;
-
-    let mut extendlut_ixs: Vec<Instruction> = vec![];
-
-    extendlut_ixs.push(lut_inx);
     let pool_lut = poolkeys_lut(amm_keys, market_keys, lut_account, server_data.clone()).await?;
-    let ata_lut = accountatas_lut(lut_account, server_data.clone(), wallets.clone()).await?;
 
-    extendlut_ixs.push(pool_lut);
-    extendlut_ixs.extend(ata_lut);
-
-    let tip = jito_tip_inx(
-        buyer_wallet.pubkey(),
-        jito_tip_acc(),
+    let (server_data, wallets, buyer_wallet, rpc) =
+        (&server_data, &wallets, &buyer_wallet, &rpc_client);
+    let (lut_inx, pool_lut) = (&lut_inx, &pool_lut);
+    let result = send_step(
+        server_data,
+        "lut",
         sol_to_lamports(0.005),
-    );
-    extendlut_ixs.push(tip);
-
-    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
-
-    let mut versioned_txns: Vec<VersionedTransaction> = vec![];
-
-    if extendlut_ixs.len() >= 2 {
-        let versioned_msg = VersionedMessage::V0(Message::try_compile(
-            &buyer_wallet.pubkey(),
-            &extendlut_ixs[0..2], // Include the first two instructions in the message
-            &[],
-            recent_blockhash,
-        )?);
-
-        let transaction = VersionedTransaction::try_new(versioned_msg, &[&buyer_wallet])?;
+        manifest,
+        |fee| async move {
+            let addresses_per_extend = match fee.is_bundle() {
+                true => ADDRESSES_PER_EXTEND,
+                false => PRIORITY_ADDRESSES_PER_EXTEND,
+            };
+            let ata_lut = accountatas_lut(
+                lut_account,
+                server_data.clone(),
+                wallets.clone(),
+                addresses_per_extend,
+            )
+            .await?;
+
+            let mut extendlut_ixs = vec![lut_inx.clone(), pool_lut.clone()];
+            extendlut_ixs.extend(ata_lut);
+            if let Some(tip) = fee.tip(buyer_wallet.pubkey()) {
+                extendlut_ixs.push(tip);
+            }
+            let compute_budget = fee.compute_budget()?;
+
+            let recent_blockhash = rpc.get_latest_blockhash().await?;
+
+            let mut versioned_txns: Vec<VersionedTransaction> = vec![];
+
+            if extendlut_ixs.len() >= 2 {
+                // Include the first two instructions in the message
+                let instructions = [&compute_budget[..], &extendlut_ixs[0..2]].concat();
+                let versioned_msg = VersionedMessage::V0(Message::try_compile(
+                    &buyer_wallet.pubkey(),
+                    &instructions,
+                    &[],
+                    recent_blockhash,
+                )?);
+
+                let transaction = VersionedTransaction::try_new(versioned_msg, &[buyer_wallet])?;
+
+                versioned_txns.push(transaction);
+            }
+
+            if extendlut_ixs.len() > 2 {
+                for ix in &extendlut_ixs[2..] {
+                    let instructions = [&compute_budget[..], std::slice::from_ref(ix)].concat();
+                    let versioned_msg = VersionedMessage::V0(Message::try_compile(
+                        &buyer_wallet.pubkey(),
+                        &instructions,
+                        &[],
+                        recent_blockhash,
+                    )?);
+
+                    let transaction =
+                        VersionedTransaction::try_new(versioned_msg, &[buyer_wallet])?;
+
+                    versioned_txns.push(transaction);
+                }
+            }
+
+            let sizes: Vec<usize> = versioned_txns
+                .iter()
+                .map(|tx| serialize(tx).map_or(0, |tx| tx.len()))
+                .collect();
+            debug!(
+                "{} LUT transactions of {:?} bytes",
+                versioned_txns.len(),
+                sizes
+            );
+
+            // Only a bundle is limited to five transactions
+            if fee.is_bundle() && versioned_txns.len() > 5 {
+                return Err(eyre::eyre!("Too many transactions"));
+            }
+
+            Ok(versioned_txns)
+        },
+    )
+    .await;
 
-        versioned_txns.push(transaction);
+    if result.is_ok() {
+        manifest.lut_key = Some(lut_account.to_string());
+        manifest.save()?;
     }
 
-    if extendlut_ixs.len() > 2 {
-        for ix in &extendlut_ixs[2..] {
-            let versioned_msg = VersionedMessage::V0(Message::try_compile(
-                &buyer_wallet.pubkey(),
-                &[ix.clone()],
-                &[],
-                recent_blockhash,
-            )?);
-
-            let transaction = VersionedTransaction::try_new(versioned_msg, &[&buyer_wallet])?;
-
-            versioned_txns.push(transaction);
-        }
+    if let Err(e) = result {
+        return Err(eyre::eyre!("Error sending bundle: {:?}", e));
     }
 
-    let mut sum = 0;
-    let txn_size: Vec<_> = versioned_txns
-        .iter()
-        .map(|x| {
-            let serialized_x = serialize(x).unwrap();
-            //sum all of them
-            sum += serialized_x.len();
-            serialized_x.len()
-        })
-        .collect();
-
-    println!("Sum: {:?}", sum);
-    println!("txn_size: {:?}", txn_size);
-
-    println!("{}", versioned_txns.len());
-
-    if versioned_txns.len() > 5 {
-        println!("{}", versioned_txns.len());
-        return Err(eyre::eyre!("Too many transactions"));
-    }
+    Ok(lut_account)
+}
 
-    let mut client = get_searcher_client(
-        "https://ny.mainnet.block-engine.jito.wtf",
-        &Arc::new(auth_keypair()),
-    )
-    .await?;
+pub async fn lut_main() -> eyre::Result<()> {
+    let pool_data = load_minter_settings().await?;
 
-    let mut bundle_results_subscription = client
-        .subscribe_bundle_results(SubscribeBundleResultsRequest {})
-        .await
-        .expect("subscribe to bundle results")
-        .into_inner();
+    let (folder, wallets): (String, Vec<Keypair>) = match list_folders().await {
+        Ok(folders) => folders,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            panic!("Error: {}", e);
+        }
+    };
 
-    use bincode::serialize;
+    let mut manifest = LaunchManifest::load(&folder)?;
 
-    let _ = match send_bundle_with_confirmation(
-        &versioned_txns,
-        &rpc_client,
-        &mut client,
-        &mut bundle_results_subscription,
-    )
-    .await
-    {
-        Ok(results) => results,
+    let lut = match create_wallets_lut(pool_data, &wallets, &mut manifest).await {
+        Ok(lut) => lut,
         Err(e) => {
-            return Err(eyre::eyre!("Error sending bundle: {:?}", e));
+            eprintln!("Error: {}", e);
+            panic!("Error: {}", e);
         }
     };
 
-    Ok(lut_account)
-}
+    info!("Lut Account:  {}", lut.to_string());
 
-pub async fn lut_main() -> eyre::Result<()> {
-    let pool_data = load_minter_settings().await?;
+    Ok(())
+}
 
+/// Creates a LUT holding the pool, market and wallet ATA keys for `wallets`.
+///
+/// The derived pool keys and the LUT address are recorded in `manifest`.
+pub async fn create_wallets_lut(
+    pool_data: PoolDataSettings,
+    wallets: &[Keypair],
+    manifest: &mut LaunchManifest,
+) -> eyre::Result<Pubkey> {
     let rpc_client = Arc::new(RpcClient::new(pool_data.rpc_url.clone()));
 
-    let amm_program = Pubkey::from_str("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8")?;
-    let market_program = Pubkey::from_str("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX")?;
+    manifest.record_target(&pool_data)?;
+
     let market = Pubkey::from_str(&pool_data.market_id)?;
     let amm_coin_mint = Pubkey::from_str(&pool_data.token_mint)?;
     let amm_pc_mint = SOL_MINT;
-    // maintnet: 7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5
-    // devnet: 3XMrhbv989VxAMi3DErLV9eJht1pHppW5LbKxe9fkEFR
 
     // generate amm keys
     let amm_keys = get_amm_pda_keys(
-        &amm_program,
-        &market_program,
+        &pool_data.cluster.amm_program,
+        &pool_data.cluster.market_program,
         &market,
         &amm_coin_mint,
         &amm_pc_mint,
@@ -263,32 +295,20 @@ pub async fn lut_main() -> eyre::Result<()> {
 
     log::info!("AMM Pool: {:?}", amm_keys.amm_pool);
 
-    let market_keys = load_pool_keys(rpc_client, amm_keys).await?;
-
-    let wallets: Vec<Keypair> = match load_wallets().await {
-        Ok(wallets) => wallets,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Error: {}", e);
-        }
+    let market_keys = match manifest.market_keys.clone() {
+        Some(market_keys) => market_keys,
+        None => load_pool_keys(rpc_client, amm_keys).await?,
     };
 
-    let lut = match lut_caller(
+    manifest.record_pool(amm_keys, market_keys.clone());
+    manifest.save()?;
+
+    lut_caller(
         pool_data,
         amm_keys,
         market_keys,
         wallets.iter().map(|x| x.pubkey()).collect::<Vec<Pubkey>>(),
+        manifest,
     )
     .await
-    {
-        Ok(lut) => lut,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Error: {}", e);
-        }
-    };
-
-    info!("Lut Account:  {}", lut.to_string());
-
-    Ok(())
 }
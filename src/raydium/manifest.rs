@@ -0,0 +1,325 @@
+//! Per-launch deployment manifest.
+//!
+//! Every mode records what it created in `<wallet folder>/manifest.json`, so later
+//! modes pick the mint, market, pool keys and LUT up from there instead of asking
+//! for them again or re-deriving them from chain.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use jito_searcher_client::retry::BundleAttempt;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    env::PoolDataSettings,
+    raydium::{
+        fallback::RpcTransaction,
+        instruction::instruction::{AmmKeys, MarketPubkeys},
+        launch::LaunchStep,
+    },
+};
+
+/// File name of the manifest inside a wallet folder.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// A bundle sent by one of the modes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleRecord {
+    /// Mode that sent the bundle, e.g. `distribute` or `launch`.
+    pub step: String,
+    /// Attempt number, counting from 1.
+    #[serde(default)]
+    pub attempt: u32,
+    /// Tip paid by this attempt, in lamports.
+    #[serde(default)]
+    pub tip: u64,
+    /// UUID assigned by the block engine, missing when the send itself failed.
+    pub uuid: Option<String>,
+    pub signatures: Vec<String>,
+    pub landed: bool,
+    /// Slot the bundle was timed to land in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_slot: Option<u64>,
+    /// Slot the bundle landed in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// Where each transaction landed, in bundle order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub landings: Vec<LandingRecord>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// One transaction of a landed bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LandingRecord {
+    pub signature: String,
+    pub slot: u64,
+    /// Position in the block, known when confirmed through the geyser.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// A transaction sent over RPC after the block engine failed a step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub step: String,
+    pub signature: String,
+    /// Compute unit price paid, in micro-lamports.
+    pub priority_fee: u64,
+    /// Slot the transaction confirmed in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaunchManifest {
+    pub wallet_folder: String,
+    #[serde(default)]
+    pub wallets: Vec<String>,
+    pub token_mint: Option<String>,
+    pub market_id: Option<String>,
+    pub pool_id: Option<String>,
+    pub amm_keys: Option<AmmKeys>,
+    pub market_keys: Option<MarketPubkeys>,
+    pub lut_key: Option<String>,
+    /// Lamports sent to each wallet by the distribute mode, keyed by wallet pubkey.
+    #[serde(default)]
+    pub funding: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub bundles: Vec<BundleRecord>,
+    #[serde(default)]
+    pub transactions: Vec<TransactionRecord>,
+    /// Launch steps the orchestrator finished.
+    #[serde(default)]
+    pub completed: BTreeSet<LaunchStep>,
+}
+
+impl LaunchManifest {
+    pub fn path(wallet_folder: &str) -> PathBuf {
+        PathBuf::from(wallet_folder).join(MANIFEST_FILE)
+    }
+
+    /// Loads the manifest of `wallet_folder`, starting an empty one if none exists yet.
+    pub fn load(wallet_folder: &str) -> eyre::Result<Self> {
+        let path = Self::path(wallet_folder);
+        if !path.exists() {
+            return Ok(LaunchManifest {
+                wallet_folder: wallet_folder.to_string(),
+                ..Default::default()
+            });
+        }
+
+        let mut manifest: LaunchManifest = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))?;
+        manifest.wallet_folder = wallet_folder.to_string();
+
+        Ok(manifest)
+    }
+
+    pub fn save(&self) -> eyre::Result<()> {
+        let path = Self::path(&self.wallet_folder);
+
+        // Write next to the manifest and rename so a crash never leaves it half written
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// Records the mint and market the launch targets.
+    ///
+    /// Fails when the manifest already belongs to a different mint or market, so
+    /// a wallet folder is never reused across launches by accident.
+    pub fn record_target(&mut self, settings: &PoolDataSettings) -> eyre::Result<()> {
+        for (field, recorded, current) in [
+            ("token mint", &mut self.token_mint, &settings.token_mint),
+            ("market", &mut self.market_id, &settings.market_id),
+        ] {
+            match recorded {
+                Some(recorded) if recorded != current => {
+                    return Err(eyre::eyre!(
+                        "{} manifest was created for {} {}, settings use {}",
+                        self.wallet_folder,
+                        field,
+                        recorded,
+                        current
+                    ));
+                }
+                Some(_) => {}
+                None => *recorded = Some(current.clone()),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn record_pool(&mut self, amm_keys: AmmKeys, market_keys: MarketPubkeys) {
+        self.pool_id = Some(amm_keys.amm_pool.to_string());
+        self.amm_keys = Some(amm_keys);
+        self.market_keys = Some(market_keys);
+    }
+
+    /// Records every submission of one bundle.
+    pub fn record_attempts(&mut self, step: &str, attempts: &[BundleAttempt]) {
+        self.bundles.extend(attempts.iter().map(|attempt| {
+            BundleRecord {
+                step: step.to_string(),
+                attempt: attempt.attempt,
+                tip: attempt.tip,
+                uuid: attempt.uuid.clone(),
+                signatures: attempt
+                    .signatures
+                    .iter()
+                    .map(|sig| sig.to_string())
+                    .collect(),
+                landed: attempt.landed,
+                expected_slot: attempt.expected_slot,
+                slot: attempt.slot,
+                landings: attempt
+                    .transactions
+                    .iter()
+                    .map(|transaction| LandingRecord {
+                        signature: transaction.signature.to_string(),
+                        slot: transaction.slot,
+                        index: transaction.index,
+                        error: transaction.err.as_ref().map(|e| e.to_string()),
+                    })
+                    .collect(),
+                error: attempt.error.clone(),
+            }
+        }));
+    }
+
+    /// Records the RPC transactions sent for a step, with the priority fee they paid.
+    pub fn record_transactions(
+        &mut self,
+        step: &str,
+        priority_fee: u64,
+        transactions: &[RpcTransaction],
+    ) {
+        self.transactions
+            .extend(transactions.iter().map(|transaction| TransactionRecord {
+                step: step.to_string(),
+                signature: transaction.signature.to_string(),
+                priority_fee,
+                slot: transaction.slot,
+                error: transaction.error.clone(),
+            }));
+    }
+
+    pub fn mark_complete(&mut self, step: LaunchStep) {
+        self.completed.insert(step);
+    }
+
+    pub fn is_complete(&self, step: LaunchStep) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// LUT recorded for this launch, falling back to the one in the settings file.
+    pub fn lut(&self, settings: &PoolDataSettings) -> eyre::Result<Pubkey> {
+        let lut_key = self.lut_key.as_deref().unwrap_or(&settings.lut_key);
+        Pubkey::from_str(lut_key).map_err(|e| {
+            eyre::eyre!(
+                "LUT key not found in {} manifest, create the LUT first: {}",
+                self.wallet_folder,
+                e
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::env::test_settings;
+
+    #[test]
+    fn records_target_of_first_launch() {
+        let settings = test_settings(json!({})).unwrap();
+        let mut manifest = LaunchManifest::default();
+
+        manifest.record_target(&settings).unwrap();
+        // The same target again is fine
+        manifest.record_target(&settings).unwrap();
+
+        assert_eq!(manifest.token_mint, Some(settings.token_mint));
+        assert_eq!(manifest.market_id, Some(settings.market_id));
+    }
+
+    #[test]
+    fn rejects_other_mint() {
+        let settings = test_settings(json!({})).unwrap();
+        let mut manifest = LaunchManifest {
+            wallet_folder: "Floki".to_string(),
+            token_mint: Some(Pubkey::new_unique().to_string()),
+            ..Default::default()
+        };
+
+        let err = manifest.record_target(&settings).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Floki manifest was created for token mint"));
+        assert!(manifest.market_id.is_none());
+    }
+
+    #[test]
+    fn rejects_other_market() {
+        let settings = test_settings(json!({})).unwrap();
+        let mut manifest = LaunchManifest {
+            wallet_folder: "Floki".to_string(),
+            token_mint: Some(settings.token_mint.clone()),
+            market_id: Some(Pubkey::new_unique().to_string()),
+            ..Default::default()
+        };
+
+        let err = manifest.record_target(&settings).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Floki manifest was created for market"));
+    }
+
+    #[test]
+    fn round_trips_through_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().to_str().unwrap();
+
+        let mut manifest = LaunchManifest::load(folder).unwrap();
+        assert!(manifest.token_mint.is_none());
+
+        manifest
+            .record_target(&test_settings(json!({})).unwrap())
+            .unwrap();
+        manifest.lut_key = Some(Pubkey::new_unique().to_string());
+        manifest
+            .funding
+            .insert(Pubkey::new_unique().to_string(), 250_000_000);
+        manifest.mark_complete(LaunchStep::Distribute);
+        manifest.save().unwrap();
+
+        let loaded = LaunchManifest::load(folder).unwrap();
+        assert_eq!(loaded.wallet_folder, folder);
+        assert_eq!(loaded.token_mint, manifest.token_mint);
+        assert_eq!(loaded.market_id, manifest.market_id);
+        assert_eq!(loaded.lut_key, manifest.lut_key);
+        assert_eq!(loaded.funding, manifest.funding);
+        assert!(loaded.is_complete(LaunchStep::Distribute));
+        assert!(!loaded.is_complete(LaunchStep::Launch));
+        // Saved by renaming, nothing is left behind
+        assert!(!LaunchManifest::path(folder)
+            .with_extension("json.tmp")
+            .exists());
+    }
+}
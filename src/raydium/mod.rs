@@ -1,6 +1,14 @@
+pub mod account_watcher;
 pub mod atas;
 pub mod bundler;
+pub mod dashboard;
 pub mod distribution;
+pub mod fallback;
 pub mod instruction;
+pub mod launch;
 pub mod lut;
+pub mod manifest;
+pub mod report;
+#[cfg(test)]
+pub(crate) mod testing;
 pub mod wallets;
@@ -0,0 +1,736 @@
+//! Reports on landed bundles, decoded from the block they landed in.
+//!
+//! The block is fetched with `getBlock` at confirmed commitment. Each bundle transaction
+//! is found by signature, which gives its position in the block, and its balances are
+//! read from the transaction meta.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+};
+
+use serde::Serialize;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::{lamports_to_sol, LAMPORTS_PER_SOL},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedTransactionWithStatusMeta, TransactionDetails,
+    UiConfirmedBlock, UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+use crate::{
+    env::{jito_auth::known_tip_accounts, load_minter_settings, PoolDataSettings},
+    raydium::{
+        manifest::{BundleRecord, LaunchManifest},
+        wallets::list_folders,
+    },
+};
+
+/// A landed bundle, transaction by transaction.
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleReport {
+    pub step: String,
+    pub uuid: Option<String>,
+    pub slot: u64,
+    pub blockhash: String,
+    pub block_time: Option<i64>,
+    /// Bundle transactions in block order.
+    pub transactions: Vec<TransactionReport>,
+    /// Every signer of the bundle, in order of first appearance.
+    pub wallets: Vec<WalletReport>,
+    /// Transaction fees of the whole bundle, in lamports.
+    pub fees: u64,
+    /// Lamports paid to Jito tip accounts.
+    pub tip: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionReport {
+    pub signature: String,
+    /// Position in the block.
+    pub index: usize,
+    pub fee_payer: String,
+    pub fee: u64,
+    pub tip: u64,
+    pub error: Option<String>,
+    pub token_balances: Vec<TokenBalanceChange>,
+}
+
+/// Pre and post balance of one token account touched by a transaction, in base units.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenBalanceChange {
+    pub account: String,
+    pub owner: Option<String>,
+    pub mint: String,
+    pub decimals: u8,
+    pub pre: u64,
+    pub post: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletReport {
+    pub wallet: String,
+    /// Lamports the wallet lost over the bundle, negative when it gained SOL.
+    pub sol_spent: i64,
+    /// Fees the wallet paid as fee payer.
+    pub fees: u64,
+    /// Tokens the wallet received, per mint, negative when it gave them away.
+    pub tokens: Vec<TokenDelta>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenDelta {
+    pub mint: String,
+    pub decimals: u8,
+    pub change: i128,
+}
+
+impl BundleReport {
+    /// Decodes the transactions of `record` from `block`, the block of `slot`. Transfers
+    /// to `tip_accounts` count as tips.
+    ///
+    /// Fails when one of them is not in the block.
+    pub fn from_block(
+        record: &BundleRecord,
+        slot: u64,
+        block: UiConfirmedBlock,
+        tip_accounts: &[Pubkey],
+    ) -> eyre::Result<Self> {
+        let signatures: HashSet<&str> = record.signatures.iter().map(String::as_str).collect();
+        let tip_accounts: HashSet<Pubkey> = tip_accounts.iter().copied().collect();
+
+        let mut transactions = Vec::new();
+        let mut wallets: Vec<WalletReport> = Vec::new();
+        for (index, encoded) in block.transactions.into_iter().flatten().enumerate() {
+            let Some(decoded) = DecodedTransaction::decode(encoded) else {
+                continue;
+            };
+            let signature = decoded.transaction.signatures[0].to_string();
+            if !signatures.contains(signature.as_str()) {
+                continue;
+            }
+
+            let report = decoded.report(signature, index, &tip_accounts);
+            for wallet in decoded.wallets(&report) {
+                match wallets
+                    .iter_mut()
+                    .find(|known| known.wallet == wallet.wallet)
+                {
+                    Some(known) => known.merge(wallet),
+                    None => wallets.push(wallet),
+                }
+            }
+            transactions.push(report);
+        }
+
+        if transactions.len() != signatures.len() {
+            return Err(eyre::eyre!(
+                "Only {} of {} bundle transactions found in slot {}",
+                transactions.len(),
+                signatures.len(),
+                slot
+            ));
+        }
+
+        Ok(Self {
+            step: record.step.clone(),
+            uuid: record.uuid.clone(),
+            slot,
+            blockhash: block.blockhash,
+            block_time: block.block_time,
+            fees: transactions.iter().map(|tx| tx.fee).sum(),
+            tip: transactions.iter().map(|tx| tx.tip).sum(),
+            transactions,
+            wallets,
+        })
+    }
+}
+
+/// A transaction of the block with its meta and full account list.
+struct DecodedTransaction {
+    transaction: VersionedTransaction,
+    meta: UiTransactionStatusMeta,
+    /// Static keys followed by the writable and readonly keys loaded from LUTs.
+    account_keys: Vec<String>,
+}
+
+impl DecodedTransaction {
+    fn decode(encoded: EncodedTransactionWithStatusMeta) -> Option<Self> {
+        let transaction = encoded.transaction.decode()?;
+        let meta = encoded.meta?;
+
+        let mut account_keys: Vec<String> = transaction
+            .message
+            .static_account_keys()
+            .iter()
+            .map(Pubkey::to_string)
+            .collect();
+        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+            account_keys.extend(loaded.writable.iter().cloned());
+            account_keys.extend(loaded.readonly.iter().cloned());
+        }
+
+        Some(Self {
+            transaction,
+            meta,
+            account_keys,
+        })
+    }
+
+    /// Lamports account `index` gained, negative when it lost some.
+    fn lamport_change(&self, index: usize) -> i64 {
+        let pre = self
+            .meta
+            .pre_balances
+            .get(index)
+            .copied()
+            .unwrap_or_default();
+        let post = self
+            .meta
+            .post_balances
+            .get(index)
+            .copied()
+            .unwrap_or_default();
+        post as i64 - pre as i64
+    }
+
+    fn report(
+        &self,
+        signature: String,
+        index: usize,
+        tip_accounts: &HashSet<Pubkey>,
+    ) -> TransactionReport {
+        let tip = self
+            .account_keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| {
+                Pubkey::from_str(key).is_ok_and(|pubkey| tip_accounts.contains(&pubkey))
+            })
+            .map(|(index, _)| self.lamport_change(index).max(0) as u64)
+            .sum();
+
+        TransactionReport {
+            signature,
+            index,
+            fee_payer: self.account_keys[0].clone(),
+            fee: self.meta.fee,
+            tip,
+            error: self.meta.err.as_ref().map(|e| e.to_string()),
+            token_balances: self.token_balances(),
+        }
+    }
+
+    fn token_balances(&self) -> Vec<TokenBalanceChange> {
+        let pre: Vec<UiTransactionTokenBalance> =
+            Option::from(self.meta.pre_token_balances.clone()).unwrap_or_default();
+        let post: Vec<UiTransactionTokenBalance> =
+            Option::from(self.meta.post_token_balances.clone()).unwrap_or_default();
+
+        // Accounts created by the transaction have no pre balance, closed ones no post balance
+        let mut accounts: BTreeMap<u8, TokenBalanceChange> = BTreeMap::new();
+        for (balance, is_post) in pre
+            .iter()
+            .map(|balance| (balance, false))
+            .chain(post.iter().map(|balance| (balance, true)))
+        {
+            let change =
+                accounts
+                    .entry(balance.account_index)
+                    .or_insert_with(|| TokenBalanceChange {
+                        account: self
+                            .account_keys
+                            .get(balance.account_index as usize)
+                            .cloned()
+                            .unwrap_or_default(),
+                        owner: Option::from(balance.owner.clone()),
+                        mint: balance.mint.clone(),
+                        decimals: balance.ui_token_amount.decimals,
+                        pre: 0,
+                        post: 0,
+                    });
+            let amount = balance.ui_token_amount.amount.parse().unwrap_or_default();
+            match is_post {
+                true => change.post = amount,
+                false => change.pre = amount,
+            }
+        }
+
+        accounts.into_values().collect()
+    }
+
+    /// What each signer of the transaction spent and received.
+    fn wallets(&self, report: &TransactionReport) -> Vec<WalletReport> {
+        let signers = self.transaction.message.header().num_required_signatures as usize;
+
+        self.account_keys
+            .iter()
+            .take(signers)
+            .enumerate()
+            .map(|(index, wallet)| {
+                let mut tokens: Vec<TokenDelta> = Vec::new();
+                for balance in &report.token_balances {
+                    if balance.owner.as_ref() != Some(wallet) {
+                        continue;
+                    }
+                    add_token(
+                        &mut tokens,
+                        TokenDelta {
+                            mint: balance.mint.clone(),
+                            decimals: balance.decimals,
+                            change: balance.post as i128 - balance.pre as i128,
+                        },
+                    );
+                }
+
+                WalletReport {
+                    wallet: wallet.clone(),
+                    sol_spent: -self.lamport_change(index),
+                    fees: if index == 0 { report.fee } else { 0 },
+                    tokens,
+                }
+            })
+            .collect()
+    }
+}
+
+fn add_token(tokens: &mut Vec<TokenDelta>, delta: TokenDelta) {
+    match tokens.iter_mut().find(|token| token.mint == delta.mint) {
+        Some(token) => token.change += delta.change,
+        None => tokens.push(delta),
+    }
+}
+
+impl WalletReport {
+    fn merge(&mut self, other: WalletReport) {
+        self.sol_spent += other.sol_spent;
+        self.fees += other.fees;
+        for delta in other.tokens {
+            add_token(&mut self.tokens, delta);
+        }
+    }
+}
+
+fn ui_amount(amount: i128, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+impl fmt::Display for BundleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} bundle {} landed in slot {} ({})",
+            self.step,
+            self.uuid.as_deref().unwrap_or("-"),
+            self.slot,
+            self.blockhash
+        )?;
+        writeln!(
+            f,
+            "Fees {} SOL, tip {} SOL",
+            lamports_to_sol(self.fees),
+            lamports_to_sol(self.tip)
+        )?;
+
+        writeln!(f, "\nTransactions")?;
+        for tx in &self.transactions {
+            writeln!(
+                f,
+                "  #{:<5} {} fee {} tip {}{}",
+                tx.index,
+                tx.signature,
+                tx.fee,
+                tx.tip,
+                tx.error
+                    .as_ref()
+                    .map(|e| format!(" failed: {}", e))
+                    .unwrap_or_default()
+            )?;
+        }
+
+        writeln!(f, "\nWallets")?;
+        for wallet in &self.wallets {
+            write!(
+                f,
+                "  {:<44} spent {:>12.6} SOL",
+                wallet.wallet,
+                wallet.sol_spent as f64 / LAMPORTS_PER_SOL as f64
+            )?;
+            for token in &wallet.tokens {
+                write!(
+                    f,
+                    ", {:+.6} {}",
+                    ui_amount(token.change, token.decimals),
+                    token.mint
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports the launch bundle of a wallet folder picked from the menu.
+pub async fn report_main() -> eyre::Result<()> {
+    let settings = load_minter_settings().await?;
+    let (folder, _) = match list_folders().await {
+        Ok(folders) => folders,
+        Err(e) => {
+            eprintln!("Error listing folders: {}", e);
+            return Ok(());
+        }
+    };
+
+    write_report(&settings, &folder, "launch", None).await?;
+
+    Ok(())
+}
+
+/// Subfolder of a wallet folder holding the reports, kept apart from the wallet files.
+pub const REPORTS_DIR: &str = "reports";
+
+/// Where the report of `step` is written inside a wallet folder.
+pub fn report_path(wallet_folder: &str, step: &str) -> PathBuf {
+    PathBuf::from(wallet_folder)
+        .join(REPORTS_DIR)
+        .join(format!("{}.json", step))
+}
+
+/// Builds the report of the last bundle of `step` that landed for `manifest`'s folder.
+pub async fn bundle_report(
+    settings: &PoolDataSettings,
+    manifest: &LaunchManifest,
+    step: &str,
+) -> eyre::Result<BundleReport> {
+    let record = manifest
+        .bundles
+        .iter()
+        .rev()
+        .find(|record| record.step == step && record.landed)
+        .ok_or_else(|| eyre::eyre!("No landed {} bundle in the manifest", step))?;
+    let rpc_client = Arc::new(RpcClient::new(settings.rpc_url.clone()));
+
+    let slot = match record.slot {
+        Some(slot) => slot,
+        None => {
+            let signature = record
+                .signatures
+                .first()
+                .ok_or_else(|| eyre::eyre!("Bundle record without signatures"))?;
+            rpc_client
+                .get_signature_statuses_with_history(&[Signature::from_str(signature)?])
+                .await?
+                .value[0]
+                .as_ref()
+                .map(|status| status.slot)
+                .ok_or_else(|| eyre::eyre!("Transaction {} not found", signature))?
+        }
+    };
+
+    let block = rpc_client
+        .get_block_with_config(
+            slot,
+            RpcBlockConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                transaction_details: Some(TransactionDetails::Full),
+                rewards: Some(false),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+
+    BundleReport::from_block(record, slot, block, &known_tip_accounts(settings).await)
+}
+
+/// Writes the report of `step` for `wallet_folder` as JSON and prints its summary.
+pub async fn write_report(
+    settings: &PoolDataSettings,
+    wallet_folder: &str,
+    step: &str,
+    out: Option<PathBuf>,
+) -> eyre::Result<BundleReport> {
+    let manifest = LaunchManifest::load(wallet_folder)?;
+    let report = bundle_report(settings, &manifest, step).await?;
+
+    let path = save_report(&report, wallet_folder, out)?;
+
+    println!("{}", report);
+    println!("Report written to {}", path.display());
+
+    Ok(report)
+}
+
+/// Writes `report` as JSON to `out`, or to its [`report_path`] in `wallet_folder`.
+pub fn save_report(
+    report: &BundleReport,
+    wallet_folder: &str,
+    out: Option<PathBuf>,
+) -> eyre::Result<PathBuf> {
+    let path = out.unwrap_or_else(|| report_path(wallet_folder, &report.step));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(report)?)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_sdk::{
+        hash::Hash,
+        message::{v0, VersionedMessage},
+        signature::Keypair,
+        signer::Signer,
+        system_instruction,
+        transaction::TransactionError,
+    };
+    use solana_transaction_status::{
+        EncodedTransaction, TransactionBinaryEncoding, UiLoadedAddresses,
+    };
+
+    use super::*;
+
+    const FEE: u64 = 5_000;
+
+    fn transaction(
+        payer: &Keypair,
+        signers: &[&Keypair],
+        to: Pubkey,
+        lamports: u64,
+    ) -> VersionedTransaction {
+        let from = signers.last().copied().unwrap_or(payer);
+        let instruction = system_instruction::transfer(&from.pubkey(), &to, lamports);
+        let message =
+            v0::Message::try_compile(&payer.pubkey(), &[instruction], &[], Hash::new_unique())
+                .unwrap();
+        let mut keypairs = vec![payer];
+        keypairs.extend(signers);
+        VersionedTransaction::try_new(VersionedMessage::V0(message), &keypairs).unwrap()
+    }
+
+    fn token_balance(
+        account_index: u8,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> UiTransactionTokenBalance {
+        UiTransactionTokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: amount.to_string(),
+            },
+            owner: OptionSerializer::Some(owner.to_string()),
+            program_id: OptionSerializer::Some(spl_token::id().to_string()),
+        }
+    }
+
+    /// `transaction` in a block with the balances of its accounts before and after.
+    fn encoded(
+        transaction: &VersionedTransaction,
+        balances: &[(u64, u64)],
+        token_balances: (
+            Vec<UiTransactionTokenBalance>,
+            Vec<UiTransactionTokenBalance>,
+        ),
+        loaded: Option<UiLoadedAddresses>,
+        err: Option<TransactionError>,
+    ) -> EncodedTransactionWithStatusMeta {
+        let (pre_token_balances, post_token_balances) = token_balances;
+        EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Binary(
+                STANDARD.encode(bincode::serialize(transaction).unwrap()),
+                TransactionBinaryEncoding::Base64,
+            ),
+            meta: Some(UiTransactionStatusMeta {
+                err: err.clone(),
+                status: err.map_or(Ok(()), Err),
+                fee: FEE,
+                pre_balances: balances.iter().map(|(pre, _)| *pre).collect(),
+                post_balances: balances.iter().map(|(_, post)| *post).collect(),
+                inner_instructions: OptionSerializer::None,
+                log_messages: OptionSerializer::None,
+                pre_token_balances: OptionSerializer::Some(pre_token_balances),
+                post_token_balances: OptionSerializer::Some(post_token_balances),
+                rewards: OptionSerializer::None,
+                loaded_addresses: loaded.map_or(OptionSerializer::Skip, OptionSerializer::Some),
+                return_data: OptionSerializer::Skip,
+                compute_units_consumed: OptionSerializer::Skip,
+            }),
+            version: None,
+        }
+    }
+
+    fn block(transactions: Vec<EncodedTransactionWithStatusMeta>) -> UiConfirmedBlock {
+        UiConfirmedBlock {
+            previous_blockhash: Hash::new_unique().to_string(),
+            blockhash: Hash::new_unique().to_string(),
+            parent_slot: 41,
+            transactions: Some(transactions),
+            signatures: None,
+            rewards: None,
+            block_time: Some(1_700_000_000),
+            block_height: Some(40),
+        }
+    }
+
+    fn record(transactions: &[&VersionedTransaction]) -> BundleRecord {
+        BundleRecord {
+            step: "launch".to_string(),
+            attempt: 1,
+            tip: 1_000,
+            uuid: Some("bundle".to_string()),
+            signatures: transactions
+                .iter()
+                .map(|tx| tx.signatures[0].to_string())
+                .collect(),
+            landed: true,
+            expected_slot: None,
+            slot: Some(42),
+            landings: Vec::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn decodes_fees_tips_and_balances_of_bundle() {
+        let buyer = Keypair::new();
+        let wallet = Keypair::new();
+        // A live tip account, not one derived from the tip program
+        let tip_account = Pubkey::new_unique();
+        let (mint, token_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let other = transaction(&Keypair::new(), &[], Pubkey::new_unique(), 1);
+        // Keys: buyer, tip account, system program
+        let tip = transaction(&buyer, &[], tip_account, 1_000);
+        // Keys: buyer, wallet, recipient, system program, then the token account from a LUT
+        let buy = transaction(&buyer, &[&wallet], Pubkey::new_unique(), 2_000_000);
+
+        let block = block(vec![
+            encoded(
+                &other,
+                &[(10_000, 4_999), (0, 1), (1, 1)],
+                Default::default(),
+                None,
+                None,
+            ),
+            encoded(
+                &tip,
+                &[(10_000_000, 10_000_000 - 1_000 - FEE), (0, 1_000), (1, 1)],
+                Default::default(),
+                None,
+                None,
+            ),
+            encoded(
+                &buy,
+                &[
+                    (9_994_000, 9_994_000 - FEE),
+                    (3_000_000, 1_000_000),
+                    (0, 2_000_000),
+                    (1, 1),
+                    (2_039_280, 2_039_280),
+                ],
+                (
+                    Vec::new(),
+                    vec![token_balance(4, &mint, &wallet.pubkey(), 500)],
+                ),
+                Some(UiLoadedAddresses {
+                    writable: vec![token_account.to_string()],
+                    readonly: Vec::new(),
+                }),
+                None,
+            ),
+        ]);
+
+        let report =
+            BundleReport::from_block(&record(&[&tip, &buy]), 42, block, &[tip_account]).unwrap();
+
+        assert_eq!(report.slot, 42);
+        assert_eq!(report.block_time, Some(1_700_000_000));
+        assert_eq!(
+            report
+                .transactions
+                .iter()
+                .map(|tx| (tx.index, tx.tip, tx.fee))
+                .collect::<Vec<_>>(),
+            [(1, 1_000, FEE), (2, 0, FEE)]
+        );
+        assert_eq!(report.fees, 2 * FEE);
+        assert_eq!(report.tip, 1_000);
+
+        let balance = &report.transactions[1].token_balances[0];
+        assert_eq!(balance.account, token_account.to_string());
+        assert_eq!((balance.pre, balance.post), (0, 500));
+
+        let [buyer_report, wallet_report] = report.wallets.as_slice() else {
+            panic!(
+                "expected the buyer and the wallet, got {:?}",
+                report.wallets
+            );
+        };
+        assert_eq!(buyer_report.wallet, buyer.pubkey().to_string());
+        assert_eq!(buyer_report.sol_spent, (1_000 + 2 * FEE) as i64);
+        assert_eq!(buyer_report.fees, 2 * FEE);
+        assert_eq!(wallet_report.wallet, wallet.pubkey().to_string());
+        assert_eq!(wallet_report.sol_spent, 2_000_000);
+        assert_eq!(wallet_report.fees, 0);
+        assert_eq!(wallet_report.tokens.len(), 1);
+        assert_eq!(wallet_report.tokens[0].mint, mint.to_string());
+        assert_eq!(wallet_report.tokens[0].change, 500);
+    }
+
+    #[test]
+    fn reports_failed_transactions() {
+        let buyer = Keypair::new();
+        let tip_account = Pubkey::new_unique();
+        let tip = transaction(&buyer, &[], tip_account, 1_000);
+        let block = block(vec![encoded(
+            &tip,
+            &[(10_000, 10_000 - FEE), (0, 0), (1, 1)],
+            Default::default(),
+            None,
+            Some(TransactionError::InsufficientFundsForFee),
+        )]);
+
+        let report = BundleReport::from_block(&record(&[&tip]), 42, block, &[tip_account]).unwrap();
+
+        assert_eq!(report.tip, 0);
+        assert!(report.transactions[0].error.is_some());
+        assert_eq!(report.wallets[0].sol_spent, FEE as i64);
+    }
+
+    #[test]
+    fn fails_when_bundle_transaction_is_missing() {
+        let buyer = Keypair::new();
+        let tip = transaction(&buyer, &[], Pubkey::new_unique(), 1_000);
+        let missing = transaction(&buyer, &[], Pubkey::new_unique(), 1_000);
+        let block = block(vec![encoded(
+            &tip,
+            &[(10_000, 10_000 - FEE), (0, 0), (1, 1)],
+            Default::default(),
+            None,
+            None,
+        )]);
+
+        let error =
+            BundleReport::from_block(&record(&[&tip, &missing]), 42, block, &[]).unwrap_err();
+
+        assert!(error.to_string().contains("Only 1 of 2"), "{}", error);
+    }
+}
@@ -0,0 +1,226 @@
+//! An RPC node and block engine in process, to run the modes end to end in tests.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jito_mock_block_engine::{
+    JsonRpcReply, MockBlockEngine, MockConfig, MockJsonRpc, ScriptedResult,
+};
+use serde_json::{json, Value};
+use solana_sdk::{
+    account::Account,
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LookupTableMeta},
+    },
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+
+use crate::env::{
+    jito_auth::{generate_tip_accounts, tip_program_id},
+    test_settings, PoolDataSettings,
+};
+
+/// Slot every bundle sent to a [`MockCluster`] lands in.
+pub(crate) const LANDED_SLOT: u64 = 42;
+
+fn with_context(value: Value) -> JsonRpcReply {
+    JsonRpcReply::Result(json!({ "context": { "slot": LANDED_SLOT }, "value": value }))
+}
+
+fn landed_status() -> Value {
+    json!({
+        "slot": LANDED_SLOT,
+        "confirmations": null,
+        "status": { "Ok": null },
+        "err": null,
+        "confirmationStatus": "confirmed",
+    })
+}
+
+/// An account as `getAccountInfo` and `getMultipleAccounts` return it.
+fn ui_account(account: &Account) -> Value {
+    json!({
+        "data": [STANDARD.encode(&account.data), "base64"],
+        "executable": account.executable,
+        "lamports": account.lamports,
+        "owner": account.owner.to_string(),
+        "rentEpoch": account.rent_epoch,
+        "space": account.data.len(),
+    })
+}
+
+/// A lookup table account holding `addresses`.
+pub(crate) fn lookup_table(addresses: Vec<Pubkey>) -> Account {
+    let data = AddressLookupTable {
+        meta: LookupTableMeta::default(),
+        addresses: addresses.into(),
+    }
+    .serialize_for_tests()
+    .unwrap();
+
+    Account {
+        lamports: 1_000_000,
+        data,
+        owner: address_lookup_table::program::id(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub(crate) struct MockCluster {
+    pub(crate) rpc: MockJsonRpc,
+    pub(crate) engine: MockBlockEngine,
+}
+
+impl MockCluster {
+    /// Starts a block engine landing every bundle and an RPC node confirming them.
+    pub(crate) async fn start() -> Self {
+        let rpc = MockJsonRpc::start().await.unwrap();
+        rpc.script(
+            "getVersion",
+            vec![JsonRpcReply::Result(
+                json!({ "solana-core": "1.17.20", "feature-set": 0 }),
+            )],
+        );
+        rpc.script(
+            "getLatestBlockhash",
+            vec![with_context(json!({
+                "blockhash": Hash::new_unique().to_string(),
+                "lastValidBlockHeight": 1_000,
+            }))],
+        );
+        // A bundle has at most five transactions
+        rpc.script(
+            "getSignatureStatuses",
+            vec![with_context(json!(vec![landed_status(); 5]))],
+        );
+
+        let engine = MockBlockEngine::start(MockConfig {
+            tip_accounts: generate_tip_accounts(&tip_program_id())
+                .iter()
+                .map(Pubkey::to_string)
+                .collect(),
+            default_script: ScriptedResult::landed(LANDED_SLOT, "leader"),
+            ..MockConfig::default()
+        })
+        .await
+        .unwrap();
+
+        Self { rpc, engine }
+    }
+
+    /// Settings pointing at this cluster, with fresh deployer and buyer keys.
+    pub(crate) fn settings(&self) -> PoolDataSettings {
+        test_settings(json!({
+            "RPC-URL": self.rpc.url(),
+            "BLOCK-ENGINE-URL": self.engine.url(),
+        }))
+        .unwrap()
+    }
+
+    /// Answers `getBalance` with `lamports` for every account.
+    pub(crate) fn set_balance(&self, lamports: u64) {
+        self.rpc
+            .script("getBalance", vec![with_context(json!(lamports))]);
+    }
+
+    /// Answers `getTokenAccountBalance` with `amount` for every account.
+    pub(crate) fn set_token_balance(&self, amount: u64) {
+        self.rpc.script(
+            "getTokenAccountBalance",
+            vec![with_context(json!({
+                "amount": amount.to_string(),
+                "decimals": 9,
+                "uiAmount": null,
+                "uiAmountString": amount.to_string(),
+            }))],
+        );
+    }
+
+    /// Answers `getAccountInfo` with a lookup table holding `addresses`.
+    pub(crate) fn set_lookup_table(&self, addresses: Vec<Pubkey>) {
+        self.rpc.script(
+            "getAccountInfo",
+            vec![with_context(ui_account(&lookup_table(addresses)))],
+        );
+    }
+
+    /// Answers `getMultipleAccounts` from `accounts`, every other account does not exist.
+    pub(crate) fn set_accounts(&self, accounts: HashMap<Pubkey, Account>) {
+        self.rpc.answer("getMultipleAccounts", move |params| {
+            let accounts: Vec<Value> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pubkey| {
+                    let pubkey: Pubkey = pubkey.as_str().unwrap().parse().unwrap();
+                    accounts.get(&pubkey).map_or(Value::Null, ui_account)
+                })
+                .collect();
+            with_context(json!(accounts))
+        });
+    }
+
+    /// Answers `getSignatureStatuses` with a confirmed status for the signatures `landed`
+    /// accepts and none for the others.
+    pub(crate) fn set_landed(&self, landed: impl Fn(&Signature) -> bool + Send + Sync + 'static) {
+        self.rpc.answer("getSignatureStatuses", move |params| {
+            let statuses: Vec<Value> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|signature| {
+                    let signature: Signature = signature.as_str().unwrap().parse().unwrap();
+                    match landed(&signature) {
+                        true => landed_status(),
+                        false => Value::Null,
+                    }
+                })
+                .collect();
+            with_context(json!(statuses))
+        });
+    }
+
+    /// Lands every transaction sent with `sendTransaction`, except from the `failing`-th
+    /// call on, counting from 0, which the node refuses.
+    ///
+    /// Replaces the statuses of [`start`](Self::start): only those transactions are on chain.
+    pub(crate) fn land_rpc_transactions(&self, failing: Option<usize>) {
+        let sent = Arc::new(Mutex::new(HashSet::new()));
+        let calls = Mutex::new(0);
+
+        let landed = sent.clone();
+        self.set_landed(move |signature| landed.lock().unwrap().contains(signature));
+        self.rpc.answer("sendTransaction", move |params| {
+            let mut calls = calls.lock().unwrap();
+            *calls += 1;
+            if failing.is_some_and(|failing| *calls > failing) {
+                return JsonRpcReply::Error {
+                    code: -32002,
+                    message: "Transaction simulation failed".to_string(),
+                };
+            }
+
+            let data = STANDARD.decode(params[0].as_str().unwrap()).unwrap();
+            let transaction: VersionedTransaction = bincode::deserialize(&data).unwrap();
+            sent.lock().unwrap().insert(transaction.signatures[0]);
+            JsonRpcReply::Result(json!(transaction.signatures[0].to_string()))
+        });
+    }
+
+    /// Transactions of every bundle the block engine received, in order.
+    pub(crate) fn bundles(&self) -> Vec<Vec<VersionedTransaction>> {
+        self.engine
+            .bundles()
+            .into_iter()
+            .map(|bundle| bundle.transactions)
+            .collect()
+    }
+}
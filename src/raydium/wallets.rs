@@ -1,17 +1,22 @@
-use std::{error::Error, fs};
+use std::{error::Error, fs, path::Path};
 
 use demand::{Confirm, DemandOption, Input, Select};
 use log::info;
 use serde_json::Value;
-use solana_sdk::signature::Keypair;
+use solana_sdk::{signature::Keypair, signer::Signer};
 
-pub fn generate_wallets(count: i32) -> Vec<String> {
+use crate::{
+    env::{encrypt_settings, keystore, secret::SecretString, SETTINGS_FILE},
+    raydium::manifest::LaunchManifest,
+};
+
+pub fn generate_wallets(count: i32) -> Vec<Keypair> {
     let mut wallet: Vec<Keypair> = vec![];
     for _ in 0..count {
         wallet.push(Keypair::new());
     }
 
-    wallet.iter().map(|x| x.to_base58_string()).collect()
+    wallet
 }
 
 pub async fn wallets_main() -> eyre::Result<()> {
@@ -48,18 +53,43 @@ pub async fn wallets_main() -> eyre::Result<()> {
         }
     }
 
-    let wallets = generate_wallets(amount as i32);
+    let encrypt = Confirm::new("Keystore")
+        .description("Encrypt the wallet files with the keystore passphrase?")
+        .affirmative("Yes")
+        .negative("No")
+        .run()
+        .unwrap();
+
+    save_wallets(&folder_name, amount, encrypt)?;
+
+    Ok(())
+}
+
+/// Generates `count` wallets and writes them to `folder_name` as `wallet_N.json`.
+///
+/// With `encrypt` set the keys are written as keystore entries instead of plaintext.
+pub fn save_wallets(folder_name: &str, count: u64, encrypt: bool) -> eyre::Result<()> {
+    let wallets = generate_wallets(count as i32);
 
     info!("Generating {} wallets", wallets.len());
 
-    fs::create_dir_all(folder_name.clone())?;
+    fs::create_dir_all(folder_name)?;
 
     for (i, wallet) in wallets.iter().enumerate() {
         let path = format!("{}/wallet_{}.json", folder_name, i + 1);
-        let data = serde_json::to_string(&wallet)?;
+        let secret = SecretString::new(wallet.to_base58_string());
+        let data = if encrypt {
+            serde_json::to_string(&keystore::encrypt(secret.expose())?)?
+        } else {
+            serde_json::to_string(secret.expose())?
+        };
         fs::write(path, data)?;
     }
 
+    let mut manifest = LaunchManifest::load(folder_name)?;
+    manifest.wallets = wallets.iter().map(|x| x.pubkey().to_string()).collect();
+    manifest.save()?;
+
     info!("{} Wallets saved to {} folder", wallets.len(), folder_name);
 
     Ok(())
@@ -106,27 +136,153 @@ pub async fn list_folders() -> Result<(String, Vec<Keypair>), Box<dyn Error>> {
 
     println!("Selected: {}", selected_option);
 
-    let mut json_values = Vec::new();
+    let wallets = read_wallets(selected_option)?;
+
+    Ok((selected_option.clone(), wallets))
+}
 
-    let json_paths = fs::read_dir(selected_option)?;
+/// Number `N` of a `wallet_N.json` file, `None` for any other file.
+fn wallet_index(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("wallet_")?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
 
-    for json_path in json_paths {
+/// Reads every wallet stored in `folder_name` without prompting, in wallet order.
+///
+/// Only `wallet_N.json` files are read, the manifest and reports next to them are not.
+pub fn read_wallets(folder_name: &str) -> eyre::Result<Vec<Keypair>> {
+    let mut wallet_paths = Vec::new();
+    for json_path in fs::read_dir(folder_name)? {
         let json_path = json_path?.path();
-        if json_path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let json_str = fs::read_to_string(json_path)?;
-            let json_value: Value = serde_json::from_str(&json_str)?;
-            json_values.push(json_value);
+        if let Some(index) = wallet_index(&json_path) {
+            wallet_paths.push((index, json_path));
         }
     }
+    wallet_paths.sort();
 
     let mut wallets = Vec::new();
-    json_values.iter().for_each(|x| {
-        let keypair = Keypair::from_base58_string(x.as_str().unwrap());
-        // println!("Wallet: {:?}", keypair.pubkey());
-        // let associated = get_associated_token_address(&keypair.pubkey(), &SOLC_MINT);
-        // println!("Associated: {:?}", associated);
-        wallets.push(keypair);
-    });
+    for (_, json_path) in wallet_paths {
+        let json_value: Value = serde_json::from_str(&fs::read_to_string(&json_path)?)?;
+        let entry = json_value
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("{} is not a wallet file", json_path.display()))?;
+        wallets.push(keystore::reveal(entry)?.keypair());
+    }
 
-    Ok((selected_option.clone(), wallets))
+    Ok(wallets)
+}
+
+pub async fn encrypt_main() -> eyre::Result<()> {
+    let (folder_name, _) = match list_folders().await {
+        Ok(folders) => folders,
+        Err(e) => {
+            eprintln!("Error listing folders: {}", e);
+            return Ok(());
+        }
+    };
+
+    let wallets = encrypt_wallets(&folder_name)?;
+    info!("Encrypted {} wallets in {} folder", wallets, folder_name);
+
+    let keys = encrypt_settings(SETTINGS_FILE)?;
+    info!("Encrypted {} keys in {}", keys, SETTINGS_FILE);
+
+    Ok(())
+}
+
+/// Rewrites every plaintext wallet in `folder_name` as a keystore entry.
+///
+/// Already encrypted wallets are left untouched. Returns the number of wallets
+/// that were encrypted.
+pub fn encrypt_wallets(folder_name: &str) -> eyre::Result<usize> {
+    let mut encrypted = 0;
+
+    for json_path in fs::read_dir(folder_name)? {
+        let json_path = json_path?.path();
+        if wallet_index(&json_path).is_none() {
+            continue;
+        }
+
+        let json_value: Value = serde_json::from_str(&fs::read_to_string(&json_path)?)?;
+        let Some(secret) = json_value.as_str() else {
+            continue;
+        };
+        if keystore::is_encrypted(secret) {
+            continue;
+        }
+
+        let entry = keystore::encrypt(secret)?;
+        if keystore::reveal(&entry)?.expose() != secret {
+            return Err(eyre::eyre!(
+                "Keystore round trip failed for {}",
+                json_path.display()
+            ));
+        }
+
+        // Write next to the original and rename so a crash never leaves a half written key
+        let tmp_path = json_path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string(&entry)?)?;
+        fs::rename(&tmp_path, &json_path)?;
+
+        encrypted += 1;
+    }
+
+    Ok(encrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raydium::report::{save_report, BundleReport};
+
+    #[test]
+    fn reads_wallets_next_to_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().to_str().unwrap();
+        save_wallets(folder, 12, false).unwrap();
+
+        let report = BundleReport {
+            step: "launch".to_string(),
+            uuid: None,
+            slot: 42,
+            blockhash: String::new(),
+            block_time: None,
+            transactions: Vec::new(),
+            wallets: Vec::new(),
+            fees: 5_000,
+            tip: 10_000,
+        };
+        save_report(&report, folder, None).unwrap();
+        // Reports used to be written next to the wallets
+        fs::write(
+            dir.path().join("report_launch.json"),
+            serde_json::to_string(&serde_json::json!({ "step": "launch" })).unwrap(),
+        )
+        .unwrap();
+
+        let wallets: Vec<String> = read_wallets(folder)
+            .unwrap()
+            .iter()
+            .map(|wallet| wallet.pubkey().to_string())
+            .collect();
+
+        // In wallet order, wallet_10 after wallet_9
+        assert_eq!(wallets, LaunchManifest::load(folder).unwrap().wallets);
+    }
+
+    #[test]
+    fn rejects_malformed_wallet_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().to_str().unwrap();
+        fs::write(dir.path().join("wallet_1.json"), "{}").unwrap();
+
+        let err = read_wallets(folder).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("wallet_1.json is not a wallet file"));
+    }
 }